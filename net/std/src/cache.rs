@@ -0,0 +1,145 @@
+use rwb_core::http::CacheEntry;
+use rwb_core::http::CacheStorage;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Keeps cache entries as files in a directory, so that they outlive the
+/// process. Each file holds the key on its first line followed by the
+/// entry as `CacheEntry::to_bytes` writes it.
+///
+/// Failing to read or write a file is treated as a cache miss; the cache
+/// only makes fetching faster.
+#[derive(Debug, Clone)]
+pub struct DiskStorage {
+    dir: PathBuf,
+}
+
+impl DiskStorage {
+    /// Uses `dir` for the entries, creating it if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> PathBuf {
+        self.dir.clone()
+    }
+
+    /// Removes every entry.
+    pub fn clear(&mut self) -> io::Result<()> {
+        for file in fs::read_dir(&self.dir)? {
+            let path = file?.path();
+            if path.extension().is_some_and(|ext| ext == "entry") {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        // キーは URL なのでファイル名には使えない。衝突はファイル内のキーで見分ける
+        self.dir
+            .join(format!("{:016x}.entry", fnv1a(key.as_bytes())))
+    }
+}
+
+impl CacheStorage for DiskStorage {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let bytes = fs::read(self.path(key)).ok()?;
+        let newline = bytes.iter().position(|b| *b == b'\n')?;
+        if &bytes[..newline] != key.as_bytes() {
+            return None;
+        }
+        CacheEntry::from_bytes(&bytes[newline + 1..]).ok()
+    }
+
+    fn put(&mut self, key: &str, entry: CacheEntry) {
+        let mut bytes = key.as_bytes().to_vec();
+        bytes.push(b'\n');
+        bytes.extend_from_slice(&entry.to_bytes());
+        // 書きかけのファイルを読まないように、別名で書いてから置き換える
+        let path = self.path(key);
+        let temp = path.with_extension("tmp");
+        if fs::write(&temp, bytes).is_err() || fs::rename(&temp, &path).is_err() {
+            let _ = fs::remove_file(temp);
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        let _ = fs::remove_file(self.path(key));
+    }
+}
+
+/// 64-bit FNV-1a.
+///
+/// http://www.isthe.com/chongo/tech/comp/fnv/index.html
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rwb_core::http::HttpCache;
+    use rwb_core::http::HttpRequest;
+    use rwb_core::http::HttpResponse;
+    use rwb_core::url::Url;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rwb-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn get(url: &str) -> HttpRequest {
+        let url = Url::new(url.to_string()).unwrap();
+        HttpRequest::builder_for_url("GET", &url).build().unwrap()
+    }
+
+    #[test]
+    fn test_entries_survive_reopening() {
+        let dir = temp_dir("cache");
+        let request = get("http://a.test/page");
+        let mut sent = 0;
+        let mut send = |_: &HttpRequest| {
+            sent += 1;
+            HttpResponse::new(b"HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\n\r\nhi".to_vec())
+        };
+
+        let mut cache = HttpCache::new(DiskStorage::new(&dir).unwrap());
+        assert_eq!(
+            cache.fetch(&request, 1000, &mut send).unwrap().body(),
+            b"hi"
+        );
+
+        // 開き直しても新鮮なうちはサーバに問い合わせない
+        let mut cache = HttpCache::new(DiskStorage::new(&dir).unwrap());
+        assert_eq!(
+            cache.fetch(&request, 1010, &mut send).unwrap().body(),
+            b"hi"
+        );
+        assert_eq!(sent, 1);
+
+        cache.storage_mut().clear().unwrap();
+        assert!(cache.storage().get("http://a.test/page").is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_key_is_checked() {
+        let dir = temp_dir("collision");
+        let storage = DiskStorage::new(&dir).unwrap();
+        // 別のキーの内容が同じ名前のファイルにあっても返さない
+        fs::write(
+            storage.path("http://a.test/"),
+            b"http://b.test/\nRWB-CACHE/1 0 0\r\n\r\nHTTP/1.1 200 OK\r\n\r\n",
+        )
+        .unwrap();
+        assert!(storage.get("http://a.test/").is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+use crate::tls;
+use crate::tls::RootStore;
+use crate::tls::TlsStream;
+use rustls::ClientConfig;
+use rwb_core::error::Error;
+use rwb_core::error::NetworkError;
+use rwb_core::transport::Connection;
+use rwb_core::transport::Transport;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::net::IpAddr;
+use std::net::Shutdown;
+use std::net::SocketAddr;
+use std::net::TcpStream;
+use std::net::ToSocketAddrs;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+/// `rwb_core`'s client over the standard library's sockets.
+pub type HttpClient = rwb_core::http::HttpClient<StdTransport>;
+
+/// How long connecting, and each read and write, may take by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connects with `std::net`, resolving names with the system resolver, and
+/// sets up TLS with rustls.
+#[derive(Debug, Clone)]
+pub struct StdTransport {
+    timeout: Option<Duration>,
+    tls: Arc<ClientConfig>,
+}
+
+impl StdTransport {
+    /// Returns a transport that trusts `RootStore::mozilla()`.
+    pub fn new() -> Self {
+        Self {
+            timeout: Some(DEFAULT_TIMEOUT),
+            tls: tls::client_config(&RootStore::mozilla()),
+        }
+    }
+
+    /// Sets the root certificates that servers are verified against.
+    pub fn set_root_store(&mut self, roots: &RootStore) {
+        self.tls = tls::client_config(roots);
+    }
+
+    /// Sets how long connecting, and each read and write, may take before
+    /// failing with `NetworkError::Timeout`. `None` waits forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Opens a TCP connection with the timeouts set.
+    fn connect_tcp(&self, address: &IpAddr, port: u16) -> Result<TcpStream, Error> {
+        let addr = SocketAddr::new(*address, port);
+        let stream = match self.timeout {
+            Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+            None => TcpStream::connect(addr),
+        }
+        .map_err(|e| {
+            network_error(e, |message| {
+                NetworkError::Connect(format!("{}: {}", addr, message))
+            })
+        })?;
+        stream
+            .set_read_timeout(self.timeout)
+            .and_then(|_| stream.set_write_timeout(self.timeout))
+            .map_err(|e| NetworkError::Connect(format!("{}: {}", addr, e)))?;
+        Ok(stream)
+    }
+}
+
+impl Default for StdTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for StdTransport {
+    type Address = IpAddr;
+    type Connection = StdConnection;
+
+    fn resolve(&self, host: &str) -> Result<Vec<IpAddr>, Error> {
+        // ポートは connect で付けるので、ここでは何でもよい
+        let addrs = (host, 0)
+            .to_socket_addrs()
+            .map_err(|e| NetworkError::Dns(format!("{}: {}", host, e)))?;
+        let ips: Vec<IpAddr> = addrs.map(|addr| addr.ip()).collect();
+        if ips.is_empty() {
+            return Err(NetworkError::Dns(format!("{}: no addresses", host)).into());
+        }
+        Ok(ips)
+    }
+
+    fn connect(&self, address: &IpAddr, port: u16) -> Result<StdConnection, Error> {
+        let stream = self.connect_tcp(address, port)?;
+        Ok(StdConnection(Stream::Plain(stream)))
+    }
+
+    fn connect_tls(
+        &self,
+        address: &IpAddr,
+        port: u16,
+        server_name: &str,
+    ) -> Result<StdConnection, Error> {
+        let stream = self.connect_tcp(address, port)?;
+        let stream = tls::handshake(self.tls.clone(), server_name, stream)?;
+        Ok(StdConnection(Stream::Tls(Box::new(stream))))
+    }
+}
+
+pub struct StdConnection(Stream);
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream>),
+}
+
+impl StdConnection {
+    /// Returns true if the connection uses TLS.
+    pub fn is_secure(&self) -> bool {
+        matches!(self.0, Stream::Tls(_))
+    }
+
+    /// Returns the protocol the server chose with ALPN, if it chose one.
+    pub fn alpn_protocol(&self) -> Option<Vec<u8>> {
+        match &self.0 {
+            Stream::Plain(_) => None,
+            Stream::Tls(stream) => stream.conn.alpn_protocol().map(|p| p.to_vec()),
+        }
+    }
+
+    fn socket(&self) -> &TcpStream {
+        match &self.0 {
+            Stream::Plain(stream) => stream,
+            Stream::Tls(stream) => &stream.sock,
+        }
+    }
+}
+
+impl Connection for StdConnection {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        loop {
+            let result = match &mut self.0 {
+                Stream::Plain(stream) => stream.read(buf),
+                Stream::Tls(stream) => stream.read(buf),
+            };
+            match result {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                // close_notify を送らずに閉じるサーバは多いので、単に閉じたものとする。
+                // 本文が途中で切れていればレスポンスの解析で分かる
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(0),
+                result => return result.map_err(|e| tls::stream_error(e, NetworkError::Read)),
+            }
+        }
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        loop {
+            let result = match &mut self.0 {
+                Stream::Plain(stream) => stream.write(buf),
+                Stream::Tls(stream) => stream.write(buf).and_then(|n| stream.flush().map(|_| n)),
+            };
+            match result {
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                result => return result.map_err(|e| tls::stream_error(e, NetworkError::Write)),
+            }
+        }
+    }
+
+    fn close(&mut self) -> Result<(), Error> {
+        if let Stream::Tls(stream) = &mut self.0 {
+            // 届かなくても構わないので、close_notify の送信の失敗は無視する
+            stream.conn.send_close_notify();
+            let _ = stream.conn.complete_io(&mut stream.sock);
+        }
+        match self.socket().shutdown(Shutdown::Both) {
+            // 相手が先に閉じていれば何もしなくてよい
+            Err(e) if e.kind() != io::ErrorKind::NotConnected => {
+                Err(NetworkError::Write(e.to_string()).into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Maps an I/O error to `NetworkError::Timeout` if a timeout expired, and
+/// to `kind` with its message otherwise.
+pub(crate) fn network_error(e: io::Error, kind: impl Fn(String) -> NetworkError) -> Error {
+    match e.kind() {
+        // タイムアウトは OS によって WouldBlock で報告される
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => NetworkError::Timeout.into(),
+        _ => kind(e.to_string()).into(),
+    }
+}
+
+/// Milliseconds since the Unix epoch, for `HttpClient::set_clock`.
+pub fn system_clock() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Answers each connection to a local port with the next of `responses`
+    /// and returns the port and what each request was.
+    fn serve(responses: Vec<&'static [u8]>) -> (u16, thread::JoinHandle<Vec<Vec<u8>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                stream.write_all(response).unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+        (port, handle)
+    }
+
+    #[test]
+    fn test_get() {
+        let (port, server) = serve(vec![
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        ]);
+        let client = HttpClient::new(StdTransport::new());
+        let response = client
+            .get("127.0.0.1".to_string(), port, "/index.html".to_string())
+            .unwrap();
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.body(), b"hello");
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with(b"GET /index.html HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_read_until_close() {
+        let (port, server) = serve(vec![b"HTTP/1.0 200 OK\r\n\r\nuntil close"]);
+        let client = HttpClient::new(StdTransport::new());
+        let response = client
+            .get("localhost".to_string(), port, "/".to_string())
+            .unwrap();
+        assert_eq!(response.text(), Ok("until close".to_string()));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_connect_error() {
+        // 待ち受けていないポートに繋ぐ
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = HttpClient::new(StdTransport::new());
+        let result = client.get("127.0.0.1".to_string(), port, "/".to_string());
+        assert!(
+            matches!(result, Err(Error::Network(NetworkError::Connect(_)))),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let mut transport = StdTransport::new();
+        transport.set_timeout(Some(Duration::from_millis(50)));
+        let client = HttpClient::new(transport);
+        // 接続は受け付けるが何も返さない
+        let result = client.get("127.0.0.1".to_string(), port, "/".to_string());
+        assert_eq!(result.err(), Some(Error::Network(NetworkError::Timeout)));
+        drop(listener);
+    }
+
+    #[test]
+    fn test_dns_error() {
+        let result = StdTransport::new().resolve("no-such-host.invalid");
+        assert!(
+            matches!(result, Err(Error::Network(NetworkError::Dns(_)))),
+            "{:?}",
+            result
+        );
+    }
+}
@@ -0,0 +1,399 @@
+//! TLS for https, with rustls. The server's certificate is verified against
+//! a `RootStore`, the host name is sent with SNI, and only "http/1.1" is
+//! offered with ALPN because that is all `HttpClient` speaks.
+
+use crate::http::network_error;
+use rustls::ClientConfig;
+use rustls::ClientConnection;
+use rustls::OwnedTrustAnchor;
+use rustls::RootCertStore;
+use rustls::ServerName;
+use rustls::StreamOwned;
+use rwb_core::error::CertificateError;
+use rwb_core::error::Error;
+use rwb_core::error::NetworkError;
+use rwb_core::error::TlsError;
+use std::io;
+use std::net::TcpStream;
+use std::sync::Arc;
+
+/// The protocols offered with ALPN.
+///
+/// https://www.rfc-editor.org/rfc/rfc7301
+pub const ALPN_PROTOCOLS: &[&[u8]] = &[b"http/1.1"];
+
+pub(crate) type TlsStream = StreamOwned<ClientConnection, TcpStream>;
+
+/// The root certificates that a server's certificate chain must lead to.
+#[derive(Debug, Clone)]
+pub struct RootStore {
+    roots: RootCertStore,
+}
+
+impl RootStore {
+    /// Returns a store that trusts no server. Add roots with `add_pem`.
+    pub fn new() -> Self {
+        Self {
+            roots: RootCertStore::empty(),
+        }
+    }
+
+    /// Returns the root certificates that Mozilla trusts, as Firefox does.
+    pub fn mozilla() -> Self {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+        Self { roots }
+    }
+
+    /// Adds the certificates in `pem`, e.g. the contents of a CA bundle, and
+    /// returns how many there were. Fails with `CertificateError::Invalid`
+    /// if one of them cannot be parsed.
+    pub fn add_pem(&mut self, pem: &[u8]) -> Result<usize, Error> {
+        let invalid = |message: String| TlsError::Certificate(CertificateError::Invalid(message));
+        let certificates =
+            rustls_pemfile::certs(&mut &pem[..]).map_err(|e| invalid(e.to_string()))?;
+        for der in &certificates {
+            self.roots
+                .add(&rustls::Certificate(der.clone()))
+                .map_err(|e| invalid(e.to_string()))?;
+        }
+        Ok(certificates.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.roots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roots.is_empty()
+    }
+}
+
+impl Default for RootStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the settings that connections trusting `roots` are made with.
+pub(crate) fn client_config(roots: &RootStore) -> Arc<ClientConfig> {
+    let mut config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots.roots.clone())
+        .with_no_client_auth();
+    config.alpn_protocols = ALPN_PROTOCOLS.iter().map(|p| p.to_vec()).collect();
+    Arc::new(config)
+}
+
+/// Sets up TLS on `socket`. The handshake is finished here rather than on
+/// the first write, so that `connect_tls` is what reports a bad certificate.
+pub(crate) fn handshake(
+    config: Arc<ClientConfig>,
+    server_name: &str,
+    mut socket: TcpStream,
+) -> Result<TlsStream, Error> {
+    // IP アドレスなら SNI は送られず、証明書の IP アドレスと照合される
+    let name = ServerName::try_from(server_name)
+        .map_err(|_| TlsError::Handshake(format!("invalid server name {}", server_name)))?;
+    let mut connection =
+        ClientConnection::new(config, name).map_err(|e| TlsError::Handshake(e.to_string()))?;
+    while connection.is_handshaking() {
+        connection
+            .complete_io(&mut socket)
+            .map_err(|e| match rustls_error(&e) {
+                Some(e) => handshake_error(e, server_name).into(),
+                None if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    TlsError::Handshake("connection closed".to_string()).into()
+                }
+                None => network_error(e, |message| {
+                    NetworkError::Connect(format!("{}: {}", server_name, message))
+                }),
+            })?;
+    }
+    Ok(StreamOwned::new(connection, socket))
+}
+
+/// Maps an I/O error from a connection with TLS set up. rustls reports
+/// invalid TLS data as `InvalidData` wrapping its own error.
+pub(crate) fn stream_error(e: io::Error, kind: impl Fn(String) -> NetworkError) -> Error {
+    match rustls_error(&e) {
+        Some(e) => TlsError::Protocol(e.to_string()).into(),
+        None => network_error(e, kind),
+    }
+}
+
+fn rustls_error(e: &io::Error) -> Option<rustls::Error> {
+    e.get_ref()?.downcast_ref::<rustls::Error>().cloned()
+}
+
+fn handshake_error(e: rustls::Error, server_name: &str) -> TlsError {
+    let e = match e {
+        rustls::Error::InvalidCertificate(e) => e,
+        e => return TlsError::Handshake(e.to_string()),
+    };
+    TlsError::Certificate(match e {
+        rustls::CertificateError::UnknownIssuer => CertificateError::UnknownIssuer,
+        rustls::CertificateError::Expired => CertificateError::Expired,
+        rustls::CertificateError::NotValidYet => CertificateError::NotValidYet,
+        rustls::CertificateError::NotValidForName => {
+            CertificateError::NotValidForName(server_name.to_string())
+        }
+        rustls::CertificateError::Revoked => CertificateError::Revoked,
+        e => CertificateError::Invalid(format!("{:?}", e)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::HttpClient;
+    use crate::http::StdTransport;
+    use rcgen::BasicConstraints;
+    use rcgen::Certificate;
+    use rcgen::CertificateParams;
+    use rcgen::DnType;
+    use rcgen::IsCa;
+    use rustls::ServerConfig;
+    use rustls::ServerConnection;
+    use rwb_core::transport::Connection;
+    use rwb_core::transport::Transport;
+    use rwb_core::url::Url;
+    use std::io::Read;
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+
+    const RESPONSE: &[u8] =
+        b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\nsecure";
+
+    /// A test CA and a server certificate it issued for "localhost".
+    struct TestPki {
+        ca_pem: String,
+        certificate: Vec<u8>,
+        key: Vec<u8>,
+    }
+
+    impl TestPki {
+        fn new(expired: bool) -> Self {
+            let mut params = CertificateParams::new(Vec::new());
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "rwb test CA");
+            let ca = Certificate::from_params(params).unwrap();
+
+            let mut params = CertificateParams::new(vec!["localhost".to_string()]);
+            if expired {
+                params.not_before = rcgen::date_time_ymd(2000, 1, 1);
+                params.not_after = rcgen::date_time_ymd(2001, 1, 1);
+            }
+            let server = Certificate::from_params(params).unwrap();
+            Self {
+                ca_pem: ca.serialize_pem().unwrap(),
+                certificate: server.serialize_der_with_signer(&ca).unwrap(),
+                key: server.serialize_private_key_der(),
+            }
+        }
+
+        fn roots(&self) -> RootStore {
+            let mut roots = RootStore::new();
+            assert_eq!(roots.add_pem(self.ca_pem.as_bytes()), Ok(1));
+            roots
+        }
+    }
+
+    /// What the server saw of a connection.
+    #[derive(Debug, Default)]
+    struct Served {
+        server_name: Option<String>,
+        alpn_protocol: Option<Vec<u8>>,
+        request: Vec<u8>,
+    }
+
+    /// Accepts one connection on a local port and answers it with
+    /// `RESPONSE` over TLS, offering `alpn_protocols`.
+    fn serve(pki: &TestPki, alpn_protocols: &[&[u8]]) -> (u16, thread::JoinHandle<Served>) {
+        let mut config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![rustls::Certificate(pki.certificate.clone())],
+                rustls::PrivateKey(pki.key.clone()),
+            )
+            .unwrap();
+        config.alpn_protocols = alpn_protocols.iter().map(|p| p.to_vec()).collect();
+        let config = Arc::new(config);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = thread::spawn(move || {
+            let (socket, _) = listener.accept().unwrap();
+            let connection = ServerConnection::new(config).unwrap();
+            let mut stream = StreamOwned::new(connection, socket);
+            let mut served = Served::default();
+            let mut buf = [0u8; 1024];
+            // 証明書を拒否されたときなどは、ハンドシェイクの途中で読めなくなる
+            while !served.request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(n) if n > 0 => served.request.extend_from_slice(&buf[..n]),
+                    _ => break,
+                }
+            }
+            served.server_name = stream.conn.server_name().map(|name| name.to_string());
+            served.alpn_protocol = stream.conn.alpn_protocol().map(|p| p.to_vec());
+            if !served.request.is_empty() {
+                stream.write_all(RESPONSE).unwrap();
+                stream.conn.send_close_notify();
+                let _ = stream.conn.complete_io(&mut stream.sock);
+            }
+            served
+        });
+        (port, handle)
+    }
+
+    fn client(roots: &RootStore) -> HttpClient {
+        let mut transport = StdTransport::new();
+        transport.set_root_store(roots);
+        HttpClient::new(transport)
+    }
+
+    #[test]
+    fn test_https_get() {
+        let pki = TestPki::new(false);
+        let (port, server) = serve(&pki, ALPN_PROTOCOLS);
+        let url = Url::new(format!("https://localhost:{}/index.html", port)).unwrap();
+        let response = client(&pki.roots()).get_url(&url).unwrap();
+        assert_eq!(response.body(), b"secure");
+
+        let served = server.join().unwrap();
+        assert!(served.request.starts_with(b"GET /index.html HTTP/1.1\r\n"));
+        assert_eq!(served.server_name, Some("localhost".to_string()));
+        assert_eq!(served.alpn_protocol, Some(b"http/1.1".to_vec()));
+    }
+
+    #[test]
+    fn test_sni_and_alpn() {
+        let pki = TestPki::new(false);
+        let (port, server) = serve(&pki, &[b"h2", b"http/1.1"]);
+        let mut transport = StdTransport::new();
+        transport.set_root_store(&pki.roots());
+        let address = "127.0.0.1".parse().unwrap();
+        let mut connection = transport.connect_tls(&address, port, "localhost").unwrap();
+        assert!(connection.is_secure());
+        // h2 は申し出ていないので選ばれない
+        assert_eq!(connection.alpn_protocol(), Some(b"http/1.1".to_vec()));
+        connection.write(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+
+        let mut response = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            match connection.read(&mut buf).unwrap() {
+                0 => break,
+                n => response.extend_from_slice(&buf[..n]),
+            }
+        }
+        assert_eq!(response, RESPONSE);
+        connection.close().unwrap();
+        assert_eq!(
+            server.join().unwrap().server_name,
+            Some("localhost".to_string())
+        );
+    }
+
+    #[test]
+    fn test_certificate_errors() {
+        let tests = [
+            // (expired, trust the test CA, host, expected)
+            (false, false, "localhost", CertificateError::UnknownIssuer),
+            (
+                false,
+                true,
+                "127.0.0.1",
+                CertificateError::NotValidForName("127.0.0.1".to_string()),
+            ),
+            (true, true, "localhost", CertificateError::Expired),
+        ];
+        for (expired, trusted, host, expected) in tests {
+            let pki = TestPki::new(expired);
+            let (port, server) = serve(&pki, ALPN_PROTOCOLS);
+            let roots = if trusted {
+                pki.roots()
+            } else {
+                RootStore::mozilla()
+            };
+            let url = Url::new(format!("https://{}:{}/", host, port)).unwrap();
+            assert_eq!(
+                client(&roots).get_url(&url).err(),
+                Some(Error::Tls(TlsError::Certificate(expected))),
+                "{}",
+                url
+            );
+            // 拒否した接続では何も送らない
+            assert!(server.join().unwrap().request.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_handshake_errors() {
+        // ALPN で共通のプロトコルがない
+        let pki = TestPki::new(false);
+        let (port, server) = serve(&pki, &[b"h2"]);
+        let url = Url::new(format!("https://localhost:{}/", port)).unwrap();
+        let result = client(&pki.roots()).get_url(&url);
+        assert!(
+            matches!(result, Err(Error::Tls(TlsError::Handshake(_)))),
+            "{:?}",
+            result
+        );
+        server.join().unwrap();
+
+        // TLS を話さないサーバ
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf);
+            socket
+                .write_all(b"HTTP/1.1 400 Bad Request\r\nConnection: close\r\n\r\n")
+                .unwrap();
+        });
+        let url = Url::new(format!("https://localhost:{}/", port)).unwrap();
+        let result = client(&pki.roots()).get_url(&url);
+        assert!(
+            matches!(result, Err(Error::Tls(TlsError::Handshake(_)))),
+            "{:?}",
+            result
+        );
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_add_pem() {
+        let pki = TestPki::new(false);
+        let bad = "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n".to_string();
+        let tests = [
+            // (pem, expected)
+            (String::new(), Some(0)),
+            (pki.ca_pem.clone(), Some(1)),
+            (format!("{}{}", pki.ca_pem, pki.ca_pem), Some(2)),
+            (bad, None),
+        ];
+        for (pem, expected) in tests {
+            let mut roots = RootStore::new();
+            match (roots.add_pem(pem.as_bytes()), expected) {
+                (Ok(n), Some(expected)) => assert_eq!(n, expected),
+                (Err(Error::Tls(TlsError::Certificate(CertificateError::Invalid(_)))), None) => {}
+                (result, _) => panic!("{:?}: {:?}", pem, result),
+            }
+        }
+        assert!(RootStore::new().is_empty());
+        assert!(!RootStore::mozilla().is_empty());
+    }
+}
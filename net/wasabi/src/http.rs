@@ -1,78 +1,76 @@
 extern crate alloc;
 use alloc::format;
-use alloc::string::String;
-// 書籍では crate::... となっているが実際にはこう
-use alloc::string::ToString;
 use alloc::vec::Vec;
 use noli::net::lookup_host;
+use noli::net::IpV4Addr;
 use noli::net::SocketAddr;
 use noli::net::TcpStream;
 use rwb_core::error::Error;
-use rwb_core::http::HttpResponse;
+use rwb_core::error::NetworkError;
+use rwb_core::transport::Connection;
+use rwb_core::transport::Transport;
 
-pub struct HttpClient {}
+/// `rwb_core`'s client over the Wasabi network stack.
+///
+/// noli has no wall clock, so there is nothing to pass to `set_clock`: the
+/// client fetches everything from the network without caching, keeps only
+/// session cookies and keeps idle connections without a timeout.
+pub type HttpClient = rwb_core::http::HttpClient<WasabiTransport>;
 
-impl HttpClient {
+/// Connects with `noli::net`, which only supports IPv4.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WasabiTransport;
+
+impl WasabiTransport {
     pub fn new() -> Self {
-        Self {}
+        Self
     }
+}
 
-    pub fn get(&self, host: String, port: u16, path: String) -> Result<HttpResponse, Error> {
-        let ips = match lookup_host(&host) {
+impl Transport for WasabiTransport {
+    type Address = IpV4Addr;
+    type Connection = WasabiConnection;
+
+    fn resolve(&self, host: &str) -> Result<Vec<IpV4Addr>, Error> {
+        let ips = match lookup_host(host) {
             Ok(ips) => ips,
-            Err(e) => return Err(Error::Network(format!("Failed to lookup host: {:#?}", e))),
+            Err(e) => return Err(NetworkError::Dns(format!("{}: {:?}", host, e)).into()),
         };
 
-        if ips.len() < 1 {
-            return Err(Error::Network(format!(
-                "No IP addresses found for host: {}",
-                host
-            )));
+        if ips.is_empty() {
+            return Err(NetworkError::Dns(format!("{}: no addresses", host)).into());
         }
+        Ok(ips)
+    }
 
-        let socket_addr: SocketAddr = (ips[0], port).into();
-
-        let mut stream = match TcpStream::connect(socket_addr) {
-            Ok(stream) => stream,
-            Err(_) => {
-                return Err(Error::Network(
-                    "Failed to connect to TCP stream".to_string(),
-                ))
-            }
-        };
+    fn connect(&self, address: &IpV4Addr, port: u16) -> Result<WasabiConnection, Error> {
+        let socket_addr: SocketAddr = (*address, port).into();
 
-        let mut request = String::from("GET /");
-        request.push_str(&path);
-        request.push_str(" HTTP/1.1\n");
+        match TcpStream::connect(socket_addr) {
+            Ok(stream) => Ok(WasabiConnection(stream)),
+            Err(e) => Err(NetworkError::Connect(format!("{:?}:{}: {:?}", address, port, e)).into()),
+        }
+    }
+}
 
-        request.push_str("Host: ");
-        request.push_str(&host);
-        request.push('\n');
-        request.push_str("Accept: text/html\n");
-        request.push_str("Connection: close\n");
-        request.push('\n');
+pub struct WasabiConnection(TcpStream);
 
-        let _bytes_written = match stream.write(request.as_bytes()) {
-            Ok(bytes) => bytes,
-            Err(_) => return Err(Error::Network("Failed to write to TCP stream".to_string())),
-        };
+impl Connection for WasabiConnection {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.0
+            .read(buf)
+            .map_err(|e| NetworkError::Read(format!("{:?}", e)).into())
+    }
 
-        let mut received = Vec::new();
-        loop {
-            let mut buf = [0u8; 4096];
-            let bytes_read = match stream.read(&mut buf) {
-                Ok(bytes) => bytes,
-                Err(_) => return Err(Error::Network("Failed to read from TCP stream".to_string())),
-            };
-            if bytes_read == 0 {
-                break;
-            }
-            received.extend_from_slice(&buf[..bytes_read]);
-        }
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.0
+            .write(buf)
+            .map_err(|e| NetworkError::Write(format!("{:?}", e)).into())
+    }
 
-        match core::str::from_utf8(&received) {
-            Ok(response) => HttpResponse::new(response.to_string()),
-            Err(e) => Err(Error::Network(format!("Invalid received response: {}", e))),
-        }
+    /// noli closes the socket when the stream is dropped, so there is
+    /// nothing to do until then.
+    fn close(&mut self) -> Result<(), Error> {
+        Ok(())
     }
 }
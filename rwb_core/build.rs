@@ -0,0 +1,36 @@
+//! Writes the rules of the embedded Public Suffix List as a sorted array, so
+//! that `url::public_suffix` can look them up by binary search instead of
+//! scanning the list for every domain.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const LIST: &str = "src/url/public_suffix_list.dat";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", LIST);
+    let list = fs::read_to_string(LIST).expect("failed to read the Public Suffix List");
+
+    // 各行の最初の空白までがルールで、残りは無視する
+    let mut rules: Vec<&str> = list
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|rule| !rule.starts_with("//"))
+        .collect();
+    rules.sort_unstable();
+    rules.dedup();
+
+    let mut out = String::from(
+        "/// The rules of the Public Suffix List, sorted. Exception rules keep\n\
+         /// their leading `!`.\n\
+         const RULES: &[&str] = &[\n",
+    );
+    for rule in rules {
+        writeln!(out, "    {:?},", rule).unwrap();
+    }
+    out.push_str("];\n");
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("public_suffix_rules.rs");
+    fs::write(path, out).expect("failed to write the sorted rules");
+}
@@ -1,9 +1,425 @@
+//! The error type shared by the whole browser.
+//!
+//! Errors are grouped by where they come from: the network, TLS, parsing of
+//! data received from the network, the HTTP layer, URLs and the UI. Each
+//! group has its own type so that callers can match on the kind without
+//! looking at messages.
+
+use crate::url;
 use alloc::string::String;
+use core::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
-    Network(String),
-    UnexpectedInput(String),
-    InvalidUI(String),
+    /// Talking to the server failed.
+    Network(NetworkError),
+    /// A secure connection could not be set up or was broken.
+    Tls(TlsError),
+    /// Data received from the server is malformed or too large.
+    Parse(ParseError),
+    /// A request could not be made or a response could not be used.
+    Http(HttpError),
+    /// A URL could not be parsed.
+    Url(url::ParseError),
+    /// The UI could not do what it was asked to.
+    Ui(String),
     Other(String),
 }
+
+impl Error {
+    /// Returns a `Parse` error of `kind` found `offset` bytes into the input.
+    pub fn parse(kind: ParseErrorKind, offset: usize) -> Self {
+        Self::Parse(ParseError::new(kind, offset))
+    }
+
+    /// Moves the offset of a parse error `base` bytes on, for input that
+    /// starts `base` bytes into a larger one. Other errors are returned as
+    /// they are.
+    pub(crate) fn offset_by(self, base: usize) -> Self {
+        match self {
+            Self::Parse(e) => Self::parse(e.kind, e.offset.saturating_add(base)),
+            e => e,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Network(e) => write!(f, "network error: {}", e),
+            Self::Tls(e) => write!(f, "TLS error: {}", e),
+            Self::Parse(e) => write!(f, "parse error: {}", e),
+            Self::Http(e) => write!(f, "HTTP error: {}", e),
+            Self::Url(e) => write!(f, "invalid URL: {}", e),
+            Self::Ui(message) => write!(f, "UI error: {}", message),
+            Self::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl core::error::Error for Error {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Network(e) => Some(e),
+            Self::Tls(e) => Some(e),
+            Self::Parse(e) => Some(e),
+            Self::Http(e) => Some(e),
+            Self::Url(e) => Some(e),
+            Self::Ui(_) | Self::Other(_) => None,
+        }
+    }
+}
+
+impl From<NetworkError> for Error {
+    fn from(e: NetworkError) -> Self {
+        Self::Network(e)
+    }
+}
+
+impl From<TlsError> for Error {
+    fn from(e: TlsError) -> Self {
+        Self::Tls(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+impl From<HttpError> for Error {
+    fn from(e: HttpError) -> Self {
+        Self::Http(e)
+    }
+}
+
+impl From<url::ParseError> for Error {
+    fn from(e: url::ParseError) -> Self {
+        Self::Url(e)
+    }
+}
+
+/// Which step of talking to the server failed. The strings hold what the
+/// network stack reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkError {
+    /// The host name could not be resolved, or has no addresses.
+    Dns(String),
+    /// No connection could be made to the resolved address.
+    Connect(String),
+    /// Reading from the connection failed.
+    Read(String),
+    /// Writing to the connection failed.
+    Write(String),
+    /// The server did not answer in time.
+    Timeout,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Dns(message) => write!(f, "failed to resolve host: {}", message),
+            Self::Connect(message) => write!(f, "failed to connect: {}", message),
+            Self::Read(message) => write!(f, "failed to read: {}", message),
+            Self::Write(message) => write!(f, "failed to write: {}", message),
+            Self::Timeout => f.write_str("timed out"),
+        }
+    }
+}
+
+impl core::error::Error for NetworkError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TlsError {
+    /// The transport cannot make TLS connections, so https URLs cannot be
+    /// fetched.
+    Unsupported,
+    /// The handshake failed for a reason other than the certificate, e.g.
+    /// there is no protocol version, cipher suite or ALPN protocol in
+    /// common, or the server does not speak TLS.
+    Handshake(String),
+    /// The server's certificate was rejected.
+    Certificate(CertificateError),
+    /// The server sent data that is not valid TLS after the handshake.
+    Protocol(String),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unsupported => f.write_str("TLS is not supported"),
+            Self::Handshake(message) => write!(f, "handshake failed: {}", message),
+            Self::Certificate(e) => write!(f, "invalid certificate: {}", e),
+            Self::Protocol(message) => write!(f, "protocol error: {}", message),
+        }
+    }
+}
+
+impl core::error::Error for TlsError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            Self::Certificate(e) => Some(e),
+            Self::Unsupported | Self::Handshake(_) | Self::Protocol(_) => None,
+        }
+    }
+}
+
+/// Why the server's certificate was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CertificateError {
+    /// The certificate chain does not lead to a trusted root certificate.
+    UnknownIssuer,
+    /// The certificate has expired.
+    Expired,
+    /// The certificate is not valid yet.
+    NotValidYet,
+    /// The certificate is not for the host that was connected to.
+    NotValidForName(String),
+    /// The certificate was revoked by its issuer.
+    Revoked,
+    /// The certificate is malformed, its signature is wrong, or it may not
+    /// be used for a server.
+    Invalid(String),
+}
+
+impl fmt::Display for CertificateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnknownIssuer => f.write_str("unknown issuer"),
+            Self::Expired => f.write_str("expired"),
+            Self::NotValidYet => f.write_str("not valid yet"),
+            Self::NotValidForName(name) => write!(f, "not valid for {}", name),
+            Self::Revoked => f.write_str("revoked"),
+            Self::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl core::error::Error for CertificateError {}
+
+/// Malformed data and where it was found.
+///
+/// The offset counts bytes from the start of the input the failing function
+/// was given: the whole response for `ResponseParser` and
+/// `HttpResponse::new`, the body for decompression and text decoding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    offset: usize,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, offset: usize) -> Self {
+        Self { kind, offset }
+    }
+
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind.clone()
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.kind, self.offset)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The response ended before the empty line that terminates the head.
+    IncompleteHead,
+    /// The status line is not `HTTP/x.y <3-digit code> [reason]`.
+    InvalidStatusLine(String),
+    /// The status code in the status line is not three digits.
+    InvalidStatusCode(String),
+    /// A header line has no colon, or its name is not a token, e.g. it
+    /// contains a space before the colon.
+    InvalidHeaderName(String),
+    /// A header value contains a control character other than tab.
+    InvalidHeaderValue(String),
+    /// A header line starts with a space or tab, continuing the previous one.
+    /// This "obs-fold" syntax is deprecated by RFC 9112 and rejected here.
+    ObsoleteLineFolding(String),
+    /// The body ended before the length given by Content-Length or before
+    /// the last chunk.
+    IncompleteBody,
+    /// A chunk-size line is malformed, or chunk data is not followed by CRLF.
+    InvalidChunk(String),
+    /// Content-Length is not a decimal number, or has conflicting values.
+    InvalidContentLength(String),
+    /// The status line and headers exceed the parser's limit.
+    HeadTooLarge,
+    /// The body exceeds the parser's limit, either as received or once
+    /// decompressed.
+    BodyTooLarge,
+    /// A compressed body is not valid deflate, zlib or gzip data, or its
+    /// checksum does not match.
+    InvalidCompressedData(String),
+    /// A body that should be UTF-8 is not.
+    InvalidUtf8,
+    /// A line of saved cookies, as `CookieJar::to_bytes` writes them, does
+    /// not have the expected fields.
+    InvalidCookieLine(String),
+    /// The first line of a saved `CacheEntry` is not the magic followed by
+    /// the request and response times.
+    InvalidCacheEntry(String),
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IncompleteHead => f.write_str("incomplete head"),
+            Self::InvalidStatusLine(line) => write!(f, "invalid status line {:?}", line),
+            Self::InvalidStatusCode(code) => write!(f, "invalid status code {:?}", code),
+            Self::InvalidHeaderName(name) => write!(f, "invalid header name {:?}", name),
+            Self::InvalidHeaderValue(name) => write!(f, "invalid value for header {:?}", name),
+            Self::ObsoleteLineFolding(line) => write!(f, "obsolete line folding {:?}", line),
+            Self::IncompleteBody => f.write_str("incomplete body"),
+            Self::InvalidChunk(line) => write!(f, "invalid chunk {:?}", line),
+            Self::InvalidContentLength(value) => write!(f, "invalid Content-Length {:?}", value),
+            Self::HeadTooLarge => f.write_str("head too large"),
+            Self::BodyTooLarge => f.write_str("body too large"),
+            Self::InvalidCompressedData(message) => write!(f, "{}", message),
+            Self::InvalidUtf8 => f.write_str("invalid UTF-8"),
+            Self::InvalidCookieLine(line) => write!(f, "invalid cookie line {:?}", line),
+            Self::InvalidCacheEntry(line) => write!(f, "invalid cache entry {:?}", line),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpError {
+    /// A request method is not a token.
+    InvalidMethod(String),
+    /// A request target is empty or contains whitespace or control characters.
+    InvalidRequestTarget(String),
+    /// A request header name is not a token.
+    InvalidHeaderName(String),
+    /// A request header value contains a control character other than tab.
+    InvalidHeaderValue(String),
+    /// A request's Content-Length is not a decimal number, or does not match
+    /// the body.
+    InvalidContentLength(String),
+    /// A request has neither a URL nor a Host header, so there is nowhere
+    /// to send it.
+    MissingHost,
+    /// More redirects were followed than the limit allows, which usually
+    /// means a redirect loop.
+    TooManyRedirects,
+    /// A Location header is not a valid URL, or points to a scheme other
+    /// than http or https.
+    InvalidRedirect(String),
+    /// The connection pool already has as many connections to the host as
+    /// it allows.
+    TooManyConnections(String),
+    /// The body is text in a charset that cannot be decoded.
+    UnsupportedCharset(String),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidMethod(method) => write!(f, "invalid method {:?}", method),
+            Self::InvalidRequestTarget(target) => write!(f, "invalid request target {:?}", target),
+            Self::InvalidHeaderName(name) => write!(f, "invalid header name {:?}", name),
+            Self::InvalidHeaderValue(name) => write!(f, "invalid value for header {:?}", name),
+            Self::InvalidContentLength(value) => write!(f, "invalid Content-Length {:?}", value),
+            Self::MissingHost => f.write_str("request has neither a URL nor a Host header"),
+            Self::TooManyRedirects => f.write_str("too many redirects"),
+            Self::InvalidRedirect(location) => write!(f, "invalid redirect to {:?}", location),
+            Self::TooManyConnections(host) => write!(f, "too many connections to {}", host),
+            Self::UnsupportedCharset(charset) => write!(f, "unsupported charset {}", charset),
+        }
+    }
+}
+
+impl core::error::Error for HttpError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_display() {
+        // (error, expected)
+        let cases: &[(Error, &str)] = &[
+            (
+                Error::Network(NetworkError::Dns("example.com".to_string())),
+                "network error: failed to resolve host: example.com",
+            ),
+            (
+                Error::Network(NetworkError::Timeout),
+                "network error: timed out",
+            ),
+            (
+                Error::Tls(TlsError::Certificate(CertificateError::NotValidForName(
+                    "example.com".to_string(),
+                ))),
+                "TLS error: invalid certificate: not valid for example.com",
+            ),
+            (
+                Error::Tls(TlsError::Handshake("no ALPN protocol".to_string())),
+                "TLS error: handshake failed: no ALPN protocol",
+            ),
+            (
+                Error::parse(ParseErrorKind::InvalidChunk("zz".to_string()), 42),
+                "parse error: invalid chunk \"zz\" at byte 42",
+            ),
+            (
+                Error::Http(HttpError::TooManyRedirects),
+                "HTTP error: too many redirects",
+            ),
+            (
+                Error::Url(url::ParseError::InvalidPort),
+                "invalid URL: invalid port",
+            ),
+            (Error::Ui("no window".to_string()), "UI error: no window"),
+            (Error::Other("oops".to_string()), "oops"),
+        ];
+        for (error, expected) in cases {
+            assert_eq!(error.to_string(), *expected);
+        }
+    }
+
+    #[test]
+    fn test_source() {
+        use core::error::Error as _;
+
+        let error = Error::parse(ParseErrorKind::HeadTooLarge, 7);
+        assert_eq!(
+            error.source().map(|e| e.to_string()),
+            Some("head too large at byte 7".to_string())
+        );
+        assert!(Error::Other("oops".to_string()).source().is_none());
+
+        let error = Error::Tls(TlsError::Certificate(CertificateError::Expired));
+        assert_eq!(
+            error
+                .source()
+                .and_then(|e| e.source())
+                .map(|e| e.to_string()),
+            Some("expired".to_string())
+        );
+        assert!(TlsError::Unsupported.source().is_none());
+    }
+
+    #[test]
+    fn test_offset_by() {
+        assert_eq!(
+            Error::parse(ParseErrorKind::IncompleteBody, 3).offset_by(10),
+            Error::parse(ParseErrorKind::IncompleteBody, 13)
+        );
+        assert_eq!(
+            Error::Http(HttpError::TooManyRedirects).offset_by(10),
+            Error::Http(HttpError::TooManyRedirects)
+        );
+    }
+}
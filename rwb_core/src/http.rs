@@ -1,5 +1,53 @@
-use crate::alloc::string::ToString;
+mod cache;
+mod chunked;
+mod client;
+mod cookie;
+mod date;
+mod header;
+mod parser;
+mod pool;
+mod redirect;
+mod request;
+mod status;
+
+pub use cache::CacheEntry;
+pub use cache::CacheStorage;
+pub use cache::HttpCache;
+pub use cache::MemoryStorage;
+pub use chunked::decode as decode_chunked;
+pub use chunked::encode as encode_chunked;
+pub use chunked::ChunkedDecoder;
+pub use chunked::DEFAULT_MAX_CHUNK_LINE_SIZE;
+pub use client::HttpClient;
+pub use cookie::Cookie;
+pub use cookie::CookieJar;
+pub use cookie::RequestContext;
+pub use cookie::SameSite;
+pub use date::parse_http_date;
+pub use header::ContentType;
+pub use header::Header;
+pub use header::HeaderMap;
+pub use parser::ResponseEvent;
+pub use parser::ResponseHead;
+pub use parser::ResponseParser;
+pub use parser::DEFAULT_MAX_BODY_SIZE;
+pub use pool::is_reusable;
+pub use pool::ConnectionPool;
+pub use pool::DEFAULT_IDLE_TIMEOUT;
+pub use pool::DEFAULT_MAX_CONNECTIONS_PER_HOST;
+pub use redirect::follow_redirects;
+pub use redirect::redirect_request;
+pub use redirect::DEFAULT_MAX_REDIRECTS;
+pub use request::origin_form;
+pub use request::HttpRequest;
+pub use request::HttpRequestBuilder;
+pub use status::StatusCode;
+
 use crate::error::Error;
+use crate::error::HttpError;
+use crate::error::ParseErrorKind;
+use crate::inflate;
+use crate::url::Url;
 use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
@@ -7,63 +55,75 @@ use alloc::vec::Vec;
 #[derive(Debug, Clone)]
 pub struct HttpResponse {
     version: String,
-    status_code: u32,
+    status_code: StatusCode,
     reason: String,
-    headers: Vec<Header>,
-    body: String,
-}
-
-#[derive(Debug, Clone)]
-pub struct Header {
-    name: String,
-    value: String,
-}
-
-impl Header {
-    pub fn new(name: String, value: String) -> Self {
-        Self { name, value }
-    }
+    headers: HeaderMap,
+    body: Vec<u8>,
+    trailers: HeaderMap,
+    /// The URLs that answered with a redirect before this response.
+    redirect_chain: Vec<Url>,
+    /// The URL this response came from, if it is known.
+    url: Option<Url>,
 }
 
 impl HttpResponse {
-    pub fn new(raw_response: String) -> Result<Self, Error> {
-        // 書籍では CRLF が \n\r になっているが、実際は \r\n である
-        let preprocessed_response = raw_response.trim_start().replace("\r\n", "\n");
-
-        let (status_line, remaining) = match preprocessed_response.split_once("\n") {
-            Some((s, r)) => (s, r),
-            None => {
-                return Err(Error::Network(format!(
-                    "Invalid http response: {}",
-                    preprocessed_response
-                )))
-            }
-        };
+    /// Parses a complete response. Lines may end with either CRLF or a bare
+    /// LF, and empty lines before the status line are ignored.
+    ///
+    /// The body is framed as RFC 9112 section 6.3 describes: a chunked body is
+    /// decoded, a Content-Length body is cut to that length, and otherwise the
+    /// body runs to the end of `raw_response`, i.e. until the connection
+    /// closed.
+    ///
+    /// A body compressed with gzip or deflate, as Content-Encoding says, is
+    /// decompressed; the headers are kept as they were received. The
+    /// decompressed body is limited to `DEFAULT_MAX_BODY_SIZE`.
+    ///
+    /// This never panics: malformed input is reported as an `Error`.
+    pub fn new(raw_response: Vec<u8>) -> Result<Self, Error> {
+        // 既にメモリ上にあるので大きさの上限は設けない
+        let mut parser = ResponseParser::new();
+        parser.set_max_header_size(usize::MAX);
+        parser.set_max_body_size(usize::MAX);
+        let mut events = parser.push(&raw_response)?;
+        events.extend(parser.finish()?);
+        // 展開後の大きさは入力からはわからないので、こちらには上限を設ける
+        Self::from_events(events, DEFAULT_MAX_BODY_SIZE)
+    }
 
-        let (headers, body) = match remaining.split_once("\n\n") {
-            Some((h, b)) => {
-                let mut headers = Vec::new();
-                for header in h.split("\n") {
-                    // 規格上は区切り文字としてコロンのあとにOWSがあるが考えない
-                    let split_header: Vec<&str> = header.splitn(2, ":").collect();
-                    headers.push(Header::new(
-                        String::from(split_header[0].trim()),
-                        String::from(split_header[1].trim()),
-                    ));
-                }
-                (headers, b)
+    /// Builds a response from the events of a `ResponseParser` that has
+    /// finished parsing it. A compressed body that expands to more than
+    /// `max_body_size` bytes fails with `BodyTooLarge`; pass the limit of
+    /// the parser the events came from.
+    pub fn from_events(events: Vec<ResponseEvent>, max_body_size: usize) -> Result<Self, Error> {
+        let mut head = None;
+        let mut body = Vec::new();
+        let mut trailers = HeaderMap::new();
+        let mut done = false;
+        for event in events {
+            match event {
+                ResponseEvent::Head(h) => head = Some(h),
+                ResponseEvent::Body(data) => body.extend_from_slice(&data),
+                ResponseEvent::Trailers(t) => trailers = t,
+                ResponseEvent::Done => done = true,
             }
-            None => (Vec::new(), remaining),
-        };
-
-        let statuses: Vec<&str> = status_line.split(" ").collect();
+        }
+        // イベントには入力上の位置がないので、本文の長さを位置とする
+        let head = head.ok_or(Error::parse(ParseErrorKind::IncompleteHead, 0))?;
+        if !done {
+            return Err(Error::parse(ParseErrorKind::IncompleteBody, body.len()));
+        }
+        let body = decode_content(&head.headers(), body, max_body_size)?;
 
         Ok(Self {
-            version: statuses[0].to_string(),
-            status_code: statuses[1].parse().unwrap_or(404),
-            reason: statuses[2].to_string(),
-            headers,
-            body: body.to_string(),
+            version: head.version(),
+            status_code: head.status_code(),
+            reason: head.reason(),
+            headers: head.headers(),
+            body,
+            trailers,
+            redirect_chain: Vec::new(),
+            url: None,
         })
     }
 
@@ -71,7 +131,7 @@ impl HttpResponse {
         self.version.clone()
     }
 
-    pub fn status_code(&self) -> u32 {
+    pub fn status_code(&self) -> StatusCode {
         self.status_code
     }
 
@@ -79,38 +139,284 @@ impl HttpResponse {
         self.reason.clone()
     }
 
-    pub fn headers(&self) -> Vec<Header> {
+    pub fn headers(&self) -> HeaderMap {
         self.headers.clone()
     }
 
-    pub fn body(&self) -> String {
+    pub fn body(&self) -> Vec<u8> {
         self.body.clone()
     }
 
-    pub fn header_value(&self, name: &str) -> Result<String, String> {
-        for h in &self.headers {
-            if h.name == name {
-                return Ok(h.value.clone());
+    /// Returns the trailer fields sent after a chunked body.
+    pub fn trailers(&self) -> HeaderMap {
+        self.trailers.clone()
+    }
+
+    /// Returns the URLs that were redirected from, in order, when the
+    /// response was fetched with `follow_redirects`. Empty if there was no
+    /// redirect.
+    pub fn redirect_chain(&self) -> Vec<Url> {
+        self.redirect_chain.clone()
+    }
+
+    /// Returns the URL of the final request when the response was fetched
+    /// with `follow_redirects`.
+    pub fn url(&self) -> Option<Url> {
+        self.url.clone()
+    }
+
+    /// Returns the `charset` parameter of the Content-Type header, lowercased.
+    pub fn charset(&self) -> Option<String> {
+        self.headers.content_type()?.charset()
+    }
+
+    /// Decodes the body as text using the charset in the Content-Type header,
+    /// or UTF-8 if there is none. Invalid UTF-8 is reported at the offset of
+    /// the first byte that is not part of a valid sequence.
+    pub fn text(&self) -> Result<String, Error> {
+        match self.charset().as_deref() {
+            None | Some("utf-8") | Some("utf8") => {
+                String::from_utf8(self.body.clone()).map_err(|e| {
+                    Error::parse(ParseErrorKind::InvalidUtf8, e.utf8_error().valid_up_to())
+                })
             }
+            Some("us-ascii") | Some("iso-8859-1") | Some("latin1") => {
+                Ok(isomorphic_decode(&self.body))
+            }
+            Some(charset) => Err(HttpError::UnsupportedCharset(String::from(charset)).into()),
         }
+    }
+
+    /// Decodes the body as UTF-8, replacing invalid sequences with U+FFFD.
+    pub fn text_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Returns the value of the first header named `name`, compared
+    /// case-insensitively.
+    pub fn header_value(&self, name: &str) -> Result<String, String> {
+        self.headers
+            .get(name)
+            .ok_or_else(|| format!("Header {} not found", name))
+    }
+}
+
+/// How the end of a response body is determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyLength {
+    Chunked,
+    Fixed(usize),
+    UntilClose,
+}
 
-        Err(format!("Header {} not found", name))
+// https://www.rfc-editor.org/rfc/rfc9112#section-6.3
+fn body_length(status_code: StatusCode, headers: &HeaderMap) -> Result<BodyLength, Error> {
+    // 1xx, 204, 304 には本文がない
+    if status_code.is_informational()
+        || status_code == StatusCode::NO_CONTENT
+        || status_code == StatusCode::NOT_MODIFIED
+    {
+        return Ok(BodyLength::Fixed(0));
     }
+
+    // Transfer-Encoding は Content-Length より優先される
+    if let Some(last) = headers.get_list("Transfer-Encoding").last() {
+        if last.eq_ignore_ascii_case("chunked") {
+            return Ok(BodyLength::Chunked);
+        }
+        return Ok(BodyLength::UntilClose);
+    }
+
+    Ok(match headers.content_length()? {
+        Some(length) => BodyLength::Fixed(length),
+        None => BodyLength::UntilClose,
+    })
+}
+
+/// Undoes the codings listed in Content-Encoding, last applied first. The
+/// body is returned as is if any of them is not supported.
+///
+/// https://www.rfc-editor.org/rfc/rfc9110#section-8.4
+fn decode_content(
+    headers: &HeaderMap,
+    body: Vec<u8>,
+    max_body_size: usize,
+) -> Result<Vec<u8>, Error> {
+    let codings = headers.get_list("Content-Encoding");
+    let supported = |coding: &String| {
+        ["gzip", "x-gzip", "deflate", "identity"]
+            .iter()
+            .any(|name| coding.eq_ignore_ascii_case(name))
+    };
+    // HEAD や 304 のように本文がなければ何もしない
+    if body.is_empty() || !codings.iter().all(supported) {
+        return Ok(body);
+    }
+
+    let mut body = body;
+    for coding in codings.iter().rev() {
+        if coding.eq_ignore_ascii_case("gzip") || coding.eq_ignore_ascii_case("x-gzip") {
+            body = inflate::gzip_decompress(&body, max_body_size)?;
+        } else if coding.eq_ignore_ascii_case("deflate") {
+            // deflate は zlib 形式のはずだが、生の DEFLATE を送るサーバもある
+            body = match inflate::zlib_decompress(&body, max_body_size) {
+                Err(Error::Parse(e)) if e.kind() != ParseErrorKind::BodyTooLarge => {
+                    inflate::inflate(&body, max_body_size)?
+                }
+                result => result?,
+            };
+        }
+    }
+    Ok(body)
+}
+
+/// A line of the head and its offset in the input.
+type Line<'a> = (usize, &'a [u8]);
+
+/// Splits the status line and header lines, which end at the first empty
+/// line, from the body.
+fn split_head(raw: &[u8]) -> Result<(Vec<Line>, &[u8]), Error> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    loop {
+        let end = match raw[start..].iter().position(|b| *b == b'\n') {
+            Some(end) => start + end,
+            None => return Err(Error::parse(ParseErrorKind::IncompleteHead, raw.len())),
+        };
+        let line = &raw[start..end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let offset = start;
+        start = end + 1;
+        if line.is_empty() {
+            return Ok((lines, &raw[start..]));
+        }
+        lines.push((offset, line));
+    }
+}
+
+/// Strips optional whitespace (spaces and tabs) from both ends.
+fn trim_ows(bytes: &[u8]) -> &[u8] {
+    let is_ows = |b: &u8| *b == b' ' || *b == b'\t';
+    let start = bytes.iter().position(|b| !is_ows(b)).unwrap_or(bytes.len());
+    let end = bytes
+        .iter()
+        .rposition(|b| !is_ows(b))
+        .map_or(start, |i| i + 1);
+    &bytes[start..end]
+}
+
+fn is_token_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+// https://www.rfc-editor.org/rfc/rfc9112#section-4
+//
+// Errors are reported at offsets within `line`.
+fn parse_status_line(line: &[u8]) -> Result<(String, StatusCode, String), Error> {
+    let invalid = |offset| {
+        Error::parse(
+            ParseErrorKind::InvalidStatusLine(isomorphic_decode(line)),
+            offset,
+        )
+    };
+
+    // HTTP-version = "HTTP/" DIGIT "." DIGIT
+    let (version, rest) = match line.iter().position(|b| *b == b' ') {
+        Some(i) => (&line[..i], &line[i + 1..]),
+        None => return Err(invalid(line.len())),
+    };
+    match version {
+        [b'H', b'T', b'T', b'P', b'/', major, b'.', minor]
+            if major.is_ascii_digit() && minor.is_ascii_digit() => {}
+        _ => return Err(invalid(0)),
+    }
+
+    // 理由句は空でもよく、空白を含んでもよい。最後の SP も省略されることがある
+    let (code, reason) = match rest.iter().position(|b| *b == b' ') {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => (rest, &[][..]),
+    };
+    let status_code = isomorphic_decode(code)
+        .parse::<StatusCode>()
+        .map_err(|e| e.offset_by(version.len() + 1))?;
+    if let Some(i) = reason
+        .iter()
+        .position(|b| b.is_ascii_control() && *b != b'\t')
+    {
+        return Err(invalid(line.len() - reason.len() + i));
+    }
+
+    Ok((
+        isomorphic_decode(version),
+        status_code,
+        isomorphic_decode(reason),
+    ))
+}
+
+// https://www.rfc-editor.org/rfc/rfc9112#section-5
+//
+// Errors are reported at offsets within `line`.
+fn parse_header_line(line: &[u8]) -> Result<Header, Error> {
+    if line[0] == b' ' || line[0] == b'\t' {
+        return Err(Error::parse(
+            ParseErrorKind::ObsoleteLineFolding(isomorphic_decode(line)),
+            0,
+        ));
+    }
+    let (name, value) = match line.iter().position(|b| *b == b':') {
+        Some(i) => (&line[..i], &line[i + 1..]),
+        None => {
+            return Err(Error::parse(
+                ParseErrorKind::InvalidHeaderName(isomorphic_decode(line)),
+                line.len(),
+            ))
+        }
+    };
+    // フィールド名とコロンの間に空白は許されない
+    let invalid_name = name.iter().position(|b| !is_token_char(*b));
+    if name.is_empty() || invalid_name.is_some() {
+        return Err(Error::parse(
+            ParseErrorKind::InvalidHeaderName(isomorphic_decode(name)),
+            invalid_name.unwrap_or(0),
+        ));
+    }
+    if let Some(i) = value
+        .iter()
+        .position(|b| b.is_ascii_control() && *b != b'\t')
+    {
+        return Err(Error::parse(
+            ParseErrorKind::InvalidHeaderValue(isomorphic_decode(name)),
+            name.len() + 1 + i,
+        ));
+    }
+    let value = trim_ows(value);
+    Ok(Header::new(
+        isomorphic_decode(name),
+        isomorphic_decode(value),
+    ))
+}
+
+/// Maps each byte to the code point with the same value, which is how the
+/// Fetch Standard turns header bytes into strings.
+fn isomorphic_decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| char::from(*b)).collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
 
     #[test]
     fn test_invalid() {
-        let raw = "HTTP/1.1 200 OK".to_string();
+        let raw = b"HTTP/1.1 200 OK".to_vec();
         assert!(HttpResponse::new(raw).is_err());
     }
 
     #[test]
     fn test_status_line_only() {
-        let raw = "HTTP/1.1 200 OK\n\n".to_string();
+        let raw = b"HTTP/1.1 200 OK\n\n".to_vec();
         let res = HttpResponse::new(raw).expect("failed to parse http response");
         assert_eq!(res.version(), "HTTP/1.1");
         assert_eq!(res.status_code(), 200);
@@ -119,7 +425,7 @@ mod tests {
 
     #[test]
     fn test_one_header() {
-        let raw = "HTTP/1.1 200 OK\nDate:xx xx xx\n\n".to_string();
+        let raw = b"HTTP/1.1 200 OK\nDate:xx xx xx\n\n".to_vec();
         let res = HttpResponse::new(raw).expect("failed to parse http response");
         assert_eq!(res.version(), "HTTP/1.1");
         assert_eq!(res.status_code(), 200);
@@ -130,19 +436,19 @@ mod tests {
 
     #[test]
     fn test_two_headers_with_white_space() {
-        let raw = "HTTP/1.1 200 OK\nDate: xx xx xx\nContent-Length: 42\n\n".to_string();
+        let raw = b"HTTP/1.1 200 OK\nDate: xx xx xx\nContent-Length: 0\n\n".to_vec();
         let res = HttpResponse::new(raw).expect("failed to parse http response");
         assert_eq!(res.version(), "HTTP/1.1");
         assert_eq!(res.status_code(), 200);
         assert_eq!(res.reason(), "OK");
 
         assert_eq!(res.header_value("Date"), Ok("xx xx xx".to_string()));
-        assert_eq!(res.header_value("Content-Length"), Ok("42".to_string()));
+        assert_eq!(res.header_value("Content-Length"), Ok("0".to_string()));
     }
 
     #[test]
     fn test_body() {
-        let raw = "HTTP/1.1 200 OK\nDate: xx xx xx\n\nbody message".to_string();
+        let raw = b"HTTP/1.1 200 OK\nDate: xx xx xx\n\nbody message".to_vec();
         let res = HttpResponse::new(raw).expect("failed to parse http response");
         assert_eq!(res.version(), "HTTP/1.1");
         assert_eq!(res.status_code(), 200);
@@ -150,6 +456,498 @@ mod tests {
 
         assert_eq!(res.header_value("Date"), Ok("xx xx xx".to_string()));
 
-        assert_eq!(res.body(), "body message".to_string());
+        assert_eq!(res.body(), b"body message".to_vec());
+        assert_eq!(res.text().unwrap(), "body message");
+    }
+
+    #[test]
+    fn test_binary_body() {
+        let mut raw = b"HTTP/1.1 200 OK\r\nContent-Type: image/png\r\n\r\n".to_vec();
+        raw.extend_from_slice(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0xff]);
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(
+            res.header_value("Content-Type"),
+            Ok("image/png".to_string())
+        );
+        assert_eq!(
+            res.body(),
+            [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0xff]
+        );
+        assert_eq!(
+            res.text(),
+            Err(Error::parse(ParseErrorKind::InvalidUtf8, 0))
+        );
+        assert_eq!(res.text_lossy(), "\u{FFFD}PNG\r\n\u{1a}\n\0\u{FFFD}");
+    }
+
+    #[test]
+    fn test_text_charset() {
+        let raw =
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=\"ISO-8859-1\"\r\n\r\ncaf\xe9"
+                .to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.charset(), Some("iso-8859-1".to_string()));
+        assert_eq!(res.text().unwrap(), "caf\u{e9}");
+
+        let raw = "HTTP/1.1 200 OK\r\nContent-Type: text/plain;charset=utf-8\r\n\r\n\u{3042}"
+            .as_bytes()
+            .to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.text().unwrap(), "\u{3042}");
+
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=Shift_JIS\r\n\r\n\x82\xa0"
+            .to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(
+            res.text(),
+            Err(Error::Http(HttpError::UnsupportedCharset(
+                "shift_jis".to_string()
+            )))
+        );
+        assert_eq!(res.body(), [0x82, 0xa0]);
+    }
+
+    #[test]
+    fn test_status_line_variants() {
+        // (status line, status code, reason)
+        let cases: &[(&str, u16, &str)] = &[
+            ("HTTP/1.1 200 OK", 200, "OK"),
+            ("HTTP/1.1 404 Not Found", 404, "Not Found"),
+            (
+                "HTTP/1.0 500 Internal  Server Error",
+                500,
+                "Internal  Server Error",
+            ),
+            ("HTTP/1.1 204", 204, ""),
+            ("HTTP/1.1 204 ", 204, ""),
+            ("HTTP/1.1 599 \tx\u{ff}", 599, "\tx\u{ff}"),
+        ];
+
+        for (status_line, status_code, reason) in cases {
+            let mut raw: Vec<u8> = status_line.chars().map(|c| c as u8).collect();
+            raw.extend_from_slice(b"\r\n\r\n");
+            let res = HttpResponse::new(raw)
+                .unwrap_or_else(|e| panic!("failed to parse {:?}: {:?}", status_line, e));
+            assert_eq!(res.status_code(), *status_code, "{:?}", status_line);
+            assert_eq!(res.reason(), *reason, "{:?}", status_line);
+        }
+    }
+
+    #[test]
+    fn test_leading_empty_lines() {
+        let raw = b"\r\n\nHTTP/1.1 200 OK\r\n\r\n".to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.status_code(), 200);
+    }
+
+    #[test]
+    fn test_header_whitespace_and_empty_value() {
+        let raw = b"HTTP/1.1 200 OK\r\nX-A: \t a b \t\r\nX-B:\r\n\r\n".to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.header_value("X-A"), Ok("a b".to_string()));
+        assert_eq!(res.header_value("X-B"), Ok("".to_string()));
+    }
+
+    #[test]
+    fn test_errors() {
+        // (raw, kind, offset)
+        let cases: &[(&[u8], ParseErrorKind, usize)] = &[
+            (b"", ParseErrorKind::IncompleteHead, 0),
+            (b"\r\n\r\n", ParseErrorKind::IncompleteHead, 4),
+            (
+                b"HTTP/1.1 200 OK\r\nDate: x\r\n",
+                ParseErrorKind::IncompleteHead,
+                26,
+            ),
+            (
+                b"HTTP/1.1\r\n\r\n",
+                ParseErrorKind::InvalidStatusLine("HTTP/1.1".to_string()),
+                8,
+            ),
+            (
+                b"HTTP/11 200 OK\r\n\r\n",
+                ParseErrorKind::InvalidStatusLine("HTTP/11 200 OK".to_string()),
+                0,
+            ),
+            (
+                b"http/1.1 200 OK\r\n\r\n",
+                ParseErrorKind::InvalidStatusLine("http/1.1 200 OK".to_string()),
+                0,
+            ),
+            (
+                b"HTTP/1.1  200 OK\r\n\r\n",
+                ParseErrorKind::InvalidStatusCode("".to_string()),
+                9,
+            ),
+            (
+                b"HTTP/1.1 abc OK\r\n\r\n",
+                ParseErrorKind::InvalidStatusCode("abc".to_string()),
+                9,
+            ),
+            (
+                b"HTTP/1.1 2000 OK\r\n\r\n",
+                ParseErrorKind::InvalidStatusCode("2000".to_string()),
+                9,
+            ),
+            (
+                b"HTTP/1.1 099 OK\r\n\r\n",
+                ParseErrorKind::InvalidStatusCode("099".to_string()),
+                9,
+            ),
+            (
+                b"HTTP/1.1 200 O\x00K\r\n\r\n",
+                ParseErrorKind::InvalidStatusLine("HTTP/1.1 200 O\0K".to_string()),
+                14,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nDate\r\n\r\n",
+                ParseErrorKind::InvalidHeaderName("Date".to_string()),
+                21,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nDate : x\r\n\r\n",
+                ParseErrorKind::InvalidHeaderName("Date ".to_string()),
+                21,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\n: x\r\n\r\n",
+                ParseErrorKind::InvalidHeaderName("".to_string()),
+                17,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nX(Y): x\r\n\r\n",
+                ParseErrorKind::InvalidHeaderName("X(Y)".to_string()),
+                18,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nX: a\rb\r\n\r\n",
+                ParseErrorKind::InvalidHeaderValue("X".to_string()),
+                21,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nX: a\r\n b\r\n\r\n",
+                ParseErrorKind::ObsoleteLineFolding(" b".to_string()),
+                23,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nX: a\r\n\tb\r\n\r\n",
+                ParseErrorKind::ObsoleteLineFolding("\tb".to_string()),
+                23,
+            ),
+        ];
+
+        for (raw, kind, offset) in cases {
+            assert_eq!(
+                HttpResponse::new(raw.to_vec()).err(),
+                Some(Error::parse(kind.clone(), *offset)),
+                "parsing {:?}",
+                isomorphic_decode(raw)
+            );
+        }
+    }
+
+    /// A xorshift generator so that the property tests below are
+    /// reproducible without pulling in a dependency.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    /// Checks the invariants every successfully parsed response must hold.
+    fn check_invariants(res: &HttpResponse) {
+        assert!(res.version().starts_with("HTTP/"));
+        assert!((100..1000).contains(&res.status_code().as_u16()));
+        for header in &res.headers() {
+            assert!(!header.name().is_empty());
+            assert!(header.name().bytes().all(is_token_char));
+            assert!(!header.value().starts_with([' ', '\t']));
+            assert!(!header.value().ends_with([' ', '\t']));
+        }
+    }
+
+    #[test]
+    fn test_random_bytes_never_panic() {
+        const ALPHABET: &[u8] = b"HTTP/1.0 23:\r\n\t\x00\xffaZ-";
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..20_000 {
+            let len = rng.below(64);
+            let raw: Vec<u8> = (0..len)
+                .map(|_| {
+                    if rng.below(4) == 0 {
+                        rng.next() as u8
+                    } else {
+                        ALPHABET[rng.below(ALPHABET.len())]
+                    }
+                })
+                .collect();
+            if let Ok(res) = HttpResponse::new(raw) {
+                check_invariants(&res);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mutated_responses_never_panic() {
+        let seed: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 5\r\nX-Multi: a, b\r\n\r\nhello";
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..20_000 {
+            let mut raw = seed.to_vec();
+            for _ in 0..=rng.below(4) {
+                let i = rng.below(raw.len() + 1);
+                match rng.below(3) {
+                    0 => raw.insert(i, rng.next() as u8),
+                    1 if i < raw.len() => {
+                        raw.remove(i);
+                    }
+                    _ => raw.truncate(i),
+                }
+            }
+            if let Ok(res) = HttpResponse::new(raw.clone()) {
+                check_invariants(&res);
+                // 本文はヘッダの後ろのバイト列を切り出したもの
+                let body = res.body();
+                assert!(body.is_empty() || raw.windows(body.len()).any(|w| w == body));
+            }
+        }
+    }
+
+    #[test]
+    fn test_body_framing() {
+        // (response, body)
+        let cases: &[(&[u8], &[u8])] = &[
+            // 接続が閉じるまでが本文
+            (b"HTTP/1.1 200 OK\r\n\r\nuntil close", b"until close"),
+            (b"HTTP/1.1 200 OK\r\n\r\n", b""),
+            // Content-Length
+            (b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello", b"hello"),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhelloHTTP/1.1 200 OK",
+                b"hello",
+            ),
+            (b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\nextra", b""),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5, 5\r\nContent-Length: 5\r\n\r\nhello",
+                b"hello",
+            ),
+            // chunked
+            (
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+                b"hello",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\ntransfer-encoding: Chunked\r\n\r\n1;a=b\r\nx\r\n0\r\n\r\n",
+                b"x",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Length: 1\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+                b"abc",
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+                b"abc",
+            ),
+            // chunked が最後でなければ接続が閉じるまで
+            (
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked, gzip\r\n\r\n3\r\nabc",
+                b"3\r\nabc",
+            ),
+            // 本文を持たないステータス
+            (b"HTTP/1.1 204 No Content\r\nContent-Length: 3\r\n\r\nabc", b""),
+            (b"HTTP/1.1 304 Not Modified\r\n\r\nabc", b""),
+            (b"HTTP/1.1 101 Switching Protocols\r\n\r\nframes", b""),
+            // 中間レスポンスは読み飛ばす
+            (
+                b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n\r\nbody",
+                b"body",
+            ),
+        ];
+
+        for (raw, body) in cases {
+            let res = HttpResponse::new(raw.to_vec()).unwrap_or_else(|e| {
+                panic!("failed to parse {:?}: {:?}", isomorphic_decode(raw), e)
+            });
+            assert_eq!(res.body(), *body, "parsing {:?}", isomorphic_decode(raw));
+        }
+    }
+
+    #[test]
+    fn test_trailers() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nTrailer: X-Sum\r\n\r\n4\r\nWiki\r\n5\r\npedia\r\n0\r\nX-Sum: 9\r\n\r\n".to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.text().unwrap(), "Wikipedia");
+        assert_eq!(
+            res.trailers(),
+            HeaderMap::from(vec![Header::new("X-Sum".to_string(), "9".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_body_framing_errors() {
+        // (raw, kind, offset)
+        let cases: &[(&[u8], ParseErrorKind, usize)] = &[
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhell",
+                ParseErrorKind::IncompleteBody,
+                42,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5, 6\r\n\r\nhello!",
+                ParseErrorKind::InvalidContentLength("6".to_string()),
+                41,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab",
+                ParseErrorKind::InvalidContentLength("2".to_string()),
+                57,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: -1\r\n\r\n",
+                ParseErrorKind::InvalidContentLength("-1".to_string()),
+                39,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: +1\r\n\r\na",
+                ParseErrorKind::InvalidContentLength("+1".to_string()),
+                39,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: 99999999999999999999999\r\n\r\n",
+                ParseErrorKind::InvalidContentLength("99999999999999999999999".to_string()),
+                60,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n",
+                ParseErrorKind::IncompleteBody,
+                57,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+                ParseErrorKind::InvalidChunk("zz".to_string()),
+                47,
+            ),
+        ];
+
+        for (raw, kind, offset) in cases {
+            assert_eq!(
+                HttpResponse::new(raw.to_vec()).err(),
+                Some(Error::parse(kind.clone(), *offset)),
+                "parsing {:?}",
+                isomorphic_decode(raw)
+            );
+        }
+    }
+
+    #[test]
+    fn test_headers_case_insensitive() {
+        let raw =
+            b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nSet-Cookie: a=1\r\nset-cookie: b=2\r\n\r\nok"
+                .to_vec();
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.header_value("Content-Length"), Ok("2".to_string()));
+        assert_eq!(res.headers().content_length(), Ok(Some(2)));
+        assert_eq!(res.headers().get_all("SET-COOKIE"), ["a=1", "b=2"]);
+        assert_eq!(res.body(), b"ok");
+    }
+
+    #[test]
+    fn test_content_encoding() {
+        let html: &[u8] = include_bytes!("inflate/fixture.html");
+        let gzip: &[u8] = include_bytes!("inflate/fixture.html.gz");
+        let zlib: &[u8] = include_bytes!("inflate/fixture.html.zlib");
+        let deflate: &[u8] = include_bytes!("inflate/fixture.html.deflate");
+
+        // (Content-Encoding, body, expected)
+        let cases: &[(&str, &[u8], &[u8])] = &[
+            ("gzip", gzip, html),
+            ("x-gzip", gzip, html),
+            ("GZIP", gzip, html),
+            ("deflate", zlib, html),
+            // zlib の枠のない生の DEFLATE
+            ("deflate", deflate, html),
+            ("identity", b"plain", b"plain"),
+            ("identity, gzip", gzip, html),
+            // 対応していない符号化はそのまま
+            ("br", b"\x0b\x02\x80ok\x03", b"\x0b\x02\x80ok\x03"),
+            ("br, gzip", gzip, gzip),
+        ];
+        for (encoding, body, expected) in cases {
+            let mut raw = format!(
+                "HTTP/1.1 200 OK\r\nContent-Encoding: {}\r\nContent-Length: {}\r\n\r\n",
+                encoding,
+                body.len()
+            )
+            .into_bytes();
+            raw.extend_from_slice(body);
+            let res = HttpResponse::new(raw).expect("failed to parse http response");
+            assert_eq!(res.body(), *expected, "{}", encoding);
+            assert_eq!(
+                res.header_value("Content-Encoding"),
+                Ok(encoding.to_string())
+            );
+        }
+
+        // gzip と deflate を重ねたもの
+        let mut raw = b"HTTP/1.1 200 OK\r\nContent-Encoding: deflate, gzip\r\n\r\n".to_vec();
+        raw.extend_from_slice(include_bytes!("inflate/fixture.html.zlib.gz"));
+        let res = HttpResponse::new(raw).expect("failed to parse http response");
+        assert_eq!(res.body(), html);
+
+        // HEAD への応答のように本文がなければ展開しない
+        let res = HttpResponse::new(
+            b"HTTP/1.1 304 Not Modified\r\nContent-Encoding: gzip\r\n\r\n".to_vec(),
+        )
+        .expect("failed to parse http response");
+        assert!(res.body().is_empty());
+
+        let res = HttpResponse::new(
+            b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\n\r\nnot gzip data".to_vec(),
+        );
+        assert_eq!(
+            res.err().map(|e| e.to_string()),
+            Some("parse error: not gzip data at byte 0".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decompressed_body_too_large() {
+        let zeros: &[u8] = include_bytes!("inflate/zeros.bin.gz");
+        let html: &[u8] = include_bytes!("inflate/fixture.html");
+        let zlib: &[u8] = include_bytes!("inflate/fixture.html.zlib");
+
+        // (Content-Encoding, body, max body size)
+        let cases: &[(&str, &[u8], usize)] = &[
+            // 16 KB ほどが 16 MiB に膨らむ
+            ("gzip", zeros, 1024 * 1024),
+            // 上限を超えたら生の DEFLATE として読み直さない
+            ("deflate", zlib, html.len() - 1),
+        ];
+        for (encoding, body, max_body_size) in cases {
+            let mut raw = format!(
+                "HTTP/1.1 200 OK\r\nContent-Encoding: {}\r\nContent-Length: {}\r\n\r\n",
+                encoding,
+                body.len()
+            )
+            .into_bytes();
+            raw.extend_from_slice(body);
+            let mut parser = ResponseParser::new();
+            parser.set_max_body_size(*max_body_size);
+            let events = parser.push(&raw).expect("failed to parse http response");
+            match HttpResponse::from_events(events, parser.max_body_size()) {
+                Err(Error::Parse(e)) => {
+                    assert_eq!(e.kind(), ParseErrorKind::BodyTooLarge, "{}", encoding)
+                }
+                result => panic!("{}: {:?}", encoding, result.map(|res| res.body().len())),
+            }
+        }
     }
 }
@@ -0,0 +1,998 @@
+//! A private HTTP cache. Fresh responses to GET requests are served from
+//! storage, and stale ones are revalidated with a conditional request.
+//!
+//! https://www.rfc-editor.org/rfc/rfc9111
+
+use super::date::parse_http_date;
+use super::isomorphic_decode;
+use super::parse_header_line;
+use super::parse_status_line;
+use super::redirect::request_url;
+use super::split_head;
+use super::HeaderMap;
+use super::HttpRequest;
+use super::HttpResponse;
+use super::StatusCode;
+use crate::error::Error;
+use crate::error::ParseErrorKind;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// Status codes a response may be cached for without explicit freshness
+/// information.
+///
+/// https://www.rfc-editor.org/rfc/rfc9110#section-15.1
+const HEURISTICALLY_CACHEABLE: &[u16] = &[200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+
+/// Headers of a 304 response that do not replace the stored ones, because
+/// they describe the 304 itself rather than the stored body.
+const NOT_UPDATED_HEADERS: &[&str] = &["Content-Encoding", "Content-Length", "Transfer-Encoding"];
+
+/// The first line of a serialized `CacheEntry`.
+const ENTRY_MAGIC: &str = "RWB-CACHE/1";
+
+/// Where an `HttpCache` keeps its entries, keyed by URL. Implement this to
+/// keep the cache somewhere other than memory, e.g. on disk with
+/// `CacheEntry::to_bytes`.
+pub trait CacheStorage {
+    fn get(&self, key: &str) -> Option<CacheEntry>;
+    fn put(&mut self, key: &str, entry: CacheEntry);
+    fn remove(&mut self, key: &str);
+}
+
+impl<S: CacheStorage + ?Sized> CacheStorage for Box<S> {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        (**self).get(key)
+    }
+
+    fn put(&mut self, key: &str, entry: CacheEntry) {
+        (**self).put(key, entry)
+    }
+
+    fn remove(&mut self, key: &str) {
+        (**self).remove(key)
+    }
+}
+
+/// Keeps cache entries in memory until the storage is dropped.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    entries: BTreeMap<String, CacheEntry>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl CacheStorage for MemoryStorage {
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        self.entries.get(key).cloned()
+    }
+
+    fn put(&mut self, key: &str, entry: CacheEntry) {
+        self.entries.insert(key.to_string(), entry);
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.entries.remove(key);
+    }
+}
+
+/// A stored response with what is needed to work out its age. Times are
+/// seconds since the Unix epoch.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    response: HttpResponse,
+    /// When the request that got the response was sent.
+    request_time: u64,
+    /// When the response was received.
+    response_time: u64,
+    /// The request headers named by the response's Vary header, as they were
+    /// sent.
+    vary: HeaderMap,
+}
+
+impl CacheEntry {
+    pub fn response(&self) -> HttpResponse {
+        self.response.clone()
+    }
+
+    pub fn request_time(&self) -> u64 {
+        self.request_time
+    }
+
+    pub fn response_time(&self) -> u64 {
+        self.response_time
+    }
+
+    /// Serializes the entry, e.g. to write it to a file. The body is stored
+    /// as it was after Content-Encoding was decoded.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(
+            format!(
+                "{} {} {}\r\n",
+                ENTRY_MAGIC, self.request_time, self.response_time
+            )
+            .as_bytes(),
+        );
+        write_headers(&mut bytes, &self.vary);
+        bytes.extend_from_slice(b"\r\n");
+
+        let response = &self.response;
+        isomorphic_encode(
+            &mut bytes,
+            &format!(
+                "{} {} {}\r\n",
+                response.version, response.status_code, response.reason
+            ),
+        );
+        write_headers(&mut bytes, &response.headers);
+        bytes.extend_from_slice(b"\r\n");
+        bytes.extend_from_slice(&response.body);
+        bytes
+    }
+
+    /// Reads an entry written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let (lines, rest) = split_head(bytes)?;
+        let ((_, first), vary_lines) = lines
+            .split_first()
+            .ok_or(Error::parse(ParseErrorKind::IncompleteHead, 0))?;
+        let first = isomorphic_decode(first);
+        let invalid =
+            |offset| Error::parse(ParseErrorKind::InvalidCacheEntry(first.clone()), offset);
+        let mut fields = first.split(' ');
+        if fields.next() != Some(ENTRY_MAGIC) {
+            return Err(invalid(0));
+        }
+        // 誤りは読めなかった時刻の位置で報告する
+        let mut times = [0u64; 2];
+        let mut offset = ENTRY_MAGIC.len();
+        for time in times.iter_mut() {
+            offset += 1;
+            let field = fields.next().ok_or_else(|| invalid(first.len()))?;
+            *time = field.parse().map_err(|_| invalid(offset))?;
+            offset += field.len();
+        }
+        let [request_time, response_time] = times;
+        let mut vary = HeaderMap::new();
+        for &(offset, line) in vary_lines {
+            let header = parse_header_line(line).map_err(|e| e.offset_by(offset))?;
+            vary.append(&header.name(), &header.value());
+        }
+
+        // 応答の誤りもエントリ全体での位置で報告する
+        let start = bytes.len() - rest.len();
+        let (lines, body) = split_head(rest).map_err(|e| e.offset_by(start))?;
+        let (&(offset, status_line), header_lines) = lines
+            .split_first()
+            .ok_or(Error::parse(ParseErrorKind::IncompleteHead, bytes.len()))?;
+        let (version, status_code, reason) =
+            parse_status_line(status_line).map_err(|e| e.offset_by(start + offset))?;
+        let mut headers = HeaderMap::new();
+        for &(offset, line) in header_lines {
+            let header = parse_header_line(line).map_err(|e| e.offset_by(start + offset))?;
+            headers.append(&header.name(), &header.value());
+        }
+
+        Ok(Self {
+            response: HttpResponse {
+                version,
+                status_code,
+                reason,
+                headers,
+                body: body.to_vec(),
+                trailers: HeaderMap::new(),
+                redirect_chain: Vec::new(),
+                url: None,
+            },
+            request_time,
+            response_time,
+            vary,
+        })
+    }
+
+    /// Returns the age of the response at `now`.
+    ///
+    /// https://www.rfc-editor.org/rfc/rfc9111#section-4.2.3
+    fn current_age(&self, now: u64) -> u64 {
+        let headers = &self.response.headers;
+        let date = self.date();
+        let age = headers
+            .get("Age")
+            .and_then(|age| age.trim().parse::<u64>().ok())
+            .unwrap_or(0);
+
+        let apparent_age = self.response_time.saturating_sub(date);
+        let response_delay = self.response_time.saturating_sub(self.request_time);
+        let corrected_initial_age = apparent_age.max(age.saturating_add(response_delay));
+        let resident_time = now.saturating_sub(self.response_time);
+        corrected_initial_age.saturating_add(resident_time)
+    }
+
+    /// Returns how long the response stays fresh after it was generated:
+    /// max-age, else Expires, else a tenth of the time since Last-Modified.
+    ///
+    /// https://www.rfc-editor.org/rfc/rfc9111#section-4.2.1
+    fn freshness_lifetime(&self) -> u64 {
+        let headers = &self.response.headers;
+        if let Some(max_age) = CacheControl::parse(headers).max_age {
+            return max_age;
+        }
+        if let Some(expires) = headers.get("Expires") {
+            // 解釈できない Expires は既に期限切れとみなす
+            return parse_http_date(&expires)
+                .map_or(0, |expires| expires.saturating_sub(self.date()));
+        }
+        match headers
+            .get("Last-Modified")
+            .and_then(|lm| parse_http_date(&lm))
+        {
+            Some(last_modified)
+                if HEURISTICALLY_CACHEABLE.contains(&self.response.status_code.as_u16()) =>
+            {
+                self.date().saturating_sub(last_modified) / 10
+            }
+            _ => 0,
+        }
+    }
+
+    /// Returns the Date header, or the time the response was received if it
+    /// has none.
+    fn date(&self) -> u64 {
+        self.response
+            .headers
+            .get("Date")
+            .and_then(|date| parse_http_date(&date))
+            .unwrap_or(self.response_time)
+    }
+
+    /// Returns true if the response can be used without revalidating it.
+    fn is_fresh(&self, now: u64, request_directives: &CacheControl) -> bool {
+        if CacheControl::parse(&self.response.headers).no_cache || request_directives.no_cache {
+            return false;
+        }
+        let age = self.current_age(now);
+        if request_directives
+            .max_age
+            .is_some_and(|max_age| age > max_age)
+        {
+            return false;
+        }
+        self.freshness_lifetime() > age
+    }
+
+    /// Returns true if `request` sends the same values as the stored request
+    /// for the headers named by Vary.
+    fn matches(&self, request: &HttpRequest) -> bool {
+        let headers = request.headers();
+        vary_names(&self.response.headers)
+            .iter()
+            .all(|name| headers.get_all(name).join(", ") == self.vary.get_all(name).join(", "))
+    }
+
+    /// Returns the stored response with its Age header set.
+    fn served(&self, now: u64) -> HttpResponse {
+        let mut response = self.response.clone();
+        response
+            .headers
+            .insert("Age", &self.current_age(now).to_string());
+        response
+    }
+}
+
+/// The Cache-Control directives the cache acts on. Others are ignored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct CacheControl {
+    max_age: Option<u64>,
+    no_cache: bool,
+    no_store: bool,
+    must_revalidate: bool,
+    public: bool,
+}
+
+impl CacheControl {
+    fn parse(headers: &HeaderMap) -> Self {
+        let mut directives = Self::default();
+        for directive in headers.get_list("Cache-Control") {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.as_str(), None),
+            };
+            match name.to_ascii_lowercase().as_str() {
+                // 数値でない max-age は期限切れとして扱う
+                "max-age" => {
+                    directives.max_age = Some(value.and_then(|v| v.parse().ok()).unwrap_or(0))
+                }
+                "no-cache" => directives.no_cache = true,
+                "no-store" => directives.no_store = true,
+                "must-revalidate" => directives.must_revalidate = true,
+                "public" => directives.public = true,
+                _ => {}
+            }
+        }
+        directives
+    }
+}
+
+/// Sits between a browser and the network, answering GET requests from its
+/// storage when it can. Times given to it are seconds since the Unix epoch.
+#[derive(Debug)]
+pub struct HttpCache<S> {
+    storage: S,
+}
+
+impl<S: CacheStorage> HttpCache<S> {
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn storage_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+
+    /// Answers `request`: from storage if the stored response is fresh,
+    /// otherwise by sending it with `send`. A stale response with an ETag or
+    /// Last-Modified is revalidated with If-None-Match or If-Modified-Since,
+    /// and kept if the server answers 304 Not Modified.
+    pub fn fetch<F>(
+        &mut self,
+        request: &HttpRequest,
+        now: u64,
+        mut send: F,
+    ) -> Result<HttpResponse, Error>
+    where
+        F: FnMut(&HttpRequest) -> Result<HttpResponse, Error>,
+    {
+        let key = match cache_key(request) {
+            Some(key) => key,
+            None => return send(request),
+        };
+        let method = request.method();
+        if !method.eq_ignore_ascii_case("GET") {
+            let response = send(request)?;
+            // 成功した安全でない要求は、その URL の応答を変えたかもしれない
+            let safe = ["HEAD", "OPTIONS", "TRACE"]
+                .iter()
+                .any(|m| m.eq_ignore_ascii_case(&method));
+            let status_code = response.status_code();
+            if !safe && (status_code.is_success() || status_code.is_redirect()) {
+                self.storage.remove(&key);
+            }
+            return Ok(response);
+        }
+
+        let headers = request.headers();
+        let request_directives = CacheControl::parse(&headers);
+        // 呼び出し側が自分で条件付き要求を作ったときは口を出さない
+        let conditional = ["If-None-Match", "If-Modified-Since", "If-Match", "Range"]
+            .iter()
+            .any(|name| headers.contains(name));
+        if request_directives.no_store || conditional {
+            return send(request);
+        }
+
+        let entry = self
+            .storage
+            .get(&key)
+            .filter(|entry| entry.matches(request));
+        if let Some(entry) = &entry {
+            if entry.is_fresh(now, &request_directives) {
+                return Ok(entry.served(now));
+            }
+        }
+
+        let revalidation = match &entry {
+            Some(entry) => revalidation_request(request, entry)?,
+            None => None,
+        };
+        let response = send(revalidation.as_ref().unwrap_or(request))?;
+
+        let stale = entry.is_some();
+        if let (Some(entry), Some(_)) = (entry, &revalidation) {
+            if response.status_code() == StatusCode::NOT_MODIFIED {
+                let entry = revalidated(entry, &response, now);
+                self.storage.put(&key, entry.clone());
+                return Ok(entry.served(now));
+            }
+        }
+
+        let mut stored = false;
+        if is_storable(request, &response) {
+            let entry = CacheEntry {
+                vary: vary_headers(request, &response),
+                response: response.clone(),
+                request_time: now,
+                response_time: now,
+            };
+            if entry.freshness_lifetime() > 0 || has_validator(&response) {
+                self.storage.put(&key, entry);
+                stored = true;
+            }
+        }
+        // 古いエントリを残すと、後の 304 でサーバが保存を禁じた本文を返してしまう
+        if stale && !stored {
+            self.storage.remove(&key);
+        }
+        Ok(response)
+    }
+}
+
+/// Returns the URL `request` is for, without the fragment.
+fn cache_key(request: &HttpRequest) -> Option<String> {
+    let url = request_url(request)?.to_string();
+    Some(match url.split_once('#') {
+        Some((url, _)) => url.to_string(),
+        None => url,
+    })
+}
+
+/// Returns `request` with If-None-Match and If-Modified-Since taken from
+/// the stored response, or `None` if it has no validator.
+fn revalidation_request(
+    request: &HttpRequest,
+    entry: &CacheEntry,
+) -> Result<Option<HttpRequest>, Error> {
+    let headers = &entry.response.headers;
+    let etag = headers.get("ETag");
+    let last_modified = headers.get("Last-Modified");
+    if etag.is_none() && last_modified.is_none() {
+        return Ok(None);
+    }
+    let mut builder = request.to_builder();
+    if let Some(etag) = etag {
+        builder = builder.header("If-None-Match", &etag);
+    }
+    if let Some(last_modified) = last_modified {
+        builder = builder.header("If-Modified-Since", &last_modified);
+    }
+    builder.build().map(Some)
+}
+
+/// Updates the stored response with the headers of a 304 response to a
+/// revalidation request.
+///
+/// https://www.rfc-editor.org/rfc/rfc9111#section-4.3.4
+fn revalidated(entry: CacheEntry, not_modified: &HttpResponse, now: u64) -> CacheEntry {
+    let mut entry = entry;
+    let updates: Vec<_> = not_modified
+        .headers
+        .iter()
+        .filter(|header| {
+            !NOT_UPDATED_HEADERS
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(&header.name()))
+        })
+        .collect();
+    for header in &updates {
+        entry.response.headers.remove(&header.name());
+    }
+    for header in &updates {
+        entry
+            .response
+            .headers
+            .append(&header.name(), &header.value());
+    }
+    entry.request_time = now;
+    entry.response_time = now;
+    entry
+}
+
+/// Returns true if the cache may store `response` to the GET `request`.
+///
+/// https://www.rfc-editor.org/rfc/rfc9111#section-3
+fn is_storable(request: &HttpRequest, response: &HttpResponse) -> bool {
+    let directives = CacheControl::parse(&response.headers);
+    // 部分的な応答はまとめられないので保存しない
+    let status_code = response.status_code;
+    if status_code.is_informational()
+        || status_code == StatusCode::PARTIAL_CONTENT
+        || status_code == StatusCode::NOT_MODIFIED
+    {
+        return false;
+    }
+    if directives.no_store || vary_names(&response.headers).iter().any(|n| n == "*") {
+        return false;
+    }
+    // 認証付きの応答は、明示的に許されたときだけ
+    if request.headers().contains("Authorization")
+        && !directives.public
+        && !directives.must_revalidate
+    {
+        return false;
+    }
+    directives.max_age.is_some()
+        || response.headers.contains("Expires")
+        || HEURISTICALLY_CACHEABLE.contains(&status_code.as_u16())
+}
+
+fn has_validator(response: &HttpResponse) -> bool {
+    response.headers.contains("ETag") || response.headers.contains("Last-Modified")
+}
+
+fn vary_names(headers: &HeaderMap) -> Vec<String> {
+    headers.get_list("Vary")
+}
+
+/// Returns the request headers named by the response's Vary header.
+fn vary_headers(request: &HttpRequest, response: &HttpResponse) -> HeaderMap {
+    let headers = request.headers();
+    let mut vary = HeaderMap::new();
+    for name in vary_names(&response.headers) {
+        for value in headers.get_all(&name) {
+            vary.append(&name, &value);
+        }
+    }
+    vary
+}
+
+fn write_headers(bytes: &mut Vec<u8>, headers: &HeaderMap) {
+    for header in headers {
+        isomorphic_encode(bytes, &format!("{}: {}\r\n", header.name(), header.value()));
+    }
+}
+
+/// The inverse of `isomorphic_decode`, so header bytes that are not UTF-8
+/// survive a round trip.
+fn isomorphic_encode(bytes: &mut Vec<u8>, s: &str) {
+    for c in s.chars() {
+        match u8::try_from(c) {
+            Ok(b) => bytes.push(b),
+            Err(_) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::NetworkError;
+    use crate::url::Url;
+
+    fn get(url: &str) -> HttpRequest {
+        let url = Url::new(url.to_string()).unwrap();
+        HttpRequest::builder_for_url("GET", &url).build().unwrap()
+    }
+
+    fn response(raw: &str) -> HttpResponse {
+        HttpResponse::new(raw.as_bytes().to_vec()).unwrap()
+    }
+
+    /// Answers requests with `responses` in order and records the requests.
+    struct Server {
+        responses: Vec<HttpResponse>,
+        requests: Vec<HttpRequest>,
+    }
+
+    impl Server {
+        fn new(responses: &[&str]) -> Self {
+            Self {
+                responses: responses.iter().rev().map(|raw| response(raw)).collect(),
+                requests: Vec::new(),
+            }
+        }
+
+        fn send(&mut self, request: &HttpRequest) -> Result<HttpResponse, Error> {
+            self.requests.push(request.clone());
+            self.responses.pop().ok_or_else(|| {
+                Error::Network(NetworkError::Connect("no more responses".to_string()))
+            })
+        }
+    }
+
+    #[test]
+    fn test_freshness_lifetime() {
+        let date = "Date: Sun, 06 Nov 1994 08:49:37 GMT\r\n";
+        // (headers, freshness lifetime)
+        let cases: &[(String, u64)] = &[
+            ("Cache-Control: max-age=60\r\n".to_string(), 60),
+            ("Cache-Control: public, MAX-AGE=\"60\"\r\n".to_string(), 60),
+            ("Cache-Control: max-age=abc\r\n".to_string(), 0),
+            (
+                format!("{}Expires: Sun, 06 Nov 1994 09:49:37 GMT\r\n", date),
+                3600,
+            ),
+            // max-age が Expires より優先される
+            (
+                format!(
+                    "{}Expires: Sun, 06 Nov 1994 09:49:37 GMT\r\nCache-Control: max-age=5\r\n",
+                    date
+                ),
+                5,
+            ),
+            (format!("{}Expires: 0\r\n", date), 0),
+            (
+                format!("{}Expires: Sun, 06 Nov 1994 07:49:37 GMT\r\n", date),
+                0,
+            ),
+            (
+                format!("{}Last-Modified: Sun, 06 Nov 1994 06:09:37 GMT\r\n", date),
+                960,
+            ),
+            ("".to_string(), 0),
+        ];
+        for (headers, expected) in cases {
+            let raw = format!("HTTP/1.1 200 OK\r\n{}Content-Length: 0\r\n\r\n", headers);
+            let entry = CacheEntry {
+                response: response(&raw),
+                request_time: 784111777,
+                response_time: 784111777,
+                vary: HeaderMap::new(),
+            };
+            assert_eq!(entry.freshness_lifetime(), *expected, "{:?}", headers);
+        }
+    }
+
+    #[test]
+    fn test_current_age() {
+        let entry = CacheEntry {
+            response: response(
+                "HTTP/1.1 200 OK\r\nDate: Thu, 01 Jan 1970 00:01:40 GMT\r\nAge: 30\r\n\r\n",
+            ),
+            request_time: 100,
+            response_time: 110,
+            vary: HeaderMap::new(),
+        };
+        // Age + 往復の時間 + 保存されていた時間
+        assert_eq!(entry.current_age(110), 40);
+        assert_eq!(entry.current_age(200), 130);
+
+        // Date からの経過時間の方が大きければそちらを使う
+        let entry = CacheEntry {
+            response: response("HTTP/1.1 200 OK\r\nDate: Thu, 01 Jan 1970 00:00:00 GMT\r\n\r\n"),
+            request_time: 100,
+            response_time: 100,
+            vary: HeaderMap::new(),
+        };
+        assert_eq!(entry.current_age(100), 100);
+    }
+
+    #[test]
+    fn test_fresh_response_is_served_from_cache() {
+        let mut cache = HttpCache::new(MemoryStorage::new());
+        let mut server = Server::new(&[
+            "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 5\r\n\r\nfirst",
+            "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 6\r\n\r\nsecond",
+        ]);
+        let request = get("http://example.com/a#top");
+
+        let res = cache.fetch(&request, 1000, |r| server.send(r)).unwrap();
+        assert_eq!(res.body(), b"first");
+        let res = cache
+            .fetch(&get("http://example.com/a"), 1059, |r| server.send(r))
+            .unwrap();
+        assert_eq!(res.body(), b"first");
+        assert_eq!(res.headers().get("Age").as_deref(), Some("59"));
+        assert_eq!(server.requests.len(), 1);
+
+        // 期限が切れて、検証子もないので取り直す
+        let res = cache.fetch(&request, 1060, |r| server.send(r)).unwrap();
+        assert_eq!(res.body(), b"second");
+        assert_eq!(server.requests.len(), 2);
+        assert!(!server.requests[1].headers().contains("If-None-Match"));
+    }
+
+    #[test]
+    fn test_revalidation() {
+        let mut cache = HttpCache::new(MemoryStorage::new());
+        let mut server = Server::new(&[
+            "HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v1\"\r\nLast-Modified: Sun, 06 Nov 1994 08:49:37 GMT\r\nContent-Type: text/html\r\nContent-Length: 4\r\n\r\nbody",
+            "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\nCache-Control: max-age=10\r\nContent-Length: 99\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nCache-Control: max-age=10\r\nContent-Length: 3\r\n\r\nnew",
+        ]);
+        let request = get("http://example.com/");
+
+        cache.fetch(&request, 0, |r| server.send(r)).unwrap();
+        // no-cache なので毎回検証する
+        let res = cache.fetch(&request, 0, |r| server.send(r)).unwrap();
+        assert_eq!(res.status_code(), 200);
+        assert_eq!(res.body(), b"body");
+        assert_eq!(
+            res.headers().get("Content-Type").as_deref(),
+            Some("text/html")
+        );
+        assert_eq!(res.headers().get("Content-Length").as_deref(), Some("4"));
+        assert_eq!(
+            res.headers().get("Cache-Control").as_deref(),
+            Some("max-age=10")
+        );
+        let sent = server.requests[1].headers();
+        assert_eq!(sent.get("If-None-Match").as_deref(), Some("\"v1\""));
+        assert_eq!(
+            sent.get("If-Modified-Since").as_deref(),
+            Some("Sun, 06 Nov 1994 08:49:37 GMT")
+        );
+
+        // 304 で新しくなった max-age の間はキャッシュから
+        let res = cache.fetch(&request, 9, |r| server.send(r)).unwrap();
+        assert_eq!(res.body(), b"body");
+        assert_eq!(server.requests.len(), 2);
+
+        // 変わっていれば新しい応答に置き換える
+        let res = cache.fetch(&request, 10, |r| server.send(r)).unwrap();
+        assert_eq!(res.body(), b"new");
+        let res = cache.fetch(&request, 11, |r| server.send(r)).unwrap();
+        assert_eq!(res.body(), b"new");
+        assert_eq!(server.requests.len(), 3);
+    }
+
+    #[test]
+    fn test_revalidation_not_stored() {
+        // (response to the revalidation)
+        let cases = [
+            "HTTP/1.1 200 OK\r\nCache-Control: no-store\r\nETag: \"v2\"\r\nContent-Length: 3\r\n\r\nnew",
+            // 検証子も新鮮さの情報もない
+            "HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nnew",
+        ];
+        for raw in cases {
+            let mut cache = HttpCache::new(MemoryStorage::new());
+            let mut server = Server::new(&[
+                "HTTP/1.1 200 OK\r\nCache-Control: no-cache\r\nETag: \"v1\"\r\nContent-Length: 4\r\n\r\nbody",
+                raw,
+                "HTTP/1.1 304 Not Modified\r\nETag: \"v1\"\r\n\r\n",
+            ]);
+            let request = get("http://example.com/");
+
+            cache.fetch(&request, 0, |r| server.send(r)).unwrap();
+            let res = cache.fetch(&request, 0, |r| server.send(r)).unwrap();
+            assert_eq!(res.body(), b"new", "{}", raw);
+            assert!(
+                cache.storage().get("http://example.com/").is_none(),
+                "{}",
+                raw
+            );
+
+            // 古いエントリの ETag で検証しないので、304 も古い本文にはならない
+            let res = cache.fetch(&request, 0, |r| server.send(r)).unwrap();
+            assert_eq!(res.status_code(), 304, "{}", raw);
+            assert!(res.body().is_empty(), "{}", raw);
+            assert!(!server.requests[2].headers().contains("If-None-Match"));
+        }
+    }
+
+    #[test]
+    fn test_request_directives() {
+        let mut cache = HttpCache::new(MemoryStorage::new());
+        let fresh = "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 0\r\n\r\n";
+        let mut server = Server::new(&[fresh, fresh, fresh, fresh]);
+        let url = Url::new("http://example.com/".to_string()).unwrap();
+        let with = |cache_control: &str| {
+            HttpRequest::builder_for_url("GET", &url)
+                .header("Cache-Control", cache_control)
+                .build()
+                .unwrap()
+        };
+
+        cache
+            .fetch(&get("http://example.com/"), 0, |r| server.send(r))
+            .unwrap();
+        cache
+            .fetch(&with("no-cache"), 1, |r| server.send(r))
+            .unwrap();
+        assert_eq!(server.requests.len(), 2);
+        cache
+            .fetch(&with("max-age=0"), 2, |r| server.send(r))
+            .unwrap();
+        assert_eq!(server.requests.len(), 3);
+        cache
+            .fetch(&with("max-age=5"), 3, |r| server.send(r))
+            .unwrap();
+        assert_eq!(server.requests.len(), 3);
+        cache
+            .fetch(&with("no-store"), 4, |r| server.send(r))
+            .unwrap();
+        assert_eq!(server.requests.len(), 4);
+    }
+
+    #[test]
+    fn test_not_stored() {
+        // (request headers, response)
+        let cases: &[(&[(&str, &str)], &str)] = &[
+            (
+                &[],
+                "HTTP/1.1 200 OK\r\nCache-Control: no-store, max-age=60\r\n\r\n",
+            ),
+            (
+                &[],
+                "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nVary: *\r\n\r\n",
+            ),
+            (
+                &[],
+                "HTTP/1.1 206 Partial Content\r\nCache-Control: max-age=60\r\n\r\n",
+            ),
+            // 鮮度も検証子もなければ使い道がない
+            (&[], "HTTP/1.1 200 OK\r\n\r\n"),
+            (
+                &[],
+                "HTTP/1.1 500 Internal Server Error\r\nETag: \"x\"\r\n\r\n",
+            ),
+            (
+                &[("Authorization", "Basic eDp5")],
+                "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\n\r\n",
+            ),
+            (
+                &[("Cache-Control", "no-store")],
+                "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\n\r\n",
+            ),
+        ];
+        let url = Url::new("http://example.com/".to_string()).unwrap();
+        for (headers, raw) in cases {
+            let mut builder = HttpRequest::builder_for_url("GET", &url);
+            for (name, value) in *headers {
+                builder = builder.header(name, value);
+            }
+            let request = builder.build().unwrap();
+            let mut cache = HttpCache::new(MemoryStorage::new());
+            let mut server = Server::new(&[raw]);
+            cache.fetch(&request, 0, |r| server.send(r)).unwrap();
+            assert!(cache.storage().is_empty(), "{:?} {:?}", headers, raw);
+        }
+
+        // Authorization があっても public なら保存する
+        let mut cache = HttpCache::new(MemoryStorage::new());
+        let mut server =
+            Server::new(&["HTTP/1.1 200 OK\r\nCache-Control: public, max-age=60\r\n\r\n"]);
+        let request = HttpRequest::builder_for_url("GET", &url)
+            .header("Authorization", "Basic eDp5")
+            .build()
+            .unwrap();
+        cache.fetch(&request, 0, |r| server.send(r)).unwrap();
+        assert_eq!(cache.storage().len(), 1);
+    }
+
+    #[test]
+    fn test_vary() {
+        let mut cache = HttpCache::new(MemoryStorage::new());
+        let raw = "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nVary: Accept-Language\r\nContent-Length: 0\r\n\r\n";
+        let mut server = Server::new(&[raw, raw]);
+        let url = Url::new("http://example.com/".to_string()).unwrap();
+        let with = |language: &str| {
+            HttpRequest::builder_for_url("GET", &url)
+                .header("Accept-Language", language)
+                .build()
+                .unwrap()
+        };
+
+        cache.fetch(&with("ja"), 0, |r| server.send(r)).unwrap();
+        cache.fetch(&with("ja"), 1, |r| server.send(r)).unwrap();
+        assert_eq!(server.requests.len(), 1);
+        cache.fetch(&with("en"), 2, |r| server.send(r)).unwrap();
+        assert_eq!(server.requests.len(), 2);
+    }
+
+    #[test]
+    fn test_unsafe_method_invalidates() {
+        let mut cache = HttpCache::new(MemoryStorage::new());
+        let mut server = Server::new(&[
+            "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+        ]);
+        let url = Url::new("http://example.com/a".to_string()).unwrap();
+        cache
+            .fetch(&get("http://example.com/a"), 0, |r| server.send(r))
+            .unwrap();
+        assert_eq!(cache.storage().len(), 1);
+
+        let post = HttpRequest::builder_for_url("POST", &url).build().unwrap();
+        cache.fetch(&post, 1, |r| server.send(r)).unwrap();
+        assert!(cache.storage().is_empty());
+    }
+
+    #[test]
+    fn test_entry_round_trip() {
+        let mut vary = HeaderMap::new();
+        vary.append("Accept-Language", "ja");
+        let entry = CacheEntry {
+            // Latin-1 のヘッダ値も元のバイトに戻る
+            response: HttpResponse::new(
+                b"HTTP/1.1 404 Not Found\r\nETag: \"\xe9\"\r\nX-Empty:\r\nContent-Length: 6\r\n\r\n\r\n\0body"
+                    .to_vec(),
+            )
+            .unwrap(),
+            request_time: 5,
+            response_time: 7,
+            vary,
+        };
+        let bytes = entry.to_bytes();
+        let read = CacheEntry::from_bytes(&bytes).unwrap();
+        assert_eq!(read.request_time(), 5);
+        assert_eq!(read.response_time(), 7);
+        assert_eq!(read.vary, entry.vary);
+        let (a, b) = (read.response(), entry.response());
+        assert_eq!(
+            (
+                a.version(),
+                a.status_code(),
+                a.reason(),
+                a.headers(),
+                a.body()
+            ),
+            (
+                b.version(),
+                b.status_code(),
+                b.reason(),
+                b.headers(),
+                b.body()
+            )
+        );
+        assert_eq!(read.to_bytes(), bytes);
+
+        // (bytes, kind, offset)
+        let cases: &[(&[u8], ParseErrorKind, usize)] = &[
+            (b"", ParseErrorKind::IncompleteHead, 0),
+            (
+                b"RWB-CACHE/1 5\r\n\r\nHTTP/1.1 200 OK\r\n\r\n",
+                ParseErrorKind::InvalidCacheEntry("RWB-CACHE/1 5".to_string()),
+                13,
+            ),
+            (
+                b"RWB-CACHE/1 5 x\r\n\r\nHTTP/1.1 200 OK\r\n\r\n",
+                ParseErrorKind::InvalidCacheEntry("RWB-CACHE/1 5 x".to_string()),
+                14,
+            ),
+            (
+                b"RWB-CACHE/1 -5 7\r\n\r\nHTTP/1.1 200 OK\r\n\r\n",
+                ParseErrorKind::InvalidCacheEntry("RWB-CACHE/1 -5 7".to_string()),
+                12,
+            ),
+            (
+                b"RWB-CACHE/2 5 7\r\n\r\nHTTP/1.1 200 OK\r\n\r\n",
+                ParseErrorKind::InvalidCacheEntry("RWB-CACHE/2 5 7".to_string()),
+                0,
+            ),
+            (
+                b"RWB-CACHE/1 5 7\r\n\r\nHTTP/1.1 200 OK\r\n",
+                ParseErrorKind::IncompleteHead,
+                36,
+            ),
+            (
+                b"RWB-CACHE/1 5 7\r\n\r\nnot a status line\r\n\r\n",
+                ParseErrorKind::InvalidStatusLine("not a status line".to_string()),
+                19,
+            ),
+        ];
+        for (bytes, kind, offset) in cases {
+            assert_eq!(
+                CacheEntry::from_bytes(bytes).err(),
+                Some(Error::parse(kind.clone(), *offset)),
+                "{:?}",
+                bytes
+            );
+        }
+    }
+
+    #[test]
+    fn test_boxed_storage() {
+        let mut cache: HttpCache<Box<dyn CacheStorage>> =
+            HttpCache::new(Box::new(MemoryStorage::new()));
+        let mut server = Server::new(&[
+            "HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nContent-Length: 0\r\n\r\n",
+        ]);
+        let request = get("http://example.com/");
+        cache.fetch(&request, 0, |r| server.send(r)).unwrap();
+        cache.fetch(&request, 1, |r| server.send(r)).unwrap();
+        assert_eq!(server.requests.len(), 1);
+        assert!(cache.storage().get("http://example.com/").is_some());
+    }
+}
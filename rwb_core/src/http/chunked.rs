@@ -0,0 +1,457 @@
+//! Decoder for the chunked transfer coding.
+//!
+//! https://www.rfc-editor.org/rfc/rfc9112#section-7.1
+
+use super::isomorphic_decode;
+use super::parse_header_line;
+use super::parser::DEFAULT_MAX_HEADER_SIZE;
+use super::HeaderMap;
+use crate::error::Error;
+use crate::error::ParseErrorKind;
+use alloc::format;
+use alloc::vec::Vec;
+
+/// The default limit on the length of a chunk-size line with its extensions
+/// and line ending, 4 KiB.
+pub const DEFAULT_MAX_CHUNK_LINE_SIZE: usize = 4 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Reading a `chunk-size [ chunk-ext ]` line.
+    Size,
+    /// Reading chunk data, with this many bytes left.
+    Data(usize),
+    /// Reading the CRLF after chunk data.
+    DataEnd,
+    /// Reading trailer lines after the last chunk.
+    Trailer,
+    Done,
+}
+
+/// A push-based chunked decoder. Bytes can be fed in pieces of any size, so
+/// the same decoder serves complete responses and data read from a socket.
+///
+/// Parse errors are reported at offsets counted from the first byte pushed.
+#[derive(Debug, Clone)]
+pub struct ChunkedDecoder {
+    state: State,
+    /// The number of bytes consumed so far.
+    position: usize,
+    line: Vec<u8>,
+    /// The offset of the start of `line`.
+    line_start: usize,
+    /// The offset of the start of the trailer section.
+    trailer_start: usize,
+    max_line_size: usize,
+    max_trailer_size: usize,
+    body: Vec<u8>,
+    trailers: HeaderMap,
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> Self {
+        Self {
+            state: State::Size,
+            position: 0,
+            line: Vec::new(),
+            line_start: 0,
+            trailer_start: 0,
+            max_line_size: DEFAULT_MAX_CHUNK_LINE_SIZE,
+            max_trailer_size: DEFAULT_MAX_HEADER_SIZE,
+            body: Vec::new(),
+            trailers: HeaderMap::new(),
+        }
+    }
+
+    /// Sets the limit on the length of a chunk-size line, including its
+    /// extensions and line ending. Exceeding it makes `push` fail with
+    /// `InvalidChunk`.
+    pub fn set_max_line_size(&mut self, max_line_size: usize) {
+        self.max_line_size = max_line_size;
+    }
+
+    /// Sets the limit on the size of the trailer section, including line
+    /// endings. Exceeding it makes `push` fail with `HeadTooLarge`.
+    pub fn set_max_trailer_size(&mut self, max_trailer_size: usize) {
+        self.max_trailer_size = max_trailer_size;
+    }
+
+    /// Decodes as much of `input` as possible and returns the number of bytes
+    /// consumed. Once the message is complete nothing more is consumed, so
+    /// the rest of `input` belongs to whatever follows the message.
+    pub fn push(&mut self, input: &[u8]) -> Result<usize, Error> {
+        let mut consumed = 0;
+        while consumed < input.len() && self.state != State::Done {
+            let rest = &input[consumed..];
+            match self.state {
+                State::Data(remaining) => {
+                    let n = remaining.min(rest.len());
+                    self.body.extend_from_slice(&rest[..n]);
+                    consumed += n;
+                    self.position += n;
+                    self.state = if n == remaining {
+                        State::DataEnd
+                    } else {
+                        State::Data(remaining - n)
+                    };
+                }
+                _ => {
+                    let (n, complete) = match rest.iter().position(|b| *b == b'\n') {
+                        Some(i) => (i + 1, true),
+                        None => (rest.len(), false),
+                    };
+                    if self.line.is_empty() {
+                        self.line_start = self.position;
+                    }
+                    // 行の終わりが来なくても、上限を超えた時点で止める
+                    if self.state == State::Trailer
+                        && self.position - self.trailer_start + n > self.max_trailer_size
+                    {
+                        return Err(Error::parse(
+                            ParseErrorKind::HeadTooLarge,
+                            self.trailer_start.saturating_add(self.max_trailer_size),
+                        ));
+                    }
+                    if self.state != State::Trailer && self.line.len() + n > self.max_line_size {
+                        let mut line = core::mem::take(&mut self.line);
+                        line.extend_from_slice(&rest[..n]);
+                        line.truncate(self.max_line_size);
+                        return Err(Error::parse(
+                            ParseErrorKind::InvalidChunk(isomorphic_decode(&line)),
+                            self.line_start,
+                        ));
+                    }
+                    self.line.extend_from_slice(&rest[..n]);
+                    consumed += n;
+                    self.position += n;
+                    if complete {
+                        let mut line = core::mem::take(&mut self.line);
+                        line.pop();
+                        if line.last() == Some(&b'\r') {
+                            line.pop();
+                        }
+                        self.process_line(&line)
+                            .map_err(|e| e.offset_by(self.line_start))?;
+                    }
+                }
+            }
+        }
+        Ok(consumed)
+    }
+
+    fn process_line(&mut self, line: &[u8]) -> Result<(), Error> {
+        match self.state {
+            State::Size => {
+                let size = parse_chunk_size(line)?;
+                self.state = if size == 0 {
+                    self.trailer_start = self.position;
+                    State::Trailer
+                } else {
+                    State::Data(size)
+                };
+            }
+            State::DataEnd => {
+                if !line.is_empty() {
+                    return Err(Error::parse(
+                        ParseErrorKind::InvalidChunk(isomorphic_decode(line)),
+                        0,
+                    ));
+                }
+                self.state = State::Size;
+            }
+            State::Trailer => {
+                if line.is_empty() {
+                    self.state = State::Done;
+                } else {
+                    let header = parse_header_line(line)?;
+                    self.trailers.append(&header.name(), &header.value());
+                }
+            }
+            State::Data(_) | State::Done => {}
+        }
+        Ok(())
+    }
+
+    /// Returns true once the last chunk and the trailer section are read.
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Returns the data decoded so far and not yet taken by `take_body`.
+    pub fn body(&self) -> Vec<u8> {
+        self.body.clone()
+    }
+
+    /// Takes the data decoded so far, so that a streaming caller does not
+    /// have to keep the whole body in the decoder.
+    pub fn take_body(&mut self) -> Vec<u8> {
+        core::mem::take(&mut self.body)
+    }
+
+    pub fn trailers(&self) -> HeaderMap {
+        self.trailers.clone()
+    }
+
+    /// Consumes the decoder, returning the decoded body and trailer fields.
+    pub fn finish(self) -> Result<(Vec<u8>, HeaderMap), Error> {
+        if !self.is_done() {
+            return Err(Error::parse(ParseErrorKind::IncompleteBody, self.position));
+        }
+        Ok((self.body, self.trailers))
+    }
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decodes a complete chunked body. Returns the body, the trailer fields and
+/// the number of bytes of `input` that made up the chunked message.
+pub fn decode(input: &[u8]) -> Result<(Vec<u8>, HeaderMap, usize), Error> {
+    let mut decoder = ChunkedDecoder::new();
+    let consumed = decoder.push(input)?;
+    let (body, trailers) = decoder.finish()?;
+    Ok((body, trailers, consumed))
+}
+
+/// Encodes `data` as a complete chunked body: one chunk holding all of
+/// `data`, if it is not empty, followed by the last chunk.
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    if !data.is_empty() {
+        encoded.extend_from_slice(format!("{:x}\r\n", data.len()).as_bytes());
+        encoded.extend_from_slice(data);
+        encoded.extend_from_slice(b"\r\n");
+    }
+    encoded.extend_from_slice(b"0\r\n\r\n");
+    encoded
+}
+
+// chunk = chunk-size [ chunk-ext ] CRLF
+// chunk-ext = *( BWS ";" BWS chunk-ext-name [ BWS "=" BWS chunk-ext-val ] )
+//
+// Errors are reported at offset 0, the start of `line`.
+fn parse_chunk_size(line: &[u8]) -> Result<usize, Error> {
+    let invalid = || Error::parse(ParseErrorKind::InvalidChunk(isomorphic_decode(line)), 0);
+
+    let digits = line.iter().take_while(|b| b.is_ascii_hexdigit()).count();
+    if digits == 0 {
+        return Err(invalid());
+    }
+    // 拡張は意味を持たないので読み飛ばすが、サイズの直後は空白か ';' でなければならない
+    match line[digits..].iter().find(|b| **b != b' ' && **b != b'\t') {
+        None | Some(b';') => {}
+        Some(_) => return Err(invalid()),
+    }
+    if line.iter().any(|b| b.is_ascii_control() && *b != b'\t') {
+        return Err(invalid());
+    }
+
+    line[..digits].iter().try_fold(0usize, |size, digit| {
+        let digit = (*digit as char).to_digit(16).ok_or_else(invalid)?;
+        size.checked_mul(16)
+            .and_then(|size| size.checked_add(digit as usize))
+            .ok_or_else(invalid)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    // (encoded, body, trailers)
+    type DecodeCase<'a> = (&'a [u8], &'a [u8], &'a [(&'a str, &'a str)]);
+
+    #[test]
+    fn test_decode() {
+        let cases: &[DecodeCase] = &[
+            (b"0\r\n\r\n", b"", &[]),
+            (b"5\r\nhello\r\n0\r\n\r\n", b"hello", &[]),
+            (
+                b"5\r\nhello\r\n1\r\n \r\n5\r\nworld\r\n0\r\n\r\n",
+                b"hello world",
+                &[],
+            ),
+            (b"a\r\n0123456789\r\n0\r\n\r\n", b"0123456789", &[]),
+            (b"A\r\n0123456789\r\n00\r\n\r\n", b"0123456789", &[]),
+            (b"5\nhello\n0\n\n", b"hello", &[]),
+            (b"3;name=value\r\nabc\r\n0;last\r\n\r\n", b"abc", &[]),
+            (b"3 ; a=\"x;y\"\r\nabc\r\n0\r\n\r\n", b"abc", &[]),
+            (b"2\r\n\r\n\r\n0\r\n\r\n", b"\r\n", &[]),
+            (
+                b"3\r\nabc\r\n0\r\nExpires: never\r\nX-Checksum:  42 \r\n\r\n",
+                b"abc",
+                &[("Expires", "never"), ("X-Checksum", "42")],
+            ),
+        ];
+
+        for (encoded, body, trailers) in cases {
+            let (decoded, decoded_trailers, consumed) =
+                decode(encoded).unwrap_or_else(|e| panic!("decoding {:?}: {:?}", encoded, e));
+            assert_eq!(decoded, *body, "decoding {:?}", encoded);
+            assert_eq!(consumed, encoded.len(), "decoding {:?}", encoded);
+            let expected: HeaderMap = trailers.iter().fold(HeaderMap::new(), |mut map, (n, v)| {
+                map.append(n, v);
+                map
+            });
+            assert_eq!(decoded_trailers, expected, "decoding {:?}", encoded);
+        }
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        // (encoded, kind, offset)
+        let cases: &[(&[u8], ParseErrorKind, usize)] = &[
+            (b"", ParseErrorKind::IncompleteBody, 0),
+            (b"5\r\nhel", ParseErrorKind::IncompleteBody, 6),
+            (b"5\r\nhello\r\n", ParseErrorKind::IncompleteBody, 10),
+            (b"0\r\n", ParseErrorKind::IncompleteBody, 3),
+            (b"0\r\nX: y\r\n", ParseErrorKind::IncompleteBody, 9),
+            (b"\r\n", ParseErrorKind::InvalidChunk("".to_string()), 0),
+            (
+                b"x\r\n\r\n",
+                ParseErrorKind::InvalidChunk("x".to_string()),
+                0,
+            ),
+            (
+                b"-1\r\n\r\n",
+                ParseErrorKind::InvalidChunk("-1".to_string()),
+                0,
+            ),
+            (
+                b"5 5\r\n",
+                ParseErrorKind::InvalidChunk("5 5".to_string()),
+                0,
+            ),
+            (
+                b"0x5\r\n",
+                ParseErrorKind::InvalidChunk("0x5".to_string()),
+                0,
+            ),
+            (
+                b"fffffffffffffffffffff\r\n",
+                ParseErrorKind::InvalidChunk("fffffffffffffffffffff".to_string()),
+                0,
+            ),
+            (
+                b"3\r\nabcd\r\n",
+                ParseErrorKind::InvalidChunk("d".to_string()),
+                6,
+            ),
+            (
+                b"1\r\na\r\n2\r\nbc\r\nz\r\n",
+                ParseErrorKind::InvalidChunk("z".to_string()),
+                13,
+            ),
+            (
+                b"0\r\nbad trailer\r\n\r\n",
+                ParseErrorKind::InvalidHeaderName("bad trailer".to_string()),
+                14,
+            ),
+        ];
+
+        for (encoded, kind, offset) in cases {
+            assert_eq!(
+                decode(encoded).err(),
+                Some(Error::parse(kind.clone(), *offset)),
+                "decoding {:?}",
+                encoded
+            );
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        // (encoded, kind, offset)
+        let cases: &[(&[u8], ParseErrorKind, usize)] = &[
+            // 終わらない chunk-ext
+            (
+                b"1;aaaaaaaaaaaa",
+                ParseErrorKind::InvalidChunk("1;aaaaaa".to_string()),
+                0,
+            ),
+            (
+                b"1\r\na\r\n1;aaaaaaaaaaaa",
+                ParseErrorKind::InvalidChunk("1;aaaaaa".to_string()),
+                6,
+            ),
+            // 終わらないトレーラ
+            (
+                b"0\r\nX: y\r\nX: y\r\nX: y\r\n",
+                ParseErrorKind::HeadTooLarge,
+                19,
+            ),
+            (
+                b"0\r\nX-Never-Ending: aaaaaaaa",
+                ParseErrorKind::HeadTooLarge,
+                19,
+            ),
+        ];
+        for (encoded, kind, offset) in cases {
+            // 一度に来ても少しずつ来ても同じ位置でエラーになる
+            for size in [1, encoded.len()] {
+                let mut decoder = ChunkedDecoder::new();
+                decoder.set_max_line_size(8);
+                decoder.set_max_trailer_size(16);
+                let result: Result<Vec<_>, _> =
+                    encoded.chunks(size).map(|p| decoder.push(p)).collect();
+                assert_eq!(
+                    result.err(),
+                    Some(Error::parse(kind.clone(), *offset)),
+                    "decoding {:?} in pieces of {}",
+                    encoded,
+                    size
+                );
+            }
+        }
+
+        // ちょうど上限なら受け付ける
+        let mut decoder = ChunkedDecoder::new();
+        decoder.set_max_line_size(8);
+        decoder.set_max_trailer_size(14);
+        let encoded = b"1;aaaa\r\na\r\n0\r\nX: y\r\nX: y\r\n\r\n";
+        assert_eq!(decoder.push(encoded), Ok(encoded.len()));
+        assert!(decoder.is_done());
+    }
+
+    #[test]
+    fn test_encode() {
+        let cases: &[&[u8]] = &[b"", b"a", b"hello world", &[0u8; 300]];
+        for data in cases {
+            let encoded = encode(data);
+            let (decoded, trailers, consumed) = decode(&encoded).unwrap();
+            assert_eq!(decoded, *data);
+            assert!(trailers.is_empty());
+            assert_eq!(consumed, encoded.len());
+        }
+        assert_eq!(encode(b"hello"), b"5\r\nhello\r\n0\r\n\r\n");
+        assert_eq!(encode(&[b'x'; 26])[..4], *b"1a\r\n");
+    }
+
+    #[test]
+    fn test_push_in_pieces() {
+        let encoded =
+            b"4;x=1\r\nWiki\r\n6\r\npedia \r\nE\r\nin \r\n\r\nchunks.\r\n0\r\nX: y\r\n\r\n";
+        for size in 1..encoded.len() {
+            let mut decoder = ChunkedDecoder::new();
+            for piece in encoded.chunks(size) {
+                assert_eq!(decoder.push(piece), Ok(piece.len()));
+            }
+            assert!(decoder.is_done());
+            assert_eq!(decoder.body(), b"Wikipedia in \r\n\r\nchunks.");
+            assert_eq!(decoder.trailers().len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_push_stops_after_message() {
+        let mut decoder = ChunkedDecoder::new();
+        assert_eq!(decoder.push(b"1\r\na\r\n0\r\n\r\nHTTP/1.1 200 OK"), Ok(11));
+        assert!(decoder.is_done());
+        assert_eq!(decoder.push(b"more"), Ok(0));
+        assert_eq!(decoder.finish(), Ok((b"a".to_vec(), HeaderMap::new())));
+    }
+}
@@ -0,0 +1,719 @@
+//! An HTTP/1.1 client that works over any `Transport`. It keeps connections
+//! alive, caches responses, stores cookies and follows redirects, so a
+//! platform only has to provide sockets, and TLS for https.
+
+use super::follow_redirects;
+use super::is_reusable;
+use super::CacheStorage;
+use super::ConnectionPool;
+use super::CookieJar;
+use super::HttpCache;
+use super::HttpRequest;
+use super::HttpResponse;
+use super::MemoryStorage;
+use super::RequestContext;
+use super::ResponseParser;
+use super::DEFAULT_MAX_REDIRECTS;
+use crate::error::Error;
+use crate::error::HttpError;
+use crate::error::NetworkError;
+use crate::transport::Connection;
+use crate::transport::Transport;
+use crate::url::Url;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+/// Methods a request may be sent again for when a kept-alive connection
+/// fails, because sending them twice has the same effect as once.
+///
+/// https://www.rfc-editor.org/rfc/rfc9110#section-9.2.2
+const IDEMPOTENT_METHODS: &[&str] = &["GET", "HEAD", "PUT", "DELETE", "OPTIONS", "TRACE"];
+
+pub struct HttpClient<T: Transport> {
+    transport: T,
+    max_redirects: usize,
+    pool: RefCell<ConnectionPool<T::Connection>>,
+    /// Connections with TLS, kept apart so that an http URL never reuses one
+    /// and an https URL never reuses a plain one.
+    tls_pool: RefCell<ConnectionPool<T::Connection>>,
+    cache: RefCell<HttpCache<Box<dyn CacheStorage>>>,
+    cookies: RefCell<CookieJar>,
+    /// Milliseconds since the Unix epoch. `None` until `set_clock` is
+    /// called.
+    clock: Option<fn() -> u64>,
+}
+
+impl<T: Transport> HttpClient<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            pool: RefCell::new(ConnectionPool::new()),
+            tls_pool: RefCell::new(ConnectionPool::new()),
+            cache: RefCell::new(HttpCache::new(Box::new(MemoryStorage::new()))),
+            cookies: RefCell::new(CookieJar::new()),
+            clock: None,
+        }
+    }
+
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
+    /// Sets the clock, in milliseconds since the Unix epoch, that idle
+    /// timeouts and the age of cached responses are measured with.
+    ///
+    /// Until a clock is set, responses are neither stored in nor served
+    /// from the cache, because their age cannot be told, and idle
+    /// connections are kept without a timeout. Cookies with an expiry are
+    /// neither stored nor sent either, so only session cookies are kept.
+    pub fn set_clock(&mut self, clock: fn() -> u64) {
+        self.clock = Some(clock);
+    }
+
+    /// Replaces where cached responses are kept. The default keeps them in
+    /// memory.
+    pub fn set_cache_storage(&mut self, storage: Box<dyn CacheStorage>) {
+        *self.cache.get_mut() = HttpCache::new(storage);
+    }
+
+    /// Sets how long, in milliseconds, an idle connection is kept for reuse.
+    pub fn set_idle_timeout(&mut self, idle_timeout: u64) {
+        self.pool.get_mut().set_idle_timeout(idle_timeout);
+        self.tls_pool.get_mut().set_idle_timeout(idle_timeout);
+    }
+
+    /// Sets how many connections to one host and port may be open at once,
+    /// counting those with TLS and those without separately.
+    pub fn set_max_connections_per_host(&mut self, max_connections_per_host: usize) {
+        self.pool
+            .get_mut()
+            .set_max_connections_per_host(max_connections_per_host);
+        self.tls_pool
+            .get_mut()
+            .set_max_connections_per_host(max_connections_per_host);
+    }
+
+    /// Returns the cookies received so far, e.g. to save them with
+    /// `CookieJar::to_bytes`.
+    pub fn cookies(&self) -> CookieJar {
+        self.cookies.borrow().clone()
+    }
+
+    /// Replaces the cookies, e.g. with ones saved in an earlier session.
+    pub fn set_cookies(&mut self, cookies: CookieJar) {
+        *self.cookies.get_mut() = cookies;
+    }
+
+    /// Sets how many redirects `request` follows. 0 makes a redirect an
+    /// error.
+    pub fn set_max_redirects(&mut self, max_redirects: usize) {
+        self.max_redirects = max_redirects;
+    }
+
+    pub fn get(&self, host: String, port: u16, path: String) -> Result<HttpResponse, Error> {
+        // path が "/" で始まっていればそのまま使う
+        let target = if path.starts_with('/') {
+            path
+        } else {
+            format!("/{}", path)
+        };
+        // IPv6 アドレスは URL の中では角括弧で囲む
+        let host = if host.contains(':') && !host.starts_with('[') {
+            format!("[{}]", host)
+        } else {
+            host
+        };
+        let url = Url::new(format!("http://{}:{}{}", host, port, target))?;
+        self.get_url(&url)
+    }
+
+    /// Fetches `url` with GET. An https URL is fetched over TLS.
+    pub fn get_url(&self, url: &Url) -> Result<HttpResponse, Error> {
+        let request = HttpRequest::builder_for_url("GET", url)
+            .header("Accept", "text/html")
+            // 本文は HttpResponse が展開する
+            .header("Accept-Encoding", "gzip, deflate")
+            .build()?;
+
+        self.request(request)
+    }
+
+    /// Sends `request` and follows any redirects. The URLs redirected from
+    /// are recorded in the response's `redirect_chain()`. Each hop carries
+    /// the cookies for its URL and is answered from the cache when it can
+    /// be.
+    ///
+    /// The request is treated as a top-level navigation by the user, e.g.
+    /// to a typed URL. Use `request_from` for one made by a page.
+    pub fn request(&self, request: HttpRequest) -> Result<HttpResponse, Error> {
+        self.request_from(request, None, true)
+    }
+
+    /// Sends `request` as made by a page at `initiator`, and follows any
+    /// redirects like `request`. Which SameSite cookies a hop carries
+    /// depends on the site of the URL it was redirected from, or of
+    /// `initiator` for the first hop.
+    ///
+    /// https://datatracker.ietf.org/doc/html/draft-ietf-httpbis-rfc6265bis#name-same-site-and-cross-site-re
+    pub fn request_from(
+        &self,
+        request: HttpRequest,
+        initiator: Option<Url>,
+        is_top_level_navigation: bool,
+    ) -> Result<HttpResponse, Error> {
+        let mut initiator = initiator;
+        // 時刻が分からなければ期限も判断できないので、期限のあるクッキーは持たない。
+        // 過去の Expires で消すクッキーも、一度入れてから捨てれば消える
+        let forget_persistent_cookies = || {
+            if self.clock.is_none() {
+                self.cookies.borrow_mut().remove_persistent_cookies();
+            }
+        };
+        follow_redirects(request, self.max_redirects, |request| {
+            let now = self.now() / 1000;
+            forget_persistent_cookies();
+            let url = request.url();
+            let context = match &url {
+                Some(url) => RequestContext::new(
+                    url,
+                    initiator.as_ref(),
+                    is_top_level_navigation && request.method() == "GET",
+                ),
+                None => RequestContext::SameSite,
+            };
+            // 次のホップはこの URL からリダイレクトされたものになる
+            if url.is_some() {
+                initiator = url;
+            }
+            let request = self
+                .cookies
+                .borrow()
+                .add_cookie_header(request, context, now)?;
+            let send = |request: &HttpRequest| {
+                let response = self.send(request)?;
+                // キャッシュから返した応答のクッキーは既に受け取っている
+                if let Some(url) = request.url() {
+                    self.cookies
+                        .borrow_mut()
+                        .store_response_cookies(&url, &response, now);
+                    forget_persistent_cookies();
+                }
+                Ok(response)
+            };
+            // 時刻が分からなければ応答の古さも分からないので、キャッシュを使わない
+            match self.clock {
+                Some(_) => self.cache.borrow_mut().fetch(&request, now, send),
+                None => send(&request),
+            }
+        })
+    }
+
+    /// Returns the time from the clock, or 0 if none has been set.
+    fn now(&self) -> u64 {
+        self.clock.map_or(0, |clock| clock())
+    }
+
+    /// Sends `request` to the host and port given by its URL or Host header
+    /// and reads the response, reusing an idle connection when there is one.
+    /// The connection uses TLS if the URL is https.
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, Error> {
+        let (host, port) = request.authority().ok_or(HttpError::MissingHost)?;
+        let secure = request.url().is_some_and(|url| url.scheme() == "https");
+        let pool = if secure { &self.tls_pool } else { &self.pool };
+
+        let pooled = pool.borrow_mut().acquire(&host, port, self.now())?;
+        let reused = pooled.is_some();
+        let mut connection = match pooled {
+            Some(connection) => connection,
+            None => match self.connect(&host, port, secure) {
+                Ok(connection) => connection,
+                Err(e) => {
+                    pool.borrow_mut().release(&host, port, None, self.now());
+                    return Err(e);
+                }
+            },
+        };
+
+        let mut received = false;
+        let mut result = exchange(&mut connection, request, &mut received);
+        // 待機中にサーバが閉じた接続だったかもしれないので、冪等な要求なら新しい接続でやり直す。
+        // 応答が届き始めていたらサーバは要求を受け取っているので送り直さない
+        let idempotent = IDEMPOTENT_METHODS.contains(&request.method().as_str());
+        if reused && idempotent && !received && matches!(result, Err(Error::Network(_))) {
+            let _ = connection.close();
+            match self.connect(&host, port, secure) {
+                Ok(new_connection) => {
+                    connection = new_connection;
+                    result = exchange(&mut connection, request, &mut received);
+                }
+                Err(e) => result = Err(e),
+            }
+        }
+
+        let keep = matches!(&result, Ok(response) if is_reusable(request, response));
+        if !keep {
+            let _ = connection.close();
+        }
+        pool.borrow_mut()
+            .release(&host, port, keep.then_some(connection), self.now());
+        result
+    }
+
+    /// Connects to the first address of `host` that accepts a connection,
+    /// with TLS if `secure`.
+    fn connect(&self, host: &str, port: u16, secure: bool) -> Result<T::Connection, Error> {
+        let addresses = self.transport.resolve(host)?;
+        let mut last_error = NetworkError::Dns(format!("{}: no addresses", host)).into();
+        for address in &addresses {
+            let connection = if secure {
+                self.transport.connect_tls(address, port, host)
+            } else {
+                self.transport.connect(address, port)
+            };
+            match connection {
+                Ok(connection) => return Ok(connection),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(last_error)
+    }
+}
+
+impl<T: Transport + Default> Default for HttpClient<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+/// Writes `request` to `connection` and reads one response from it.
+/// `received` is set once any byte of the response has arrived.
+fn exchange<C: Connection>(
+    connection: &mut C,
+    request: &HttpRequest,
+    received: &mut bool,
+) -> Result<HttpResponse, Error> {
+    // 一度で書ききれないこともあるので、全部送るまで繰り返す
+    let bytes = request.to_bytes();
+    let mut written = 0;
+    while written < bytes.len() {
+        match connection.write(&bytes[written..])? {
+            0 => return Err(NetworkError::Write("connection closed".to_string()).into()),
+            n => written += n,
+        }
+    }
+
+    // 届いた分から解析し、レスポンスが終わったら接続が閉じるのを待たない
+    let mut parser = ResponseParser::new();
+    parser.set_request_method(&request.method());
+    let mut events = Vec::new();
+    while !parser.is_done() {
+        let mut buf = [0u8; 4096];
+        let bytes_read = connection.read(&mut buf)?;
+        if bytes_read == 0 {
+            events.extend(parser.finish()?);
+            break;
+        }
+        *received = true;
+        events.extend(parser.push(&buf[..bytes_read])?);
+    }
+
+    HttpResponse::from_events(events, parser.max_body_size())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CertificateError;
+    use crate::error::ParseErrorKind;
+    use crate::error::TlsError;
+    use crate::transport::mock::MockResponse;
+    use crate::transport::mock::MockTransport;
+    use alloc::vec;
+
+    /// A clock that stands still at an arbitrary time.
+    fn clock() -> u64 {
+        1_700_000_000_000
+    }
+
+    fn ok(body: &str) -> MockResponse {
+        MockResponse::new(
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        )
+    }
+
+    /// Returns the Cookie header of each request the server received.
+    fn cookie_headers(client: &HttpClient<MockTransport>) -> Vec<Option<String>> {
+        client
+            .transport()
+            .requests()
+            .iter()
+            .map(|request| {
+                String::from_utf8_lossy(request)
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Cookie: ").map(|c| c.to_string()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_get() {
+        let client = HttpClient::new(MockTransport::new());
+        client
+            .transport()
+            .respond("a.test", 8000, "/index.html", ok("hi"));
+        let response = client
+            .get("a.test".to_string(), 8000, "index.html".to_string())
+            .unwrap();
+        assert_eq!(response.body(), b"hi");
+
+        let requests = client.transport().requests();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with(b"GET /index.html HTTP/1.1\r\nHost: a.test:8000\r\n"));
+    }
+
+    #[test]
+    fn test_get_ipv6() {
+        let client = HttpClient::new(MockTransport::new());
+        client.transport().respond("::1", 8000, "/", ok("v6"));
+        for host in ["::1", "[::1]"] {
+            let response = client.get(host.to_string(), 8000, "/".to_string()).unwrap();
+            assert_eq!(response.body(), b"v6", "{}", host);
+        }
+        let requests = client.transport().requests();
+        assert!(requests[0].starts_with(b"GET / HTTP/1.1\r\nHost: [::1]:8000\r\n"));
+    }
+
+    #[test]
+    fn test_connection_is_reused() {
+        let client = HttpClient::new(MockTransport::new());
+        client.transport().respond("a.test", 80, "/a", ok("a"));
+        client.transport().respond("a.test", 80, "/b", ok("b"));
+        for (path, body) in [("/a", b"a"), ("/b", b"b")] {
+            let response = client
+                .get("a.test".to_string(), 80, path.to_string())
+                .unwrap();
+            assert_eq!(response.body(), body);
+        }
+        assert_eq!(client.transport().connections(), 1);
+    }
+
+    #[test]
+    fn test_retry_on_stale_connection() {
+        // 再利用した接続が閉じていたら、新しい接続で送り直す
+        let client = HttpClient::new(MockTransport::new());
+        client
+            .transport()
+            .respond("a.test", 80, "/a", ok("a").close());
+        client.transport().respond("a.test", 80, "/b", ok("b"));
+        for (path, body) in [("/a", b"a"), ("/b", b"b")] {
+            let response = client
+                .get("a.test".to_string(), 80, path.to_string())
+                .unwrap();
+            assert_eq!(response.body(), body);
+        }
+        assert_eq!(client.transport().connections(), 2);
+        let requests = client.transport().requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].starts_with(b"GET /b HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn test_no_retry_for_post() {
+        let client = HttpClient::new(MockTransport::new());
+        client
+            .transport()
+            .respond("a.test", 80, "/a", ok("a").close());
+        client.transport().respond("a.test", 80, "/b", ok("b"));
+        client
+            .get("a.test".to_string(), 80, "/a".to_string())
+            .unwrap();
+        let url = Url::new("http://a.test/b".to_string()).unwrap();
+        let request = HttpRequest::builder_for_url("POST", &url).build().unwrap();
+        assert_eq!(
+            client.request(request).err(),
+            Some(Error::Network(NetworkError::Write(
+                "connection reset".to_string()
+            )))
+        );
+        assert_eq!(client.transport().connections(), 1);
+    }
+
+    #[test]
+    fn test_retry_methods() {
+        // (method, retried)
+        let cases = [
+            ("GET", true),
+            ("HEAD", true),
+            ("PUT", true),
+            ("DELETE", true),
+            ("OPTIONS", true),
+            ("TRACE", true),
+            ("POST", false),
+            ("PATCH", false),
+            ("PROPFIND", false),
+            // メソッド名は大文字小文字を区別する
+            ("get", false),
+        ];
+        for (method, retried) in cases {
+            let client = HttpClient::new(MockTransport::new());
+            client
+                .transport()
+                .respond("a.test", 80, "/a", ok("a").close());
+            client.transport().respond("a.test", 80, "/b", ok("b"));
+            client
+                .get("a.test".to_string(), 80, "/a".to_string())
+                .unwrap();
+            let url = Url::new("http://a.test/b".to_string()).unwrap();
+            let request = HttpRequest::builder_for_url(method, &url).build().unwrap();
+            assert_eq!(client.request(request).is_ok(), retried, "{}", method);
+            let connections = if retried { 2 } else { 1 };
+            assert_eq!(client.transport().connections(), connections, "{}", method);
+        }
+    }
+
+    #[test]
+    fn test_no_retry_after_response_started() {
+        // (response on the reused connection, expected error)
+        let cases = [
+            (
+                MockResponse::new(b"HTTP/1.1 OK\r\n\r\n"),
+                Error::parse(ParseErrorKind::InvalidStatusCode("OK".to_string()), 9),
+            ),
+            (
+                ok("b").reset_after(5),
+                NetworkError::Read("connection reset".to_string()).into(),
+            ),
+        ];
+        for (response, expected) in cases {
+            let client = HttpClient::new(MockTransport::new());
+            client.transport().respond("a.test", 80, "/a", ok("a"));
+            client.transport().respond("a.test", 80, "/b", response);
+            client
+                .get("a.test".to_string(), 80, "/a".to_string())
+                .unwrap();
+            assert_eq!(
+                client.get("a.test".to_string(), 80, "/b".to_string()).err(),
+                Some(expected)
+            );
+            // サーバは要求を受け取っているので送り直さない
+            assert_eq!(client.transport().requests().len(), 2);
+            assert_eq!(client.transport().connections(), 1);
+        }
+    }
+
+    #[test]
+    fn test_https() {
+        let client = HttpClient::new(MockTransport::new());
+        client.transport().respond(
+            "a.test",
+            443,
+            "/",
+            MockResponse::new(
+                b"HTTP/1.1 200 OK\r\nSet-Cookie: id=1; Secure\r\nContent-Length: 1\r\n\r\ns",
+            ),
+        );
+        client.transport().respond("a.test", 443, "/plain", ok("p"));
+
+        let tests = [
+            // (url, expected)
+            ("https://a.test/", b"s"),
+            ("http://a.test:443/plain", b"p"),
+            ("https://a.test/", b"s"),
+        ];
+        for (url, expected) in tests {
+            let url = Url::new(url.to_string()).unwrap();
+            assert_eq!(client.get_url(&url).unwrap().body(), expected, "{}", url);
+        }
+        // 同じホストとポートでも TLS の接続と平文の接続は別に持つ
+        assert_eq!(client.transport().connections(), 2);
+        assert_eq!(client.transport().tls_connections(), 1);
+        // Secure なクッキーは平文では送らない
+        let requests = client.transport().requests();
+        assert!(!requests[1].windows(7).any(|w| w == b"Cookie:"));
+        assert!(requests[2].windows(12).any(|w| w == b"Cookie: id=1"));
+    }
+
+    #[test]
+    fn test_tls_error() {
+        let client = HttpClient::new(MockTransport::new());
+        client.transport().respond("a.test", 443, "/", ok("a"));
+        client.transport().fail_tls(
+            "a.test",
+            TlsError::Certificate(CertificateError::UnknownIssuer),
+        );
+        let url = Url::new("https://a.test/".to_string()).unwrap();
+        assert_eq!(
+            client.get_url(&url).err(),
+            Some(Error::Tls(TlsError::Certificate(
+                CertificateError::UnknownIssuer
+            )))
+        );
+    }
+
+    #[test]
+    fn test_dns_error() {
+        let client = HttpClient::new(MockTransport::new());
+        assert_eq!(
+            client.get("b.test".to_string(), 80, "/".to_string()).err(),
+            Some(Error::Network(NetworkError::Dns(
+                "b.test: no such host".to_string()
+            )))
+        );
+    }
+
+    #[test]
+    fn test_cache_needs_clock() {
+        let mut client = HttpClient::new(MockTransport::new());
+        client.transport().respond(
+            "a.test",
+            80,
+            "/",
+            MockResponse::new(
+                b"HTTP/1.1 200 OK\r\nCache-Control: max-age=1\r\nContent-Length: 1\r\n\r\na",
+            ),
+        );
+        // 時刻が分からないうちは、新鮮に見えてもキャッシュから返さない
+        for expected in [1, 2] {
+            client
+                .get("a.test".to_string(), 80, "/".to_string())
+                .unwrap();
+            assert_eq!(client.transport().requests().len(), expected);
+        }
+
+        client.set_clock(clock);
+        for _ in 0..2 {
+            client
+                .get("a.test".to_string(), 80, "/".to_string())
+                .unwrap();
+        }
+        assert_eq!(client.transport().requests().len(), 3);
+    }
+
+    #[test]
+    fn test_same_site_cookies() {
+        let client = HttpClient::new(MockTransport::new());
+        client.transport().respond(
+            "a.test",
+            80,
+            "/set",
+            MockResponse::new(
+                b"HTTP/1.1 200 OK\r\nSet-Cookie: s=1; SameSite=Strict\r\nSet-Cookie: l=1; SameSite=Lax\r\nContent-Length: 0\r\n\r\n",
+            ),
+        );
+        client.transport().respond("a.test", 80, "/check", ok("c"));
+        client.transport().respond(
+            "b.test",
+            80,
+            "/go",
+            MockResponse::new(
+                b"HTTP/1.1 302 Found\r\nLocation: http://a.test/check\r\nContent-Length: 0\r\n\r\n",
+            ),
+        );
+        let get = |url: &str| {
+            let url = Url::new(url.to_string()).unwrap();
+            HttpRequest::builder_for_url("GET", &url).build().unwrap()
+        };
+        client.request(get("http://a.test/set")).unwrap();
+
+        // リダイレクト元が別サイトなら、Strict なクッキーは送らない
+        client.request(get("http://b.test/go")).unwrap();
+        // ページからのサブリソースの要求なら Lax なクッキーも送らない
+        let b = Url::new("http://b.test/".to_string()).unwrap();
+        client
+            .request_from(get("http://a.test/check"), Some(b), false)
+            .unwrap();
+        client.request(get("http://a.test/check")).unwrap();
+
+        assert_eq!(
+            cookie_headers(&client)[2..],
+            vec![Some("l=1".to_string()), None, Some("s=1; l=1".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_cookies_need_clock() {
+        let mut client = HttpClient::new(MockTransport::new());
+        client.transport().respond(
+            "a.test",
+            80,
+            "/set",
+            MockResponse::new(
+                b"HTTP/1.1 200 OK\r\nSet-Cookie: p=1; Max-Age=60\r\nSet-Cookie: s=1\r\nContent-Length: 0\r\n\r\n",
+            ),
+        );
+        client.transport().respond(
+            "a.test",
+            80,
+            "/delete",
+            MockResponse::new(
+                b"HTTP/1.1 200 OK\r\nSet-Cookie: s=1; Expires=Sun, 06 Nov 1994 08:49:37 GMT\r\nContent-Length: 0\r\n\r\n",
+            ),
+        );
+        client.transport().respond("a.test", 80, "/check", ok("c"));
+        let mut saved = CookieJar::new();
+        let url = Url::new("http://a.test/".to_string()).unwrap();
+        saved.set_cookie(&url, "saved=1; Max-Age=60", 0);
+        client.set_cookies(saved);
+
+        // 期限のあるクッキーは保存も送信もせず、過去の Expires ではクッキーが消える
+        for path in ["/set", "/check", "/delete", "/check"] {
+            client
+                .get("a.test".to_string(), 80, path.to_string())
+                .unwrap();
+        }
+        assert_eq!(
+            cookie_headers(&client),
+            vec![None, Some("s=1".to_string()), Some("s=1".to_string()), None]
+        );
+        assert!(client.cookies().is_empty());
+    }
+
+    #[test]
+    fn test_pipeline() {
+        // リダイレクト、クッキー、キャッシュ、圧縮を一度に通す
+        let mut client = HttpClient::new(MockTransport::new());
+        client.set_clock(clock);
+        client.transport().respond(
+            "a.test",
+            80,
+            "/",
+            MockResponse::new(
+                b"HTTP/1.1 302 Found\r\nLocation: http://b.test:8000/page\r\nSet-Cookie: id=1\r\nContent-Length: 0\r\n\r\n",
+            )
+            .slow(),
+        );
+        client.transport().respond(
+            "b.test",
+            8000,
+            "/page",
+            MockResponse::new(
+                b"HTTP/1.1 200 OK\r\nCache-Control: max-age=60\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+            )
+            .read_size(3),
+        );
+
+        for _ in 0..2 {
+            let response = client
+                .get("a.test".to_string(), 80, "/".to_string())
+                .unwrap();
+            assert_eq!(response.body(), b"abc");
+            assert_eq!(response.redirect_chain().len(), 1);
+        }
+        // 2 回目の /page はキャッシュから返す
+        let requests = client.transport().requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].starts_with(b"GET /page HTTP/1.1\r\n"));
+        assert!(!requests[1].windows(7).any(|w| w == b"Cookie:"));
+        assert!(requests[2].windows(12).any(|w| w == b"Cookie: id=1"));
+    }
+}
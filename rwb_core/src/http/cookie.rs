@@ -0,0 +1,971 @@
+//! Cookies as RFC 6265 describes, with the Secure, SameSite and prefix rules
+//! from its revision that browsers follow.
+//!
+//! https://www.rfc-editor.org/rfc/rfc6265
+//! https://datatracker.ietf.org/doc/html/draft-ietf-httpbis-rfc6265bis
+
+use super::date::parse_cookie_date;
+use super::HttpRequest;
+use super::HttpResponse;
+use crate::error::Error;
+use crate::error::ParseErrorKind;
+use crate::percent_encoding::percent_decode_str;
+use crate::percent_encoding::percent_encode;
+use crate::percent_encoding::COMPONENT_SET;
+use crate::url::Host;
+use crate::url::Url;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+/// Where a cookie may be sent from, given by its SameSite attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    /// Only with requests from the cookie's own site.
+    Strict,
+    /// Also with top-level navigations from other sites. Cookies without
+    /// SameSite are treated as Lax, as browsers do.
+    Lax,
+    /// With every request. Such cookies must be Secure.
+    None,
+}
+
+/// How a request relates to the site of the page that caused it, which
+/// decides the SameSite cookies it carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestContext {
+    /// The page is on the same site as the request, or there is no page,
+    /// e.g. the user typed the URL.
+    SameSite,
+    /// A top-level GET navigation from a page on another site.
+    CrossSiteNavigation,
+    /// Any other request from a page on another site.
+    CrossSite,
+}
+
+impl RequestContext {
+    /// Works out the context of a request for `url` made by a page at
+    /// `initiator`, or by the user if `initiator` is `None`.
+    pub fn new(url: &Url, initiator: Option<&Url>, is_top_level_navigation: bool) -> Self {
+        match initiator {
+            Some(initiator) if !initiator.origin().is_same_site(&url.origin()) => {
+                if is_top_level_navigation {
+                    RequestContext::CrossSiteNavigation
+                } else {
+                    RequestContext::CrossSite
+                }
+            }
+            _ => RequestContext::SameSite,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    /// The host the cookie was set by, or the Domain attribute without its
+    /// leading dot.
+    domain: String,
+    /// True if the cookie is only sent to `domain` itself, not its
+    /// subdomains, because it was set without a Domain attribute.
+    host_only: bool,
+    path: String,
+    /// When the cookie expires, in seconds since the Unix epoch. `None` for
+    /// a session cookie.
+    expires: Option<u64>,
+    secure: bool,
+    http_only: bool,
+    same_site: SameSite,
+    /// When the cookie was first set, used to order cookies with paths of
+    /// the same length.
+    creation_time: u64,
+}
+
+impl Cookie {
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn value(&self) -> String {
+        self.value.clone()
+    }
+
+    pub fn domain(&self) -> String {
+        self.domain.clone()
+    }
+
+    pub fn host_only(&self) -> bool {
+        self.host_only
+    }
+
+    pub fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    pub fn expires(&self) -> Option<u64> {
+        self.expires
+    }
+
+    pub fn secure(&self) -> bool {
+        self.secure
+    }
+
+    /// Returns true if scripts must not see the cookie.
+    pub fn http_only(&self) -> bool {
+        self.http_only
+    }
+
+    pub fn same_site(&self) -> SameSite {
+        self.same_site
+    }
+
+    pub fn creation_time(&self) -> u64 {
+        self.creation_time
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Returns true if the cookie is sent with requests for `url`, leaving
+    /// SameSite aside.
+    fn matches(&self, url: &Url) -> bool {
+        let host = canonical_host(url);
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+        domain_ok && path_match(&url.path(), &self.path) && (!self.secure || is_secure(url))
+    }
+}
+
+/// The Set-Cookie attributes the storage model needs.
+#[derive(Debug, Default)]
+struct Attributes {
+    expires: Option<u64>,
+    max_age: Option<i64>,
+    domain: Option<String>,
+    path: Option<String>,
+    secure: bool,
+    http_only: bool,
+    same_site: Option<SameSite>,
+}
+
+/// Stores cookies from responses and gives them back for requests. Times
+/// are seconds since the Unix epoch.
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self {
+            cookies: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cookies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Cookie> {
+        self.cookies.iter()
+    }
+
+    /// Stores the cookie in a Set-Cookie header received from `url`.
+    /// Returns false if the cookie was ignored, e.g. because its Domain is
+    /// not `url`'s host or one of its parents.
+    ///
+    /// https://www.rfc-editor.org/rfc/rfc6265#section-5.3
+    pub fn set_cookie(&mut self, url: &Url, set_cookie: &str, now: u64) -> bool {
+        let (name, value, attributes) = match parse_set_cookie(set_cookie) {
+            Some(cookie) => cookie,
+            None => return false,
+        };
+        let host = canonical_host(url);
+
+        // Max-Age は Expires より優先される
+        let expires = match attributes.max_age {
+            Some(max_age) if max_age <= 0 => Some(0),
+            Some(max_age) => Some(now.saturating_add(max_age as u64)),
+            None => attributes.expires,
+        };
+
+        let (domain, host_only) = match attributes.domain {
+            // 公開サフィックス全体には設定させない
+            Some(domain) if is_public_suffix(&domain) => {
+                if domain != host {
+                    return false;
+                }
+                (host, true)
+            }
+            Some(domain) => {
+                if !domain_match(&host, &domain) {
+                    return false;
+                }
+                (domain, false)
+            }
+            None => (host, true),
+        };
+        // __Host- には既定のパスではなく、明示された Path=/ が要る
+        let root_path = attributes.path.as_deref() == Some("/");
+        let path = attributes.path.unwrap_or_else(|| default_path(url));
+
+        if attributes.secure && !is_secure(url) {
+            return false;
+        }
+        let same_site = attributes.same_site.unwrap_or(SameSite::Lax);
+        if same_site == SameSite::None && !attributes.secure {
+            return false;
+        }
+        // https://datatracker.ietf.org/doc/html/draft-ietf-httpbis-rfc6265bis#name-cookie-name-prefixes
+        if starts_with_ignore_case(&name, "__Secure-") && !attributes.secure {
+            return false;
+        }
+        if starts_with_ignore_case(&name, "__Host-")
+            && (!attributes.secure || !host_only || !root_path)
+        {
+            return false;
+        }
+        // 安全でない接続からは Secure なクッキーを上書きさせない
+        if !attributes.secure
+            && !is_secure(url)
+            && self.cookies.iter().any(|c| {
+                c.secure
+                    && c.name == name
+                    && (domain_match(&domain, &c.domain) || domain_match(&c.domain, &domain))
+                    && path_match(&path, &c.path)
+            })
+        {
+            return false;
+        }
+
+        let mut cookie = Cookie {
+            name,
+            value,
+            domain,
+            host_only,
+            path,
+            expires,
+            secure: attributes.secure,
+            http_only: attributes.http_only,
+            same_site,
+            creation_time: now,
+        };
+        if let Some(i) = self.cookies.iter().position(|c| {
+            c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path
+        }) {
+            cookie.creation_time = self.cookies.remove(i).creation_time;
+        }
+        // 過去の期限は削除の指示なので、古いものを消すだけ
+        if !cookie.is_expired(now) {
+            self.cookies.push(cookie);
+        }
+        true
+    }
+
+    /// Stores the cookies in the Set-Cookie headers of `response`, which came
+    /// from `url`.
+    pub fn store_response_cookies(&mut self, url: &Url, response: &HttpResponse, now: u64) {
+        for set_cookie in response.headers().get_all("Set-Cookie") {
+            self.set_cookie(url, &set_cookie, now);
+        }
+    }
+
+    /// Returns the cookies to send with a request for `url`, longest path
+    /// first and then oldest first.
+    ///
+    /// https://www.rfc-editor.org/rfc/rfc6265#section-5.4
+    pub fn cookies_for(&self, url: &Url, context: RequestContext, now: u64) -> Vec<Cookie> {
+        let mut cookies: Vec<Cookie> = self
+            .cookies
+            .iter()
+            .filter(|c| !c.is_expired(now) && c.matches(url))
+            .filter(|c| match context {
+                RequestContext::SameSite => true,
+                RequestContext::CrossSiteNavigation => c.same_site != SameSite::Strict,
+                RequestContext::CrossSite => c.same_site == SameSite::None,
+            })
+            .cloned()
+            .collect();
+        cookies.sort_by(|a, b| {
+            b.path
+                .len()
+                .cmp(&a.path.len())
+                .then(a.creation_time.cmp(&b.creation_time))
+        });
+        cookies
+    }
+
+    /// Returns the value of the Cookie header for a request for `url`, or
+    /// `None` if no cookie is sent.
+    pub fn cookie_header(&self, url: &Url, context: RequestContext, now: u64) -> Option<String> {
+        let cookies = self.cookies_for(url, context, now);
+        if cookies.is_empty() {
+            return None;
+        }
+        let pairs: Vec<String> = cookies
+            .iter()
+            .map(|c| format!("{}={}", c.name, c.value))
+            .collect();
+        Some(pairs.join("; "))
+    }
+
+    /// Returns `request` with its Cookie header replaced by the cookies for
+    /// its URL. Requests without a URL are returned unchanged.
+    pub fn add_cookie_header(
+        &self,
+        request: &HttpRequest,
+        context: RequestContext,
+        now: u64,
+    ) -> Result<HttpRequest, Error> {
+        let url = match request.url() {
+            Some(url) => url,
+            None => return Ok(request.clone()),
+        };
+        let builder = request.to_builder().remove_header("Cookie");
+        match self.cookie_header(&url, context, now) {
+            Some(cookies) => builder.header("Cookie", &cookies).build(),
+            None => builder.build(),
+        }
+    }
+
+    /// Drops the cookies that have expired.
+    pub fn remove_expired(&mut self, now: u64) {
+        self.cookies.retain(|c| !c.is_expired(now));
+    }
+
+    /// Drops the session cookies, as happens when the browser is closed.
+    pub fn remove_session_cookies(&mut self) {
+        self.cookies.retain(|c| c.expires.is_some());
+    }
+
+    /// Drops the cookies that have an expiry, leaving the session cookies.
+    pub fn remove_persistent_cookies(&mut self) {
+        self.cookies.retain(|c| c.expires.is_none());
+    }
+
+    /// Serializes the persistent cookies, one per line, so they survive a
+    /// restart. Session cookies are left out.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for c in self.cookies.iter().filter(|c| c.expires.is_some()) {
+            let line = format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                percent_encode(&c.name, COMPONENT_SET),
+                percent_encode(&c.value, COMPONENT_SET),
+                c.domain,
+                c.host_only,
+                percent_encode(&c.path, COMPONENT_SET),
+                c.expires.unwrap_or_default(),
+                c.secure,
+                c.http_only,
+                match c.same_site {
+                    SameSite::Strict => "Strict",
+                    SameSite::Lax => "Lax",
+                    SameSite::None => "None",
+                },
+                c.creation_time,
+            );
+            bytes.extend_from_slice(line.as_bytes());
+        }
+        bytes
+    }
+
+    /// Reads cookies written by `to_bytes`. Errors are reported at the
+    /// offset of the field that could not be read, or of the line if it has
+    /// the wrong number of fields.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let text = core::str::from_utf8(bytes)
+            .map_err(|e| Error::parse(ParseErrorKind::InvalidUtf8, e.valid_up_to()))?;
+        let mut cookies = Vec::new();
+        let mut start = 0;
+        for line in text.split_inclusive('\n') {
+            let line_start = start;
+            start += line.len();
+            let line = line.trim_end_matches('\n').trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut fields = Vec::new();
+            let mut offset = line_start;
+            for field in line.split('\t') {
+                fields.push((offset, field));
+                offset += field.len() + 1;
+            }
+            let [name, value, domain, host_only, path, expires, secure, http_only, same_site, creation_time] =
+                fields[..]
+            else {
+                return Err(invalid_line(line, line_start));
+            };
+            cookies.push(Cookie {
+                name: percent_decode_str(name.1),
+                value: percent_decode_str(value.1),
+                domain: domain.1.to_string(),
+                host_only: parse_field(line, host_only)?,
+                path: percent_decode_str(path.1),
+                expires: Some(parse_field(line, expires)?),
+                secure: parse_field(line, secure)?,
+                http_only: parse_field(line, http_only)?,
+                same_site: match same_site.1 {
+                    "Strict" => SameSite::Strict,
+                    "Lax" => SameSite::Lax,
+                    "None" => SameSite::None,
+                    _ => return Err(invalid_line(line, same_site.0)),
+                },
+                creation_time: parse_field(line, creation_time)?,
+            });
+        }
+        Ok(Self { cookies })
+    }
+}
+
+/// Parses a field, found at `offset`, of a line written by
+/// `CookieJar::to_bytes`.
+fn parse_field<T: FromStr>(line: &str, (offset, field): (usize, &str)) -> Result<T, Error> {
+    field.parse().map_err(|_| invalid_line(line, offset))
+}
+
+fn invalid_line(line: &str, offset: usize) -> Error {
+    Error::parse(ParseErrorKind::InvalidCookieLine(line.to_string()), offset)
+}
+
+/// Splits a Set-Cookie header into the name, value and attributes. Returns
+/// `None` if the cookie must be ignored.
+///
+/// https://www.rfc-editor.org/rfc/rfc6265#section-5.2
+fn parse_set_cookie(set_cookie: &str) -> Option<(String, String, Attributes)> {
+    let (pair, unparsed) = match set_cookie.split_once(';') {
+        Some((pair, unparsed)) => (pair, unparsed),
+        None => (set_cookie, ""),
+    };
+    let (name, value) = pair.split_once('=')?;
+    let (name, value) = (trim_wsp(name), trim_wsp(value));
+    // 制御文字を含むものは、タブを除いて受け付けない
+    let is_control = |c: char| c.is_ascii_control() && c != '\t';
+    if name.is_empty() || name.contains(is_control) || value.contains(is_control) {
+        return None;
+    }
+
+    let mut attributes = Attributes::default();
+    for attribute in unparsed.split(';') {
+        let (key, value) = match attribute.split_once('=') {
+            Some((key, value)) => (trim_wsp(key), trim_wsp(value)),
+            None => (trim_wsp(attribute), ""),
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "expires" => {
+                if let Some(expires) = parse_cookie_date(value) {
+                    attributes.expires = Some(expires);
+                }
+            }
+            "max-age" => {
+                let negative = value.starts_with('-');
+                let digits = value.strip_prefix('-').unwrap_or(value);
+                if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+                    // 桁あふれするほど大きな値は、その向きの端に丸める
+                    let max_age =
+                        value
+                            .parse()
+                            .unwrap_or(if negative { i64::MIN } else { i64::MAX });
+                    attributes.max_age = Some(max_age);
+                }
+            }
+            "domain" => {
+                if !value.is_empty() {
+                    let domain = value.strip_prefix('.').unwrap_or(value);
+                    attributes.domain = Some(domain.to_ascii_lowercase());
+                }
+            }
+            "path" => {
+                attributes.path = if value.starts_with('/') {
+                    Some(value.to_string())
+                } else {
+                    None
+                };
+            }
+            "secure" => attributes.secure = true,
+            "httponly" => attributes.http_only = true,
+            "samesite" => {
+                attributes.same_site = match value.to_ascii_lowercase().as_str() {
+                    "strict" => Some(SameSite::Strict),
+                    "lax" => Some(SameSite::Lax),
+                    "none" => Some(SameSite::None),
+                    _ => None,
+                };
+            }
+            _ => {}
+        }
+    }
+    Some((name.to_string(), value.to_string(), attributes))
+}
+
+/// The host of `url` as cookies compare it: lowercase, and an IPv6 address
+/// in brackets.
+fn canonical_host(url: &Url) -> String {
+    url.host().to_ascii_lowercase()
+}
+
+/// https://www.rfc-editor.org/rfc/rfc6265#section-5.1.3
+fn domain_match(host: &str, domain: &str) -> bool {
+    if host == domain {
+        return true;
+    }
+    // IP アドレスは完全に一致したときだけ
+    let is_ip = host.starts_with('[') || host.bytes().all(|b| b.is_ascii_digit() || b == b'.');
+    !is_ip
+        && host
+            .strip_suffix(domain)
+            .is_some_and(|rest| rest.ends_with('.'))
+}
+
+/// https://www.rfc-editor.org/rfc/rfc6265#section-5.1.4
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    let request_path = if request_path.is_empty() {
+        "/"
+    } else {
+        request_path
+    };
+    match request_path.strip_prefix(cookie_path) {
+        Some(rest) => rest.is_empty() || cookie_path.ends_with('/') || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// The path a cookie gets when Set-Cookie has no Path: the directory of the
+/// request path.
+///
+/// https://www.rfc-editor.org/rfc/rfc6265#section-5.1.4
+fn default_path(url: &Url) -> String {
+    let path = url.path();
+    if !path.starts_with('/') {
+        return "/".to_string();
+    }
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
+
+fn is_secure(url: &Url) -> bool {
+    url.scheme() == "https"
+}
+
+fn is_public_suffix(domain: &str) -> bool {
+    let host = Host::Domain(domain.to_string());
+    host.public_suffix() == Some(domain)
+}
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+fn trim_wsp(s: &str) -> &str {
+    s.trim_matches(|c| c == ' ' || c == '\t')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(s: &str) -> Url {
+        Url::new(s.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_set_cookie() {
+        let cases: &[(&str, Option<(&str, &str)>)] = &[
+            ("a=b", Some(("a", "b"))),
+            (" a = b c ; Path=/", Some(("a", "b c"))),
+            ("a=", Some(("a", ""))),
+            ("a==b=", Some(("a", "=b="))),
+            ("a=\"quoted\"", Some(("a", "\"quoted\""))),
+            ("a", None),
+            ("=b", None),
+            ("a=b\x01", None),
+            ("", None),
+        ];
+        for (set_cookie, expected) in cases {
+            let parsed = parse_set_cookie(set_cookie);
+            assert_eq!(
+                parsed.as_ref().map(|(n, v, _)| (n.as_str(), v.as_str())),
+                *expected,
+                "{:?}",
+                set_cookie
+            );
+        }
+    }
+
+    #[test]
+    fn test_attributes() {
+        let mut jar = CookieJar::new();
+        let from = url("https://www.example.com/docs/page.html");
+        assert!(jar.set_cookie(
+            &from,
+            "id=1; Domain=.Example.COM; Path=/; Secure; HttpOnly; SameSite=Strict; Max-Age=60",
+            1000
+        ));
+        assert!(jar.set_cookie(&from, "lang=ja", 1000));
+        assert!(jar.set_cookie(
+            &from,
+            "old=1; Expires=Sun, 06 Nov 2044 08:49:37 GMT; samesite=bogus",
+            1000
+        ));
+
+        let cookies: Vec<&Cookie> = jar.iter().collect();
+        assert_eq!(cookies[0].name(), "id");
+        assert_eq!(cookies[0].domain(), "example.com");
+        assert!(!cookies[0].host_only());
+        assert_eq!(cookies[0].path(), "/");
+        assert_eq!(cookies[0].expires(), Some(1060));
+        assert!(cookies[0].secure() && cookies[0].http_only());
+        assert_eq!(cookies[0].same_site(), SameSite::Strict);
+
+        // 属性がなければホスト限定、パスはディレクトリ、セッションクッキー
+        assert_eq!(cookies[1].domain(), "www.example.com");
+        assert!(cookies[1].host_only());
+        assert_eq!(cookies[1].path(), "/docs");
+        assert_eq!(cookies[1].expires(), None);
+        assert_eq!(cookies[1].same_site(), SameSite::Lax);
+
+        assert_eq!(cookies[2].expires(), Some(2362034977));
+        assert_eq!(cookies[2].same_site(), SameSite::Lax);
+    }
+
+    #[test]
+    fn test_max_age_takes_precedence() {
+        // (set-cookie, expires)
+        let cases: &[(&str, Option<u64>)] = &[
+            (
+                "a=b; Max-Age=10; Expires=Sun, 06 Nov 2044 08:49:37 GMT",
+                Some(110),
+            ),
+            (
+                "a=b; Expires=Sun, 06 Nov 2044 08:49:37 GMT; Max-Age=10",
+                Some(110),
+            ),
+            (
+                "a=b; Max-Age=ten; Expires=Sun, 06 Nov 2044 08:49:37 GMT",
+                Some(2362034977),
+            ),
+            ("a=b; Expires=garbage", None),
+            (
+                "a=b; Max-Age=99999999999999999999999",
+                Some(i64::MAX as u64 + 100),
+            ),
+        ];
+        for (set_cookie, expected) in cases {
+            let mut jar = CookieJar::new();
+            assert!(jar.set_cookie(&url("http://example.com/"), set_cookie, 100));
+            assert_eq!(
+                jar.iter().next().unwrap().expires(),
+                *expected,
+                "{}",
+                set_cookie
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejected() {
+        // (url, set-cookie)
+        let cases: &[(&str, &str)] = &[
+            ("http://example.com/", "a=b; Domain=other.com"),
+            ("http://example.com/", "a=b; Domain=www.example.com"),
+            ("http://example.com/", "a=b; Domain=com"),
+            ("http://www.example.co.jp/", "a=b; Domain=co.jp"),
+            ("http://192.168.0.1/", "a=b; Domain=0.1"),
+            ("http://example.com/", "a=b; Secure"),
+            ("https://example.com/", "a=b; SameSite=None"),
+            ("https://example.com/", "__Secure-a=b"),
+            (
+                "https://example.com/",
+                "__Host-a=b; Secure; Domain=example.com; Path=/",
+            ),
+            ("https://example.com/a/b", "__Host-a=b; Secure"),
+            ("https://example.com/", "__Host-a=b; Secure"),
+            ("http://example.com/", "no-equals"),
+        ];
+        for (from, set_cookie) in cases {
+            let mut jar = CookieJar::new();
+            assert!(
+                !jar.set_cookie(&url(from), set_cookie, 0),
+                "{} {}",
+                from,
+                set_cookie
+            );
+            assert!(jar.is_empty());
+        }
+
+        // 公開サフィックスそのもののホストなら、ホスト限定として受け付ける
+        let mut jar = CookieJar::new();
+        assert!(jar.set_cookie(&url("http://localhost/"), "a=b; Domain=localhost", 0));
+        assert!(jar.iter().next().unwrap().host_only());
+        let mut jar = CookieJar::new();
+        assert!(jar.set_cookie(
+            &url("https://example.com/"),
+            "__Host-a=b; Secure; Path=/",
+            0
+        ));
+        assert!(jar.set_cookie(&url("https://example.com/"), "__Secure-a=b; Secure", 0));
+        assert!(jar.set_cookie(
+            &url("https://example.com/"),
+            "c=d; Secure; SameSite=None",
+            0
+        ));
+    }
+
+    #[test]
+    fn test_cookies_for() {
+        let mut jar = CookieJar::new();
+        let from = url("https://www.example.com/");
+        jar.set_cookie(&from, "host=1", 1);
+        jar.set_cookie(&from, "domain=1; Domain=example.com", 2);
+        jar.set_cookie(&from, "docs=1; Path=/docs", 3);
+        jar.set_cookie(&from, "secure=1; Secure", 4);
+        jar.set_cookie(&from, "short=1; Max-Age=10", 5);
+
+        // (url, now, cookie header)
+        let cases: &[(&str, u64, Option<&str>)] = &[
+            (
+                "https://www.example.com/docs/a",
+                10,
+                Some("docs=1; host=1; domain=1; secure=1; short=1"),
+            ),
+            (
+                "https://www.example.com/",
+                10,
+                Some("host=1; domain=1; secure=1; short=1"),
+            ),
+            (
+                "http://www.example.com/",
+                10,
+                Some("host=1; domain=1; short=1"),
+            ),
+            (
+                "https://www.example.com/",
+                15,
+                Some("host=1; domain=1; secure=1"),
+            ),
+            (
+                "https://www.example.com/documents",
+                15,
+                Some("host=1; domain=1; secure=1"),
+            ),
+            (
+                "https://www.example.com/docs",
+                15,
+                Some("docs=1; host=1; domain=1; secure=1"),
+            ),
+            ("https://sub.www.example.com/", 15, Some("domain=1")),
+            ("https://example.com/", 15, Some("domain=1")),
+            ("https://notexample.com/", 15, None),
+            ("https://example.org/", 15, None),
+        ];
+        for (to, now, expected) in cases {
+            assert_eq!(
+                jar.cookie_header(&url(to), RequestContext::SameSite, *now)
+                    .as_deref(),
+                *expected,
+                "{}",
+                to
+            );
+        }
+    }
+
+    #[test]
+    fn test_same_site() {
+        let mut jar = CookieJar::new();
+        let site = url("https://example.com/");
+        jar.set_cookie(&site, "strict=1; SameSite=Strict", 0);
+        jar.set_cookie(&site, "lax=1; SameSite=Lax", 0);
+        jar.set_cookie(&site, "default=1", 0);
+        jar.set_cookie(&site, "none=1; SameSite=None; Secure", 0);
+
+        let other = url("https://other.test/");
+        let sub = url("https://sub.example.com/");
+        // (initiator, top-level navigation, cookie header)
+        let cases: &[(Option<&Url>, bool, &str)] = &[
+            (None, false, "strict=1; lax=1; default=1; none=1"),
+            (Some(&sub), false, "strict=1; lax=1; default=1; none=1"),
+            (Some(&other), true, "lax=1; default=1; none=1"),
+            (Some(&other), false, "none=1"),
+        ];
+        for (initiator, navigation, expected) in cases {
+            let context = RequestContext::new(&site, *initiator, *navigation);
+            assert_eq!(
+                jar.cookie_header(&site, context, 0).as_deref(),
+                Some(*expected),
+                "{:?}",
+                context
+            );
+        }
+    }
+
+    #[test]
+    fn test_replace_and_delete() {
+        let mut jar = CookieJar::new();
+        let from = url("http://example.com/");
+        jar.set_cookie(&from, "a=1", 10);
+        jar.set_cookie(&from, "b=1", 20);
+        jar.set_cookie(&from, "a=2", 30);
+        assert_eq!(jar.len(), 2);
+        // 置き換えても作成時刻は最初のまま
+        assert_eq!(
+            jar.cookie_header(&from, RequestContext::SameSite, 30)
+                .as_deref(),
+            Some("a=2; b=1")
+        );
+
+        jar.set_cookie(&from, "a=; Max-Age=0", 40);
+        jar.set_cookie(&from, "b=; Expires=Thu, 01 Jan 1970 00:00:00 GMT", 40);
+        assert!(jar.is_empty());
+    }
+
+    #[test]
+    fn test_secure_cookie_not_overwritten_from_http() {
+        let mut jar = CookieJar::new();
+        jar.set_cookie(&url("https://example.com/"), "a=secure; Secure", 0);
+        assert!(!jar.set_cookie(&url("http://example.com/"), "a=plain", 0));
+        assert!(jar.set_cookie(&url("http://example.com/"), "b=plain", 0));
+        assert_eq!(
+            jar.cookie_header(&url("https://example.com/"), RequestContext::SameSite, 0)
+                .as_deref(),
+            Some("a=secure; b=plain")
+        );
+    }
+
+    #[test]
+    fn test_path_match() {
+        // (request path, cookie path, expected)
+        let cases: &[(&str, &str, bool)] = &[
+            ("/", "/", true),
+            ("/a", "/", true),
+            ("/a", "/a", true),
+            ("/a/b", "/a", true),
+            ("/a/b", "/a/", true),
+            ("/ab", "/a", false),
+            ("/", "/a", false),
+            ("", "/", true),
+        ];
+        for (request_path, cookie_path, expected) in cases {
+            assert_eq!(
+                path_match(request_path, cookie_path),
+                *expected,
+                "{} {}",
+                request_path,
+                cookie_path
+            );
+        }
+    }
+
+    #[test]
+    fn test_response_and_request() {
+        let mut jar = CookieJar::new();
+        let page = url("http://example.com/login");
+        let response = HttpResponse::new(
+            b"HTTP/1.1 200 OK\r\nSet-Cookie: session=abc; HttpOnly\r\nSet-Cookie: theme=dark\r\nContent-Length: 0\r\n\r\n"
+                .to_vec(),
+        )
+        .unwrap();
+        jar.store_response_cookies(&page, &response, 0);
+        assert_eq!(jar.len(), 2);
+
+        let request = HttpRequest::builder_for_url("GET", &url("http://example.com/home"))
+            .header("Cookie", "stale=1")
+            .build()
+            .unwrap();
+        let request = jar
+            .add_cookie_header(&request, RequestContext::SameSite, 0)
+            .unwrap();
+        assert_eq!(
+            request.headers().get_all("Cookie"),
+            ["session=abc; theme=dark"]
+        );
+
+        let request = HttpRequest::builder_for_url("GET", &url("http://other.test/"))
+            .header("Cookie", "stale=1")
+            .build()
+            .unwrap();
+        let request = jar
+            .add_cookie_header(&request, RequestContext::SameSite, 0)
+            .unwrap();
+        assert!(!request.headers().contains("Cookie"));
+    }
+
+    #[test]
+    fn test_serialization() {
+        let mut jar = CookieJar::new();
+        let from = url("https://example.com/a/");
+        jar.set_cookie(&from, "session=1", 0);
+        jar.set_cookie(
+            &from,
+            "odd name=tab\tand%25;percent; Max-Age=100; Secure; HttpOnly; SameSite=Strict",
+            5,
+        );
+        jar.set_cookie(&from, "wide=1; Domain=example.com; Path=/; Max-Age=50", 7);
+
+        let bytes = jar.to_bytes();
+        let read = CookieJar::from_bytes(&bytes).unwrap();
+        // セッションクッキーは保存しない
+        assert_eq!(read.len(), 2);
+        let persistent: Vec<Cookie> = jar
+            .iter()
+            .filter(|c| c.expires().is_some())
+            .cloned()
+            .collect();
+        assert_eq!(read.iter().cloned().collect::<Vec<_>>(), persistent);
+        assert_eq!(read.to_bytes(), bytes);
+
+        let line = "a\tb\texample.com\ttrue\t/\t1\tfalse\tfalse\tLax\t0";
+        let error = |line: &str, offset| {
+            Some(Error::parse(
+                ParseErrorKind::InvalidCookieLine(line.to_string()),
+                offset,
+            ))
+        };
+        // (bytes, expected)
+        let cases: &[(String, Option<Error>)] = &[
+            ("a\tb\n".to_string(), error("a\tb", 0)),
+            // 2 行目の誤りはその行の位置で報告する
+            (format!("{}\na\tb\n", line), error("a\tb", line.len() + 1)),
+            (
+                line.replace("true", "maybe"),
+                error(&line.replace("true", "maybe"), 16),
+            ),
+            (
+                line.replace("\t1\t", "\tx\t"),
+                error(&line.replace("\t1\t", "\tx\t"), 23),
+            ),
+            (
+                line.replace("Lax", "Sometimes"),
+                error(&line.replace("Lax", "Sometimes"), 37),
+            ),
+            (format!("{}x", line), error(&format!("{}x", line), 41)),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(
+                CookieJar::from_bytes(bytes.as_bytes()).err(),
+                *expected,
+                "{:?}",
+                bytes
+            );
+        }
+        assert_eq!(
+            CookieJar::from_bytes(b"\xff\n").err(),
+            Some(Error::parse(ParseErrorKind::InvalidUtf8, 0))
+        );
+        assert!(CookieJar::from_bytes(b"").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+//! HTTP dates, as used by Date, Expires and Last-Modified.
+//!
+//! https://www.rfc-editor.org/rfc/rfc9110#section-5.6.7
+
+use alloc::vec::Vec;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Parses an HTTP date into seconds since the Unix epoch. All three formats
+/// recipients must accept are understood:
+///
+/// - `Sun, 06 Nov 1994 08:49:37 GMT` (IMF-fixdate)
+/// - `Sunday, 06-Nov-94 08:49:37 GMT` (RFC 850)
+/// - `Sun Nov  6 08:49:37 1994` (asctime)
+///
+/// Returns `None` for anything else, or for a date before 1970.
+pub fn parse_http_date(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (day, month, year, time) = match value.split_once(", ") {
+        Some((_, rest)) => {
+            let fields: Vec<&str> = rest.split([' ', '-']).collect();
+            match fields[..] {
+                [day, month, year, time, "GMT"] => (day, month, year, time),
+                _ => return None,
+            }
+        }
+        None => {
+            let fields: Vec<&str> = value.split_whitespace().collect();
+            match fields[..] {
+                [_, month, day, time, year] => (day, month, year, time),
+                _ => return None,
+            }
+        }
+    };
+
+    let day = parse_number(day, 1, 2)?;
+    let month = MONTHS.iter().position(|m| *m == month)? as u64 + 1;
+    let year = match year.len() {
+        // RFC 850 の 2 桁の年は、50 年以上先にならないように解釈する
+        2 => match parse_number(year, 2, 2)? {
+            year @ 70.. => 1900 + year,
+            year => 2000 + year,
+        },
+        _ => parse_number(year, 4, 4)?,
+    };
+    let mut time = time.split(':');
+    let hour = parse_number(time.next()?, 2, 2)?;
+    let minute = parse_number(time.next()?, 2, 2)?;
+    let second = parse_number(time.next()?, 2, 2)?;
+    if time.next().is_some()
+        || !(1..=31).contains(&day)
+        || year < 1970
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let days = days_from_civil(year, month, day);
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Parses the Expires attribute of a Set-Cookie header into seconds since
+/// the Unix epoch. This is far more lenient than `parse_http_date`: the
+/// time, day, month and year are picked out of the tokens in any order.
+///
+/// https://www.rfc-editor.org/rfc/rfc6265#section-5.1.1
+pub(super) fn parse_cookie_date(value: &str) -> Option<u64> {
+    let is_delimiter = |c: char| matches!(c, '\t' | ' '..='/' | ';'..='@' | '['..='`' | '{'..='~');
+    let mut time = None;
+    let mut day = None;
+    let mut month = None;
+    let mut year = None;
+    for token in value.split(is_delimiter).filter(|t| !t.is_empty()) {
+        if time.is_none() {
+            if let Some(t) = parse_cookie_time(token) {
+                time = Some(t);
+                continue;
+            }
+        }
+        if day.is_none() {
+            if let Some(d) = leading_digits(token, 1, 2) {
+                day = Some(d);
+                continue;
+            }
+        }
+        if month.is_none() {
+            if let Some(m) = MONTHS.iter().position(|m| {
+                token
+                    .get(..3)
+                    .is_some_and(|prefix| prefix.eq_ignore_ascii_case(m))
+            }) {
+                month = Some(m as u64 + 1);
+                continue;
+            }
+        }
+        if year.is_none() {
+            if let Some(y) = leading_digits(token, 2, 4) {
+                year = Some(y);
+                continue;
+            }
+        }
+    }
+
+    let (hour, minute, second) = time?;
+    let (day, month, year) = (day?, month?, year?);
+    let year = match year {
+        70..=99 => year + 1900,
+        0..=69 => year + 2000,
+        _ => year,
+    };
+    if !(1..=31).contains(&day) || year < 1970 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    Some(days * 86400 + hour * 3600 + minute * 60 + second)
+}
+
+/// Parses `hh:mm:ss`, where each part is one or two digits and anything may
+/// follow the seconds.
+fn parse_cookie_time(token: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = token.splitn(3, ':');
+    let hour = parse_number(parts.next()?, 1, 2)?;
+    let minute = parse_number(parts.next()?, 1, 2)?;
+    let second = leading_digits(parts.next()?, 1, 2)?;
+    Some((hour, minute, second))
+}
+
+/// Parses `min..=max` ASCII digits at the start of `token`, which may be
+/// followed by anything but another digit.
+fn leading_digits(token: &str, min: usize, max: usize) -> Option<u64> {
+    let len = token.bytes().take_while(|b| b.is_ascii_digit()).count();
+    parse_number(&token[..len], min, max)
+}
+
+/// Parses `min..=max` ASCII digits.
+fn parse_number(s: &str, min: usize, max: usize) -> Option<u64> {
+    if s.len() < min || s.len() > max || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Returns the number of days from 1970-01-01 to the given date in the
+/// proleptic Gregorian calendar. `year` must be 1970 or later.
+///
+/// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    // 3 月始まりにすると閏日が年の最後に来る
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_http_date() {
+        // (value, expected)
+        let cases: &[(&str, Option<u64>)] = &[
+            ("Sun, 06 Nov 1994 08:49:37 GMT", Some(784111777)),
+            ("Sunday, 06-Nov-94 08:49:37 GMT", Some(784111777)),
+            ("Sun Nov  6 08:49:37 1994", Some(784111777)),
+            ("Thu, 01 Jan 1970 00:00:00 GMT", Some(0)),
+            ("Tue, 29 Feb 2000 12:00:00 GMT", Some(951825600)),
+            ("Sat, 01 Jan 2050 00:00:00 GMT", Some(2524608000)),
+            ("Thursday, 01-Jan-37 00:00:00 GMT", Some(2114380800)),
+            (" Sun, 06 Nov 1994 08:49:37 GMT ", Some(784111777)),
+            ("", None),
+            ("0", None),
+            ("-1", None),
+            ("Sun, 06 Nov 1994 08:49:37", None),
+            ("Sun, 06 Nov 1994 08:49:37 PST", None),
+            ("Sun, 06 Foo 1994 08:49:37 GMT", None),
+            ("Sun, 32 Nov 1994 08:49:37 GMT", None),
+            ("Sun, 06 Nov 1994 24:00:00 GMT", None),
+            ("Sun, 06 Nov 1994 08:49 GMT", None),
+            ("Sun, 06 Nov 1994 08:49:37:00 GMT", None),
+            ("Wed, 31 Dec 1969 23:59:59 GMT", None),
+            ("Sun, +6 Nov 1994 08:49:37 GMT", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_http_date(value), *expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn test_parse_cookie_date() {
+        // (value, expected)
+        let cases: &[(&str, Option<u64>)] = &[
+            ("Sun, 06 Nov 1994 08:49:37 GMT", Some(784111777)),
+            ("Sunday, 06-Nov-94 08:49:37 GMT", Some(784111777)),
+            ("Sun Nov  6 08:49:37 1994", Some(784111777)),
+            // 順番も区切りも自由
+            ("1994 nov 6 8:49:37", Some(784111777)),
+            ("06-November-1994 08:49:37.000Z", Some(784111777)),
+            ("Thu, 01 Jan 2037 00:00:00 UTC", Some(2114380800)),
+            ("Thu, 01-Jan-70 00:00:00 GMT", Some(0)),
+            ("Sun, 06 Nov 1994", None),
+            ("Sun, 06 Nov 08:49:37 GMT", None),
+            ("Sun, 06 1994 08:49:37 GMT", None),
+            ("Sun, 32 Nov 1994 08:49:37 GMT", None),
+            ("Sun, 06 Nov 1994 08:49:60 GMT", None),
+            ("Sun, 06 Nov 1969 08:49:37 GMT", None),
+            ("", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_cookie_date(value), *expected, "{:?}", value);
+        }
+    }
+}
@@ -0,0 +1,466 @@
+use crate::error::Error;
+use crate::error::ParseErrorKind;
+use crate::url::Url;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    name: String,
+    value: String,
+}
+
+impl Header {
+    pub fn new(name: String, value: String) -> Self {
+        Self { name, value }
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn value(&self) -> String {
+        self.value.clone()
+    }
+}
+
+/// Header fields in the order they were added. Names are compared
+/// case-insensitively but keep the case they were given in, and a name may
+/// appear more than once, as `Set-Cookie` often does.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    headers: Vec<Header>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Self {
+            headers: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, Header> {
+        self.headers.iter()
+    }
+
+    /// Returns the value of the first field named `name`.
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.clone())
+    }
+
+    /// Returns the values of all fields named `name`, in order.
+    pub fn get_all(&self, name: &str) -> Vec<String> {
+        self.iter()
+            .filter(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value.clone())
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.iter().any(|h| h.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Adds a field after the existing ones, even if one has the same name.
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.headers
+            .push(Header::new(name.to_string(), value.to_string()));
+    }
+
+    /// Sets the field named `name` to `value`. The first existing field keeps
+    /// its position and any others with the same name are removed.
+    pub fn insert(&mut self, name: &str, value: &str) {
+        match self
+            .headers
+            .iter()
+            .position(|h| h.name.eq_ignore_ascii_case(name))
+        {
+            Some(i) => {
+                self.headers[i] = Header::new(name.to_string(), value.to_string());
+                let mut index = 0;
+                self.headers.retain(|h| {
+                    index += 1;
+                    index - 1 <= i || !h.name.eq_ignore_ascii_case(name)
+                });
+            }
+            None => self.append(name, value),
+        }
+    }
+
+    /// Removes all fields named `name`.
+    pub fn remove(&mut self, name: &str) {
+        self.headers.retain(|h| !h.name.eq_ignore_ascii_case(name));
+    }
+
+    /// Returns the elements of the comma-separated lists in all fields named
+    /// `name`, e.g. `["gzip", "chunked"]` for `Transfer-Encoding`.
+    pub fn get_list(&self, name: &str) -> Vec<String> {
+        self.iter()
+            .filter(|h| h.name.eq_ignore_ascii_case(name))
+            .flat_map(|h| h.value.split(','))
+            .map(|v| v.trim_matches([' ', '\t']))
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    /// Returns the Content-Length. Repeated values such as `42, 42` are
+    /// allowed as long as they all agree. An invalid value is reported at
+    /// offset 0, since a `HeaderMap` does not know where its fields were.
+    ///
+    /// https://www.rfc-editor.org/rfc/rfc9110#section-8.6
+    pub fn content_length(&self) -> Result<Option<usize>, Error> {
+        let invalid = |value| Error::parse(ParseErrorKind::InvalidContentLength(value), 0);
+        let mut length = None;
+        for value in self.get_list("Content-Length") {
+            if !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid(value));
+            }
+            let parsed = match value.parse::<usize>() {
+                Ok(parsed) => parsed,
+                Err(_) => return Err(invalid(value)),
+            };
+            if length.is_some_and(|length| length != parsed) {
+                return Err(invalid(value));
+            }
+            length = Some(parsed);
+        }
+        Ok(length)
+    }
+
+    /// Returns the last Content-Type that parses as a MIME type.
+    pub fn content_type(&self) -> Option<ContentType> {
+        self.get_all("Content-Type")
+            .iter()
+            .rev()
+            .find_map(|value| ContentType::parse(value))
+    }
+
+    /// Returns the Location resolved against `base`, the URL of the request.
+    pub fn location(&self, base: &Url) -> Option<Result<Url, Error>> {
+        self.get("Location").map(|location| base.join(&location))
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = &'a Header;
+    type IntoIter = core::slice::Iter<'a, Header>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl From<Vec<Header>> for HeaderMap {
+    fn from(headers: Vec<Header>) -> Self {
+        Self { headers }
+    }
+}
+
+/// A parsed Content-Type such as `text/html; charset=utf-8`.
+///
+/// https://mimesniff.spec.whatwg.org/#parsing-a-mime-type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentType {
+    mime_type: String,
+    parameters: Vec<(String, String)>,
+}
+
+impl ContentType {
+    /// Parses a MIME type. The type, subtype and parameter names are
+    /// lowercased; parameter values keep their case.
+    pub fn parse(input: &str) -> Option<Self> {
+        let is_token = |s: &str| !s.is_empty() && s.bytes().all(super::is_token_char);
+        let input = input.trim_matches([' ', '\t', '\r', '\n']);
+
+        let (mime_type, mut rest) = input.split_once(';').unwrap_or((input, ""));
+        let mime_type = mime_type.trim_end_matches([' ', '\t']);
+        let (type_, subtype) = mime_type.split_once('/')?;
+        if !is_token(type_) || !is_token(subtype) {
+            return None;
+        }
+
+        let mut parameters: Vec<(String, String)> = Vec::new();
+        while !rest.is_empty() {
+            rest = rest.trim_start_matches([' ', '\t']);
+            let name_end = rest.find([';', '=']).unwrap_or(rest.len());
+            let name = rest[..name_end].to_ascii_lowercase();
+            rest = &rest[name_end..];
+            if !rest.starts_with('=') {
+                rest = rest.strip_prefix(';').unwrap_or(rest);
+                continue;
+            }
+            rest = &rest[1..];
+
+            let value = if let Some(quoted) = rest.strip_prefix('"') {
+                // 引用符で囲まれた値。'\' は次の文字をそのまま値に含める
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        '\\' => match chars.next() {
+                            Some((_, c)) => value.push(c),
+                            None => value.push('\\'),
+                        },
+                        _ => value.push(c),
+                    }
+                }
+                let after = &quoted[end..];
+                rest = after.find(';').map_or("", |i| &after[i + 1..]);
+                value
+            } else {
+                let value_end = rest.find(';').unwrap_or(rest.len());
+                let value = rest[..value_end].trim_end_matches([' ', '\t']).to_string();
+                rest = rest.get(value_end + 1..).unwrap_or("");
+                if value.is_empty() {
+                    continue;
+                }
+                value
+            };
+
+            // 同じ名前の引数は最初のものだけを使う
+            if is_token(&name) && !parameters.iter().any(|(n, _)| *n == name) {
+                parameters.push((name, value));
+            }
+        }
+
+        Some(Self {
+            mime_type: mime_type.to_ascii_lowercase(),
+            parameters,
+        })
+    }
+
+    /// Returns the essence, e.g. `text/html`.
+    pub fn mime_type(&self) -> String {
+        self.mime_type.clone()
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<String> {
+        self.parameters
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.clone())
+    }
+
+    /// Returns the `charset` parameter, lowercased.
+    pub fn charset(&self) -> Option<String> {
+        self.parameter("charset").map(|c| c.to_ascii_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Fields<'a> = &'a [(&'a str, &'a str)];
+
+    fn header_map(headers: Fields) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(name, value);
+        }
+        map
+    }
+
+    fn pairs(map: &HeaderMap) -> Vec<(String, String)> {
+        map.iter().map(|h| (h.name(), h.value())).collect()
+    }
+
+    #[test]
+    fn test_get() {
+        let map = header_map(&[
+            ("Content-Length", "42"),
+            ("Set-Cookie", "a=1"),
+            ("set-cookie", "b=2"),
+            ("SET-COOKIE", "c=3"),
+        ]);
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get("content-length"), Some("42".to_string()));
+        assert_eq!(map.get("CONTENT-LENGTH"), Some("42".to_string()));
+        assert_eq!(map.get("Set-Cookie"), Some("a=1".to_string()));
+        assert_eq!(map.get("Location"), None);
+        assert_eq!(map.get_all("Set-Cookie"), ["a=1", "b=2", "c=3"]);
+        assert_eq!(map.get_all("Location"), Vec::<String>::new());
+        assert!(map.contains("set-COOKIE"));
+        assert!(!map.contains("Cookie"));
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut map = header_map(&[("B", "1"), ("a", "2"), ("C", "3"), ("A", "4")]);
+        assert_eq!(
+            pairs(&map),
+            [
+                ("B".to_string(), "1".to_string()),
+                ("a".to_string(), "2".to_string()),
+                ("C".to_string(), "3".to_string()),
+                ("A".to_string(), "4".to_string()),
+            ]
+        );
+
+        map.insert("A", "5");
+        map.insert("D", "6");
+        assert_eq!(
+            pairs(&map),
+            [
+                ("B".to_string(), "1".to_string()),
+                ("A".to_string(), "5".to_string()),
+                ("C".to_string(), "3".to_string()),
+                ("D".to_string(), "6".to_string()),
+            ]
+        );
+
+        map.remove("b");
+        map.remove("missing");
+        assert_eq!(
+            (&map).into_iter().map(|h| h.name()).collect::<Vec<_>>(),
+            ["A", "C", "D"]
+        );
+    }
+
+    #[test]
+    fn test_get_list() {
+        let map = header_map(&[
+            ("Transfer-Encoding", "gzip , "),
+            ("transfer-encoding", ",\tchunked"),
+        ]);
+        assert_eq!(map.get_list("Transfer-Encoding"), ["gzip", "chunked"]);
+    }
+
+    #[test]
+    fn test_content_length() {
+        let cases: &[(Fields, Result<Option<usize>, Error>)] = &[
+            (&[], Ok(None)),
+            (&[("Content-Length", "0")], Ok(Some(0))),
+            (&[("content-length", " 42 ")], Ok(Some(42))),
+            (&[("Content-Length", "42, 42")], Ok(Some(42))),
+            (
+                &[("Content-Length", "7"), ("Content-Length", "7")],
+                Ok(Some(7)),
+            ),
+            (
+                &[("Content-Length", "7"), ("Content-Length", "8")],
+                Err(Error::parse(
+                    ParseErrorKind::InvalidContentLength("8".to_string()),
+                    0,
+                )),
+            ),
+            (
+                &[("Content-Length", "0x10")],
+                Err(Error::parse(
+                    ParseErrorKind::InvalidContentLength("0x10".to_string()),
+                    0,
+                )),
+            ),
+            (
+                &[("Content-Length", "1 2")],
+                Err(Error::parse(
+                    ParseErrorKind::InvalidContentLength("1 2".to_string()),
+                    0,
+                )),
+            ),
+        ];
+        for (headers, expected) in cases {
+            assert_eq!(
+                header_map(headers).content_length(),
+                *expected,
+                "{:?}",
+                headers
+            );
+        }
+    }
+
+    #[test]
+    fn test_content_type() {
+        // (input, mime type, parameters)
+        let cases: &[(&str, Option<(&str, Fields)>)] = &[
+            ("text/html", Some(("text/html", &[]))),
+            (" Text/HTML ", Some(("text/html", &[]))),
+            (
+                "text/html; charset=UTF-8",
+                Some(("text/html", &[("charset", "UTF-8")])),
+            ),
+            (
+                "text/html;CHARSET=\"Shift_JIS\";charset=utf-8",
+                Some(("text/html", &[("charset", "Shift_JIS")])),
+            ),
+            (
+                "multipart/form-data; boundary=\"a;b\\\"c\" ; x=1",
+                Some(("multipart/form-data", &[("boundary", "a;b\"c"), ("x", "1")])),
+            ),
+            (
+                "text/plain; empty=; novalue; =x; a=b",
+                Some(("text/plain", &[("a", "b")])),
+            ),
+            (
+                "text/plain; a=\"unterminated",
+                Some(("text/plain", &[("a", "unterminated")])),
+            ),
+            ("text", None),
+            ("text/", None),
+            ("/html", None),
+            ("te xt/html", None),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            let content_type = ContentType::parse(input);
+            match expected {
+                None => assert_eq!(content_type, None, "parsing {:?}", input),
+                Some((mime_type, parameters)) => {
+                    let content_type =
+                        content_type.unwrap_or_else(|| panic!("failed to parse {:?}", input));
+                    assert_eq!(content_type.mime_type(), *mime_type, "parsing {:?}", input);
+                    let expected: Vec<(String, String)> = parameters
+                        .iter()
+                        .map(|(n, v)| (n.to_string(), v.to_string()))
+                        .collect();
+                    assert_eq!(content_type.parameters, expected, "parsing {:?}", input);
+                }
+            }
+        }
+
+        let map = header_map(&[
+            ("Content-Type", "text/html; charset=\"ISO-8859-1\""),
+            ("Content-Type", "invalid"),
+        ]);
+        let content_type = map.content_type().expect("no content type");
+        assert_eq!(content_type.mime_type(), "text/html");
+        assert_eq!(content_type.charset(), Some("iso-8859-1".to_string()));
+    }
+
+    #[test]
+    fn test_location() {
+        let base = Url::new("http://example.com/a/b?q".to_string()).unwrap();
+        let cases: &[(&str, &str)] = &[
+            ("http://other.test/x", "http://other.test/x"),
+            ("/root", "http://example.com/root"),
+            ("c", "http://example.com/a/c"),
+            ("?r", "http://example.com/a/b?r"),
+            ("//cdn.test/d", "http://cdn.test/d"),
+        ];
+        for (location, expected) in cases {
+            let map = header_map(&[("location", location)]);
+            let url = map.location(&base).expect("no location").unwrap();
+            assert_eq!(url.to_string(), *expected, "resolving {:?}", location);
+        }
+
+        assert!(HeaderMap::new().location(&base).is_none());
+        let map = header_map(&[("Location", "http://[::1/")]);
+        assert!(map.location(&base).unwrap().is_err());
+    }
+}
@@ -0,0 +1,622 @@
+//! A push-based response parser that works on bytes as they arrive from the
+//! network, so that callers can act on the head before the body is complete.
+
+use super::body_length;
+use super::parse_header_line;
+use super::parse_status_line;
+use super::split_head;
+use super::BodyLength;
+use super::ChunkedDecoder;
+use super::HeaderMap;
+use super::StatusCode;
+use crate::error::Error;
+use crate::error::ParseErrorKind;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// The default limit on the size of the status line and headers, 64 KiB.
+pub const DEFAULT_MAX_HEADER_SIZE: usize = 64 * 1024;
+/// The default limit on the size of the decoded body, 64 MiB.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 64 * 1024 * 1024;
+
+/// The status line and headers of a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseHead {
+    version: String,
+    status_code: StatusCode,
+    reason: String,
+    headers: HeaderMap,
+}
+
+impl ResponseHead {
+    pub fn version(&self) -> String {
+        self.version.clone()
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        self.status_code
+    }
+
+    pub fn reason(&self) -> String {
+        self.reason.clone()
+    }
+
+    pub fn headers(&self) -> HeaderMap {
+        self.headers.clone()
+    }
+}
+
+/// What `ResponseParser` found in the bytes pushed to it. A response yields
+/// one `Head`, any number of `Body` pieces, trailers if the body was chunked
+/// and had any, and finally `Done`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResponseEvent {
+    Head(ResponseHead),
+    Body(Vec<u8>),
+    Trailers(HeaderMap),
+    Done,
+}
+
+#[derive(Debug, Clone)]
+enum State {
+    Head,
+    Fixed(usize),
+    Chunked(ChunkedDecoder),
+    UntilClose,
+    Done,
+}
+
+/// Parses a response incrementally. Feed it with `push` as data is read and
+/// call `finish` when the connection is closed.
+///
+/// Parse errors are reported at offsets counted from the first byte pushed.
+#[derive(Debug, Clone)]
+pub struct ResponseParser {
+    state: State,
+    buffer: Vec<u8>,
+    /// How far `buffer` has been searched for the end of the head.
+    scanned: usize,
+    /// The offset of the start of `buffer` while reading the head, and of the
+    /// start of the body after that.
+    position: usize,
+    /// The number of bytes pushed so far.
+    received: usize,
+    body_size: usize,
+    max_header_size: usize,
+    max_body_size: usize,
+    /// Whether the response answers a HEAD request and so has no body.
+    head_request: bool,
+}
+
+impl ResponseParser {
+    pub fn new() -> Self {
+        Self {
+            state: State::Head,
+            buffer: Vec::new(),
+            scanned: 0,
+            position: 0,
+            received: 0,
+            body_size: 0,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            head_request: false,
+        }
+    }
+
+    /// Tells the parser which method the request used. A response to HEAD
+    /// has no body even if its headers describe one.
+    pub fn set_request_method(&mut self, method: &str) {
+        self.head_request = method.eq_ignore_ascii_case("HEAD");
+    }
+
+    /// Sets the limit on the size of the status line and headers, including
+    /// line endings. Exceeding it makes `push` fail with `HeadTooLarge`. The
+    /// trailer section of a chunked body has a limit of the same size.
+    pub fn set_max_header_size(&mut self, max_header_size: usize) {
+        self.max_header_size = max_header_size;
+    }
+
+    /// Sets the limit on the size of the decoded body. Exceeding it makes
+    /// `push` fail with `BodyTooLarge`.
+    pub fn set_max_body_size(&mut self, max_body_size: usize) {
+        self.max_body_size = max_body_size;
+    }
+
+    pub fn max_body_size(&self) -> usize {
+        self.max_body_size
+    }
+
+    /// Returns true once the whole response has been parsed. Bytes pushed
+    /// after that are ignored.
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, State::Done)
+    }
+
+    /// Parses the next piece of the response.
+    pub fn push(&mut self, input: &[u8]) -> Result<Vec<ResponseEvent>, Error> {
+        let mut events = Vec::new();
+        let mut input = input;
+        self.received = self.received.saturating_add(input.len());
+        if let State::Head = self.state {
+            // ステータス行の前の空行は無視する
+            if self.buffer.is_empty() {
+                let start = input
+                    .iter()
+                    .position(|b| *b != b'\r' && *b != b'\n')
+                    .unwrap_or(input.len());
+                input = &input[start..];
+                self.position += start;
+            }
+            self.buffer.extend_from_slice(input);
+            loop {
+                let too_large = Error::parse(
+                    ParseErrorKind::HeadTooLarge,
+                    self.position.saturating_add(self.max_header_size),
+                );
+                let end = match find_head_end(&self.buffer, self.scanned) {
+                    Some(end) if end <= self.max_header_size => end,
+                    Some(_) => return Err(too_large),
+                    None if self.buffer.len() > self.max_header_size => return Err(too_large),
+                    None => {
+                        self.scanned = self.buffer.len();
+                        return Ok(events);
+                    }
+                };
+                let buffer = core::mem::take(&mut self.buffer);
+                let head = parse_head(&buffer[..end]).map_err(|e| e.offset_by(self.position))?;
+                self.position += end;
+                // 100 Continue などの中間レスポンスは読み飛ばし、最終レスポンスを待つ
+                if head.status_code.is_informational()
+                    && head.status_code != StatusCode::SWITCHING_PROTOCOLS
+                {
+                    self.buffer = buffer[end..].to_vec();
+                    self.scanned = 0;
+                    continue;
+                }
+
+                // 本文の長さを決めるヘッダの誤りは、ヘッダの終わりの位置で報告する
+                let length = if self.head_request {
+                    BodyLength::Fixed(0)
+                } else {
+                    body_length(head.status_code, &head.headers)
+                        .map_err(|e| e.offset_by(self.position))?
+                };
+                self.state = match length {
+                    BodyLength::Fixed(length) if length > self.max_body_size => {
+                        return Err(Error::parse(ParseErrorKind::BodyTooLarge, self.position))
+                    }
+                    BodyLength::Fixed(length) => State::Fixed(length),
+                    BodyLength::Chunked => {
+                        // トレーラもヘッダと同じ上限で数える
+                        let mut decoder = ChunkedDecoder::new();
+                        decoder.set_max_trailer_size(self.max_header_size);
+                        State::Chunked(decoder)
+                    }
+                    BodyLength::UntilClose => State::UntilClose,
+                };
+                events.push(ResponseEvent::Head(head));
+                self.push_body(&buffer[end..], &mut events)?;
+                break;
+            }
+        } else {
+            self.push_body(input, &mut events)?;
+        }
+        Ok(events)
+    }
+
+    fn push_body(&mut self, input: &[u8], events: &mut Vec<ResponseEvent>) -> Result<(), Error> {
+        // トレーラは本文が終わったときだけ Some になる
+        let (data, trailers) = match &mut self.state {
+            State::Head | State::Done => return Ok(()),
+            State::Fixed(remaining) => {
+                let n = (*remaining).min(input.len());
+                *remaining -= n;
+                (input[..n].to_vec(), (*remaining == 0).then(HeaderMap::new))
+            }
+            State::Chunked(decoder) => {
+                decoder
+                    .push(input)
+                    .map_err(|e| e.offset_by(self.position))?;
+                let trailers = decoder.is_done().then(|| decoder.trailers());
+                (decoder.take_body(), trailers)
+            }
+            State::UntilClose => (input.to_vec(), None),
+        };
+        self.emit_body(data, events)?;
+        if let Some(trailers) = trailers {
+            if !trailers.is_empty() {
+                events.push(ResponseEvent::Trailers(trailers));
+            }
+            self.state = State::Done;
+            events.push(ResponseEvent::Done);
+        }
+        Ok(())
+    }
+
+    fn emit_body(&mut self, data: Vec<u8>, events: &mut Vec<ResponseEvent>) -> Result<(), Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.body_size = self.body_size.saturating_add(data.len());
+        if self.body_size > self.max_body_size {
+            return Err(Error::parse(ParseErrorKind::BodyTooLarge, self.received));
+        }
+        events.push(ResponseEvent::Body(data));
+        Ok(())
+    }
+
+    /// Tells the parser that the connection was closed. This completes a
+    /// body delimited by the close, and is an error anywhere else before the
+    /// response is done. The error is reported at the end of the input.
+    pub fn finish(&mut self) -> Result<Vec<ResponseEvent>, Error> {
+        match self.state {
+            State::Head => Err(Error::parse(ParseErrorKind::IncompleteHead, self.received)),
+            State::Fixed(_) | State::Chunked(_) => {
+                Err(Error::parse(ParseErrorKind::IncompleteBody, self.received))
+            }
+            State::UntilClose => {
+                self.state = State::Done;
+                Ok(alloc::vec![ResponseEvent::Done])
+            }
+            State::Done => Ok(Vec::new()),
+        }
+    }
+}
+
+impl Default for ResponseParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the length of the head including the empty line that ends it,
+/// searching for that line's LF from `from` on.
+fn find_head_end(buffer: &[u8], from: usize) -> Option<usize> {
+    (from..buffer.len()).find_map(|i| {
+        let line = &buffer[..i];
+        let blank = buffer[i] == b'\n' && (line.ends_with(b"\n") || line.ends_with(b"\n\r"));
+        blank.then_some(i + 1)
+    })
+}
+
+fn parse_head(raw: &[u8]) -> Result<ResponseHead, Error> {
+    let (lines, _) = split_head(raw)?;
+    let (&(offset, status_line), header_lines) = match lines.split_first() {
+        Some(split) => split,
+        None => return Err(Error::parse(ParseErrorKind::IncompleteHead, raw.len())),
+    };
+    let (version, status_code, reason) =
+        parse_status_line(status_line).map_err(|e| e.offset_by(offset))?;
+
+    let mut headers = HeaderMap::new();
+    for &(offset, line) in header_lines {
+        let header = parse_header_line(line).map_err(|e| e.offset_by(offset))?;
+        headers.append(&header.name(), &header.value());
+    }
+    Ok(ResponseHead {
+        version,
+        status_code,
+        reason,
+        headers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::Header;
+    use crate::http::DEFAULT_MAX_CHUNK_LINE_SIZE;
+    use alloc::format;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\nX-Sum: 1\r\n\r\n";
+
+    /// Pushes `input` in pieces of `size` bytes and merges the body events.
+    fn parse_in_pieces(input: &[u8], size: usize) -> Result<Vec<ResponseEvent>, Error> {
+        let mut parser = ResponseParser::new();
+        let mut events: Vec<ResponseEvent> = Vec::new();
+        for piece in input.chunks(size) {
+            for event in parser.push(piece)? {
+                match (events.last_mut(), event) {
+                    (Some(ResponseEvent::Body(body)), ResponseEvent::Body(data)) => {
+                        body.extend_from_slice(&data)
+                    }
+                    (_, event) => events.push(event),
+                }
+            }
+        }
+        events.extend(parser.finish()?);
+        Ok(events)
+    }
+
+    fn head(status_code: u16, headers: &[(&str, &str)]) -> ResponseEvent {
+        ResponseEvent::Head(ResponseHead {
+            version: "HTTP/1.1".to_string(),
+            status_code: StatusCode::from_u16(status_code).unwrap(),
+            reason: "OK".to_string(),
+            headers: headers
+                .iter()
+                .fold(HeaderMap::new(), |mut map, (name, value)| {
+                    map.append(name, value);
+                    map
+                }),
+        })
+    }
+
+    #[test]
+    fn test_events() {
+        let expected = vec![
+            head(200, &[("Transfer-Encoding", "chunked")]),
+            ResponseEvent::Body(b"hello world".to_vec()),
+            ResponseEvent::Trailers(HeaderMap::from(vec![Header::new(
+                "X-Sum".to_string(),
+                "1".to_string(),
+            )])),
+            ResponseEvent::Done,
+        ];
+        // 1 バイトずつでも一度にでも同じ結果になる
+        for size in 1..=RESPONSE.len() {
+            assert_eq!(
+                parse_in_pieces(RESPONSE, size).as_ref(),
+                Ok(&expected),
+                "size {}",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn test_head_is_emitted_before_body() {
+        let mut parser = ResponseParser::new();
+        assert_eq!(parser.push(b"\r\nHTTP/1.1 200 OK\r\n"), Ok(vec![]));
+        assert_eq!(parser.push(b"Content-Length: 4\r\n"), Ok(vec![]));
+        assert_eq!(
+            parser.push(b"\r\nab"),
+            Ok(vec![
+                head(200, &[("Content-Length", "4")]),
+                ResponseEvent::Body(b"ab".to_vec())
+            ])
+        );
+        assert!(!parser.is_done());
+        assert_eq!(
+            parser.push(b"cdHTTP/1.1"),
+            Ok(vec![
+                ResponseEvent::Body(b"cd".to_vec()),
+                ResponseEvent::Done
+            ])
+        );
+        assert!(parser.is_done());
+        assert_eq!(parser.push(b"more"), Ok(vec![]));
+        assert_eq!(parser.finish(), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_until_close() {
+        let mut parser = ResponseParser::new();
+        assert_eq!(
+            parser.push(b"HTTP/1.1 200 OK\n\nab"),
+            Ok(vec![head(200, &[]), ResponseEvent::Body(b"ab".to_vec())])
+        );
+        assert_eq!(parser.push(b""), Ok(vec![]));
+        assert_eq!(
+            parser.push(b"c"),
+            Ok(vec![ResponseEvent::Body(b"c".to_vec())])
+        );
+        assert!(!parser.is_done());
+        assert_eq!(parser.finish(), Ok(vec![ResponseEvent::Done]));
+        assert!(parser.is_done());
+    }
+
+    #[test]
+    fn test_empty_body() {
+        let mut parser = ResponseParser::new();
+        assert_eq!(
+            parser.push(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"),
+            Ok(vec![
+                head(200, &[("Content-Length", "0")]),
+                ResponseEvent::Done
+            ])
+        );
+    }
+
+    #[test]
+    fn test_incomplete() {
+        // (raw, kind); the error is at the end of the input
+        let cases: &[(&[u8], ParseErrorKind)] = &[
+            (b"", ParseErrorKind::IncompleteHead),
+            (b"HTTP/1.1 200 OK\r\n", ParseErrorKind::IncompleteHead),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nab",
+                ParseErrorKind::IncompleteBody,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n",
+                ParseErrorKind::IncompleteBody,
+            ),
+        ];
+        for (raw, kind) in cases {
+            let mut parser = ResponseParser::new();
+            assert!(parser.push(raw).is_ok(), "parsing {:?}", raw);
+            assert_eq!(
+                parser.finish(),
+                Err(Error::parse(kind.clone(), raw.len())),
+                "parsing {:?}",
+                raw
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_offsets() {
+        // (raw, kind, offset)
+        let cases: &[(&[u8], ParseErrorKind, usize)] = &[
+            // 前の空行と中間レスポンスも数える
+            (
+                b"\r\nHTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nX(Y): 1\r\n\r\n",
+                ParseErrorKind::InvalidHeaderName("X(Y)".to_string()),
+                45,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: x\r\n\r\n",
+                ParseErrorKind::InvalidContentLength("x".to_string()),
+                38,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\nzz\r\n",
+                ParseErrorKind::InvalidChunk("zz".to_string()),
+                55,
+            ),
+        ];
+        for (raw, kind, offset) in cases {
+            // 一度に来ても少しずつ来ても同じ位置になる
+            for size in 1..=raw.len() {
+                let mut parser = ResponseParser::new();
+                let result: Result<Vec<_>, _> = raw.chunks(size).map(|p| parser.push(p)).collect();
+                assert_eq!(
+                    result.err(),
+                    Some(Error::parse(kind.clone(), *offset)),
+                    "parsing {:?} in pieces of {}",
+                    raw,
+                    size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_header_size() {
+        let raw = b"HTTP/1.1 200 OK\r\nX: 0123456789\r\n\r\n";
+        for max in [raw.len(), raw.len() + 1] {
+            let mut parser = ResponseParser::new();
+            parser.set_max_header_size(max);
+            assert!(parser.push(raw).is_ok(), "max {}", max);
+        }
+
+        // 一度に来ても少しずつ来ても上限を超えた時点でエラーになる
+        for size in [1, 7, raw.len()] {
+            let mut parser = ResponseParser::new();
+            parser.set_max_header_size(raw.len() - 1);
+            let result: Result<Vec<_>, _> = raw.chunks(size).map(|p| parser.push(p)).collect();
+            assert_eq!(
+                result,
+                Err(Error::parse(ParseErrorKind::HeadTooLarge, raw.len() - 1)),
+                "size {}",
+                size
+            );
+        }
+
+        let mut parser = ResponseParser::new();
+        parser.set_max_header_size(16);
+        assert_eq!(
+            parser.push(b"HTTP/1.1 200 OK\r\nX-Never-Ending"),
+            Err(Error::parse(ParseErrorKind::HeadTooLarge, 16))
+        );
+    }
+
+    #[test]
+    fn test_max_body_size() {
+        // Content-Length が上限を超えていれば本文を待たずにエラーになる
+        let mut parser = ResponseParser::new();
+        parser.set_max_body_size(4);
+        assert_eq!(
+            parser.push(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n"),
+            Err(Error::parse(ParseErrorKind::BodyTooLarge, 38))
+        );
+
+        let mut parser = ResponseParser::new();
+        parser.set_max_body_size(4);
+        assert!(parser.push(b"HTTP/1.1 200 OK\r\n\r\nabc").is_ok());
+        assert!(parser.push(b"d").is_ok());
+        assert_eq!(
+            parser.push(b"e"),
+            Err(Error::parse(ParseErrorKind::BodyTooLarge, 24))
+        );
+
+        let mut parser = ResponseParser::new();
+        parser.set_max_body_size(10);
+        assert_eq!(
+            parser.push(RESPONSE),
+            Err(Error::parse(ParseErrorKind::BodyTooLarge, RESPONSE.len()))
+        );
+
+        let mut parser = ResponseParser::new();
+        parser.set_max_body_size(11);
+        assert!(parser.push(RESPONSE).is_ok());
+        assert!(parser.is_done());
+    }
+
+    #[test]
+    fn test_chunked_limits() {
+        let head = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+
+        // 終わらない chunk-ext は本文の大きさに数えられなくても止める
+        let mut parser = ResponseParser::new();
+        assert!(parser.push(head).is_ok());
+        assert!(parser.push(b"1;").is_ok());
+        let ext = [b'a'; 1024];
+        let result: Result<Vec<_>, _> = (0..8).map(|_| parser.push(&ext)).collect();
+        let line = format!("1;{}", "a".repeat(DEFAULT_MAX_CHUNK_LINE_SIZE - 2));
+        assert_eq!(
+            result,
+            Err(Error::parse(ParseErrorKind::InvalidChunk(line), head.len()))
+        );
+
+        // 終わらないトレーラはヘッダの上限で止める
+        let mut parser = ResponseParser::new();
+        parser.set_max_header_size(head.len());
+        assert!(parser.push(head).is_ok());
+        assert!(parser.push(b"0\r\n").is_ok());
+        let result: Result<Vec<_>, _> = (0..100).map(|_| parser.push(b"X: y\r\n")).collect();
+        assert_eq!(
+            result,
+            Err(Error::parse(
+                ParseErrorKind::HeadTooLarge,
+                head.len() * 2 + 3
+            ))
+        );
+    }
+
+    #[test]
+    fn test_head_request() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 42\r\n\r\n";
+        let mut parser = ResponseParser::new();
+        parser.set_request_method("HEAD");
+        assert_eq!(
+            parser.push(raw),
+            Ok(vec![
+                head(200, &[("Content-Length", "42")]),
+                ResponseEvent::Done
+            ])
+        );
+
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let mut parser = ResponseParser::new();
+        parser.set_request_method("head");
+        assert!(parser.push(raw).is_ok());
+        assert!(parser.is_done());
+
+        let mut parser = ResponseParser::new();
+        parser.set_request_method("GET");
+        assert!(parser.push(raw).is_ok());
+        assert!(!parser.is_done());
+    }
+
+    #[test]
+    fn test_interim_responses_are_skipped() {
+        let raw = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 103 Early Hints\r\nLink: </a.css>\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let expected = vec![
+            head(200, &[("Content-Length", "2")]),
+            ResponseEvent::Body(b"ok".to_vec()),
+            ResponseEvent::Done,
+        ];
+        for size in 1..=raw.len() {
+            assert_eq!(
+                parse_in_pieces(raw, size).as_ref(),
+                Ok(&expected),
+                "size {}",
+                size
+            );
+        }
+    }
+}
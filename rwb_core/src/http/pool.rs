@@ -0,0 +1,324 @@
+//! Bookkeeping for persistent connections. The pool does not know how to
+//! open a connection; transports ask it for an idle one and give connections
+//! back when a response is done.
+
+use super::body_length;
+use super::BodyLength;
+use super::HttpRequest;
+use super::HttpResponse;
+use crate::error::Error;
+use crate::error::HttpError;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// How long an idle connection is kept, in milliseconds.
+pub const DEFAULT_IDLE_TIMEOUT: u64 = 60_000;
+/// The number of connections to one host and port, the same as browsers use.
+pub const DEFAULT_MAX_CONNECTIONS_PER_HOST: usize = 6;
+
+#[derive(Debug)]
+struct Idle<C> {
+    host: String,
+    port: u16,
+    connection: C,
+    /// When the connection was given back, in milliseconds.
+    since: u64,
+}
+
+/// Keep-alive connections keyed by host and port. Times are milliseconds
+/// from any fixed point, as given by the caller's clock.
+#[derive(Debug)]
+pub struct ConnectionPool<C> {
+    idle: Vec<Idle<C>>,
+    /// The number of connections handed out per host and port.
+    active: Vec<(String, u16, usize)>,
+    idle_timeout: u64,
+    max_connections_per_host: usize,
+}
+
+impl<C> ConnectionPool<C> {
+    pub fn new() -> Self {
+        Self {
+            idle: Vec::new(),
+            active: Vec::new(),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+            max_connections_per_host: DEFAULT_MAX_CONNECTIONS_PER_HOST,
+        }
+    }
+
+    pub fn set_idle_timeout(&mut self, idle_timeout: u64) {
+        self.idle_timeout = idle_timeout;
+    }
+
+    pub fn set_max_connections_per_host(&mut self, max_connections_per_host: usize) {
+        self.max_connections_per_host = max_connections_per_host;
+    }
+
+    /// Starts using a connection to `host` and `port`. Returns the most
+    /// recently used idle connection if there is one, or `None` if the caller
+    /// should open a new one. Either way the connection counts against the
+    /// limit until it is passed to `release`.
+    pub fn acquire(&mut self, host: &str, port: u16, now: u64) -> Result<Option<C>, Error> {
+        self.remove_expired(now);
+        if let Some(i) = self
+            .idle
+            .iter()
+            .rposition(|idle| idle.host == host && idle.port == port)
+        {
+            *self.active_mut(host, port) += 1;
+            return Ok(Some(self.idle.remove(i).connection));
+        }
+
+        if self.active_count(host, port) >= self.max_connections_per_host {
+            return Err(HttpError::TooManyConnections(host.to_string()).into());
+        }
+        *self.active_mut(host, port) += 1;
+        Ok(None)
+    }
+
+    /// Finishes using a connection from `acquire`. Pass the connection back
+    /// if it can carry another request, or `None` if it was closed.
+    pub fn release(&mut self, host: &str, port: u16, connection: Option<C>, now: u64) {
+        let active = self.active_mut(host, port);
+        *active = active.saturating_sub(1);
+        self.active.retain(|(_, _, count)| *count > 0);
+
+        if let Some(connection) = connection {
+            if self.active_count(host, port) + self.idle_count(host, port)
+                < self.max_connections_per_host
+            {
+                self.idle.push(Idle {
+                    host: host.to_string(),
+                    port,
+                    connection,
+                    since: now,
+                });
+            }
+        }
+    }
+
+    /// Drops the idle connections that have not been used for longer than
+    /// the idle timeout.
+    pub fn remove_expired(&mut self, now: u64) {
+        let idle_timeout = self.idle_timeout;
+        self.idle
+            .retain(|idle| now.saturating_sub(idle.since) < idle_timeout);
+    }
+
+    pub fn idle_count(&self, host: &str, port: u16) -> usize {
+        self.idle
+            .iter()
+            .filter(|idle| idle.host == host && idle.port == port)
+            .count()
+    }
+
+    pub fn active_count(&self, host: &str, port: u16) -> usize {
+        self.active
+            .iter()
+            .find(|(h, p, _)| h == host && *p == port)
+            .map_or(0, |(_, _, count)| *count)
+    }
+
+    fn active_mut(&mut self, host: &str, port: u16) -> &mut usize {
+        let i = match self
+            .active
+            .iter()
+            .position(|(h, p, _)| h == host && *p == port)
+        {
+            Some(i) => i,
+            None => {
+                self.active.push((host.to_string(), port, 0));
+                self.active.len() - 1
+            }
+        };
+        &mut self.active[i].2
+    }
+}
+
+impl<C> Default for ConnectionPool<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns true if the connection that carried `request` and `response` can
+/// be used for another request: the body did not end with the connection,
+/// and neither side asked to close it.
+///
+/// https://www.rfc-editor.org/rfc/rfc9112#section-9.3
+pub fn is_reusable(request: &HttpRequest, response: &HttpResponse) -> bool {
+    let has_token =
+        |list: Vec<String>, token: &str| list.iter().any(|value| value.eq_ignore_ascii_case(token));
+    let request_headers = request.headers();
+    let response_headers = response.headers();
+    if has_token(request_headers.get_list("Connection"), "close")
+        || has_token(response_headers.get_list("Connection"), "close")
+    {
+        return false;
+    }
+    // HTTP/1.0 は明示的に keep-alive と言われたときだけ
+    if response.version() != "HTTP/1.1"
+        && !has_token(response_headers.get_list("Connection"), "keep-alive")
+    {
+        return false;
+    }
+    if request.method().eq_ignore_ascii_case("HEAD") {
+        return true;
+    }
+    matches!(
+        body_length(response.status_code(), &response_headers),
+        Ok(BodyLength::Fixed(_)) | Ok(BodyLength::Chunked)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reuse_idle_connection() {
+        let mut pool: ConnectionPool<u32> = ConnectionPool::new();
+        assert_eq!(pool.acquire("a.test", 80, 0), Ok(None));
+        assert_eq!(pool.active_count("a.test", 80), 1);
+        pool.release("a.test", 80, Some(1), 0);
+        assert_eq!(pool.active_count("a.test", 80), 0);
+        assert_eq!(pool.idle_count("a.test", 80), 1);
+
+        // ホストかポートが違えば使わない
+        assert_eq!(pool.acquire("a.test", 8080, 1), Ok(None));
+        assert_eq!(pool.acquire("b.test", 80, 1), Ok(None));
+        assert_eq!(pool.acquire("a.test", 80, 1), Ok(Some(1)));
+        assert_eq!(pool.idle_count("a.test", 80), 0);
+        assert_eq!(pool.acquire("a.test", 80, 1), Ok(None));
+    }
+
+    #[test]
+    fn test_most_recently_used_first() {
+        let mut pool: ConnectionPool<u32> = ConnectionPool::new();
+        for _ in 0..3 {
+            assert_eq!(pool.acquire("a.test", 80, 0), Ok(None));
+        }
+        pool.release("a.test", 80, Some(1), 10);
+        pool.release("a.test", 80, Some(2), 20);
+        pool.release("a.test", 80, Some(3), 30);
+        assert_eq!(pool.acquire("a.test", 80, 40), Ok(Some(3)));
+        assert_eq!(pool.acquire("a.test", 80, 40), Ok(Some(2)));
+    }
+
+    #[test]
+    fn test_closed_connection_is_not_kept() {
+        let mut pool: ConnectionPool<u32> = ConnectionPool::new();
+        assert_eq!(pool.acquire("a.test", 80, 0), Ok(None));
+        pool.release("a.test", 80, None, 0);
+        assert_eq!(pool.idle_count("a.test", 80), 0);
+        assert_eq!(pool.active_count("a.test", 80), 0);
+    }
+
+    #[test]
+    fn test_idle_timeout() {
+        let mut pool: ConnectionPool<u32> = ConnectionPool::new();
+        pool.set_idle_timeout(1_000);
+        assert_eq!(pool.acquire("a.test", 80, 0), Ok(None));
+        pool.release("a.test", 80, Some(1), 5_000);
+        assert_eq!(pool.acquire("a.test", 80, 5_999), Ok(Some(1)));
+        pool.release("a.test", 80, Some(1), 6_000);
+        assert_eq!(pool.acquire("a.test", 80, 7_000), Ok(None));
+        assert_eq!(pool.idle_count("a.test", 80), 0);
+
+        pool.release("a.test", 80, Some(2), 7_000);
+        pool.remove_expired(8_000);
+        assert_eq!(pool.idle_count("a.test", 80), 0);
+    }
+
+    #[test]
+    fn test_max_connections_per_host() {
+        let mut pool: ConnectionPool<u32> = ConnectionPool::new();
+        pool.set_max_connections_per_host(2);
+        assert_eq!(pool.acquire("a.test", 80, 0), Ok(None));
+        assert_eq!(pool.acquire("a.test", 80, 0), Ok(None));
+        assert_eq!(
+            pool.acquire("a.test", 80, 0),
+            Err(Error::Http(HttpError::TooManyConnections(
+                "a.test".to_string()
+            )))
+        );
+        // 別のホストには影響しない
+        assert_eq!(pool.acquire("b.test", 80, 0), Ok(None));
+
+        pool.release("a.test", 80, Some(1), 0);
+        assert_eq!(pool.acquire("a.test", 80, 0), Ok(Some(1)));
+        pool.release("a.test", 80, None, 0);
+        assert_eq!(pool.acquire("a.test", 80, 0), Ok(None));
+    }
+
+    #[test]
+    fn test_idle_connections_count_against_limit() {
+        let mut pool: ConnectionPool<u32> = ConnectionPool::new();
+        pool.set_max_connections_per_host(1);
+        assert_eq!(pool.acquire("a.test", 80, 0), Ok(None));
+        pool.release("a.test", 80, Some(1), 0);
+        // 上限を超えて返されたものは閉じる
+        pool.release("a.test", 80, Some(2), 0);
+        assert_eq!(pool.idle_count("a.test", 80), 1);
+    }
+
+    #[test]
+    fn test_is_reusable() {
+        let get = HttpRequest::builder("GET", "/").build().unwrap();
+        let head = HttpRequest::builder("HEAD", "/").build().unwrap();
+        let close = HttpRequest::builder("GET", "/")
+            .header("Connection", "close")
+            .build()
+            .unwrap();
+
+        // (request, response, reusable)
+        let cases: &[(&HttpRequest, &[u8], bool)] = &[
+            (
+                &get,
+                b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+                true,
+            ),
+            (
+                &get,
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+                true,
+            ),
+            (&get, b"HTTP/1.1 204 No Content\r\n\r\n", true),
+            (&get, b"HTTP/1.1 304 Not Modified\r\n\r\n", true),
+            (&head, b"HTTP/1.1 200 OK\r\n\r\n", true),
+            (&get, b"HTTP/1.1 200 OK\r\n\r\nuntil close", false),
+            (
+                &get,
+                b"HTTP/1.1 200 OK\r\nConnection: Close\r\nContent-Length: 0\r\n\r\n",
+                false,
+            ),
+            (
+                &get,
+                b"HTTP/1.1 200 OK\r\nConnection: upgrade, close\r\nContent-Length: 0\r\n\r\n",
+                false,
+            ),
+            (
+                &close,
+                b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+                false,
+            ),
+            (&get, b"HTTP/1.0 200 OK\r\nContent-Length: 0\r\n\r\n", false),
+            (
+                &get,
+                b"HTTP/1.0 200 OK\r\nConnection: keep-alive\r\nContent-Length: 0\r\n\r\n",
+                true,
+            ),
+        ];
+        for (request, raw, expected) in cases {
+            let response = HttpResponse::new(raw.to_vec()).unwrap();
+            assert_eq!(
+                is_reusable(request, &response),
+                *expected,
+                "{} {:?}",
+                request.method(),
+                String::from_utf8_lossy(raw)
+            );
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! Following redirects as the Fetch Standard does for `redirect: "follow"`.
+//!
+//! https://fetch.spec.whatwg.org/#http-redirect-fetch
+
+use super::HttpRequest;
+use super::HttpResponse;
+use crate::error::Error;
+use crate::error::HttpError;
+use crate::url::Url;
+use alloc::format;
+use alloc::vec::Vec;
+
+/// The number of redirects followed before giving up, the same as in the
+/// Fetch Standard.
+pub const DEFAULT_MAX_REDIRECTS: usize = 20;
+
+/// Headers that describe the request body, dropped when a redirect turns the
+/// request into a GET without a body.
+const BODY_HEADERS: &[&str] = &[
+    "Content-Encoding",
+    "Content-Language",
+    "Content-Length",
+    "Content-Location",
+    "Content-Type",
+    "Transfer-Encoding",
+];
+
+/// Headers with credentials, which are not sent to another origin.
+const CREDENTIAL_HEADERS: &[&str] = &["Authorization", "Cookie", "Proxy-Authorization"];
+
+/// Returns the URL `request` is for: the URL it was built with, or else one
+/// made from its Host header and target.
+pub(super) fn request_url(request: &HttpRequest) -> Option<Url> {
+    if let Some(url) = request.url() {
+        return Some(url);
+    }
+    let target = request.target();
+    if !target.starts_with('/') {
+        // absolute-form
+        return Url::new(target).ok();
+    }
+    let host = request.headers().get("Host")?;
+    Url::new(format!("http://{}{}", host, target)).ok()
+}
+
+/// Returns the request to send next if `response` redirects `request`, or
+/// `None` if it does not.
+///
+/// 303 turns any method but HEAD into GET, and 301 and 302 turn POST into GET,
+/// as browsers do. 307 and 308 keep the method and body.
+pub fn redirect_request(
+    request: &HttpRequest,
+    response: &HttpResponse,
+) -> Result<Option<HttpRequest>, Error> {
+    let status_code = response.status_code();
+    if ![301, 302, 303, 307, 308].contains(&status_code.as_u16()) {
+        return Ok(None);
+    }
+    // Location がなければリダイレクトせず、そのまま返す
+    let location = match response.headers().get("Location") {
+        Some(location) => location,
+        None => return Ok(None),
+    };
+
+    let base = request_url(request);
+    let url = match &base {
+        Some(base) => base.join(&location),
+        None => Url::new(location.clone()),
+    }
+    .map_err(|_| HttpError::InvalidRedirect(location.clone()))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(HttpError::InvalidRedirect(location).into());
+    }
+
+    let method = request.method();
+    let to_get = match status_code.as_u16() {
+        303 => !method.eq_ignore_ascii_case("HEAD"),
+        301 | 302 => method.eq_ignore_ascii_case("POST"),
+        _ => false,
+    };
+    let same_origin = base.is_some_and(|base| base.origin().is_same_origin(&url.origin()));
+
+    let mut builder = HttpRequest::builder_for_url(if to_get { "GET" } else { &method }, &url);
+    for header in &request.headers() {
+        let name = header.name();
+        let is = |names: &[&str]| names.iter().any(|n| n.eq_ignore_ascii_case(&name));
+        if name.eq_ignore_ascii_case("Host")
+            || (to_get && is(BODY_HEADERS))
+            || (!same_origin && is(CREDENTIAL_HEADERS))
+        {
+            continue;
+        }
+        builder = builder.header(&name, &header.value());
+    }
+    if !to_get {
+        builder = builder.body(request.body());
+    }
+    builder.build().map(Some)
+}
+
+/// Sends `request` with `send` and keeps following redirects, up to
+/// `max_redirects` of them. The returned response records the URLs it was
+/// redirected from in `redirect_chain()`.
+pub fn follow_redirects<F>(
+    request: HttpRequest,
+    max_redirects: usize,
+    mut send: F,
+) -> Result<HttpResponse, Error>
+where
+    F: FnMut(&HttpRequest) -> Result<HttpResponse, Error>,
+{
+    let mut request = request;
+    let mut redirect_chain = Vec::new();
+    loop {
+        let mut response = send(&request)?;
+        let url = request_url(&request);
+        let next = match redirect_request(&request, &response)? {
+            Some(next) => next,
+            None => {
+                response.redirect_chain = redirect_chain;
+                response.url = url;
+                return Ok(response);
+            }
+        };
+        if redirect_chain.len() >= max_redirects {
+            return Err(HttpError::TooManyRedirects.into());
+        }
+        // リダイレクトできたなら、元の URL も分かっている
+        if let Some(url) = url {
+            redirect_chain.push(url);
+        }
+        request = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::NetworkError;
+    use alloc::string::String;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn response(raw: &str) -> HttpResponse {
+        HttpResponse::new(raw.as_bytes().to_vec()).unwrap()
+    }
+
+    fn redirect(status: u32, location: &str) -> HttpResponse {
+        response(&format!(
+            "HTTP/1.1 {} Redirect\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+            status, location
+        ))
+    }
+
+    fn get(url: &str) -> HttpRequest {
+        let url = Url::new(url.to_string()).unwrap();
+        HttpRequest::builder_for_url("GET", &url).build().unwrap()
+    }
+
+    #[test]
+    fn test_method_rewriting() {
+        // (status, method, method after redirect, body kept)
+        let cases: &[(u32, &str, &str, bool)] = &[
+            (301, "GET", "GET", true),
+            (301, "POST", "GET", false),
+            (301, "PUT", "PUT", true),
+            (302, "POST", "GET", false),
+            (302, "DELETE", "DELETE", true),
+            (303, "POST", "GET", false),
+            (303, "PUT", "GET", false),
+            (303, "HEAD", "HEAD", false),
+            (307, "POST", "POST", true),
+            (307, "PUT", "PUT", true),
+            (308, "POST", "POST", true),
+            (308, "HEAD", "HEAD", false),
+        ];
+        let url = Url::new("http://example.com/form".to_string()).unwrap();
+        for (status, method, expected, body_kept) in cases {
+            let mut builder =
+                HttpRequest::builder_for_url(method, &url).header("Accept", "text/html");
+            if *method != "HEAD" {
+                builder = builder
+                    .header("Content-Type", "text/plain")
+                    .body(b"data".to_vec());
+            }
+            let request = builder.build().unwrap();
+            let next = redirect_request(&request, &redirect(*status, "/done"))
+                .unwrap()
+                .expect("not redirected");
+            let case = format!("{} {}", status, method);
+            assert_eq!(next.method(), *expected, "{}", case);
+            assert_eq!(next.target(), "/done", "{}", case);
+            assert_eq!(
+                next.headers().get("Accept").as_deref(),
+                Some("text/html"),
+                "{}",
+                case
+            );
+            if *body_kept {
+                assert_eq!(next.body(), b"data", "{}", case);
+                assert_eq!(next.headers().content_length(), Ok(Some(4)), "{}", case);
+                assert!(next.headers().contains("Content-Type"), "{}", case);
+            } else {
+                assert!(next.body().is_empty(), "{}", case);
+                assert!(!next.headers().contains("Content-Length"), "{}", case);
+                assert!(!next.headers().contains("Content-Type"), "{}", case);
+            }
+        }
+    }
+
+    #[test]
+    fn test_location_resolution() {
+        // (request URL, Location, next URL, next Host)
+        let cases: &[(&str, &str, &str, &str)] = &[
+            ("http://a.test/x/y", "z", "http://a.test/x/z", "a.test"),
+            (
+                "http://a.test/x/y",
+                "/z?q=1",
+                "http://a.test/z?q=1",
+                "a.test",
+            ),
+            (
+                "http://a.test:8000/x",
+                "//b.test/",
+                "http://b.test/",
+                "b.test",
+            ),
+            (
+                "http://a.test/x",
+                "https://b.test:8443/p",
+                "https://b.test:8443/p",
+                "b.test:8443",
+            ),
+        ];
+        for (from, location, to, host) in cases {
+            let next = redirect_request(&get(from), &redirect(302, location))
+                .unwrap()
+                .expect("not redirected");
+            assert_eq!(next.url().map(|u| u.to_string()).as_deref(), Some(*to));
+            assert_eq!(next.headers().get_all("Host"), [*host], "{}", location);
+        }
+
+        // URL を持たない要求は Host ヘッダと対象から URL を組み立てる
+        let request = HttpRequest::builder("GET", "/a/b")
+            .header("Host", "host.test:8000")
+            .build()
+            .unwrap();
+        let next = redirect_request(&request, &redirect(301, "c"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            next.url().map(|u| u.to_string()).as_deref(),
+            Some("http://host.test:8000/a/c")
+        );
+    }
+
+    #[test]
+    fn test_not_redirected() {
+        let request = get("http://a.test/");
+        let cases = [
+            response("HTTP/1.1 200 OK\r\nLocation: /x\r\n\r\n"),
+            response("HTTP/1.1 304 Not Modified\r\nLocation: /x\r\n\r\n"),
+            response("HTTP/1.1 300 Multiple Choices\r\nLocation: /x\r\n\r\n"),
+            response("HTTP/1.1 302 Found\r\n\r\n"),
+        ];
+        for response in &cases {
+            assert_eq!(redirect_request(&request, response), Ok(None));
+        }
+    }
+
+    #[test]
+    fn test_invalid_location() {
+        let request = get("http://a.test/");
+        let cases = [
+            "http://[::1",
+            "ftp://a.test/",
+            "javascript:alert(1)",
+            "data:,x",
+        ];
+        for location in cases {
+            assert_eq!(
+                redirect_request(&request, &redirect(302, location)),
+                Err(Error::Http(HttpError::InvalidRedirect(
+                    location.to_string()
+                )))
+            );
+        }
+    }
+
+    #[test]
+    fn test_credentials_not_sent_cross_origin() {
+        let url = Url::new("http://a.test/".to_string()).unwrap();
+        let request = HttpRequest::builder_for_url("GET", &url)
+            .header("Authorization", "Basic eDp5")
+            .header("Cookie", "id=1")
+            .build()
+            .unwrap();
+
+        let next = redirect_request(&request, &redirect(302, "/same"))
+            .unwrap()
+            .unwrap();
+        assert!(next.headers().contains("Authorization"));
+        assert!(next.headers().contains("Cookie"));
+
+        let next = redirect_request(&request, &redirect(302, "http://b.test/"))
+            .unwrap()
+            .unwrap();
+        assert!(!next.headers().contains("Authorization"));
+        assert!(!next.headers().contains("Cookie"));
+    }
+
+    #[test]
+    fn test_follow_redirects() {
+        let mut requested = Vec::new();
+        let response = follow_redirects(get("http://a.test/1"), DEFAULT_MAX_REDIRECTS, |r| {
+            let url = r.url().unwrap().to_string();
+            requested.push(url.clone());
+            Ok(match url.as_str() {
+                "http://a.test/1" => redirect(301, "/2"),
+                "http://a.test/2" => redirect(308, "http://b.test/3"),
+                "http://b.test/3" => redirect(303, "4"),
+                _ => response("HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\ndone"),
+            })
+        })
+        .unwrap();
+
+        assert_eq!(
+            requested,
+            [
+                "http://a.test/1",
+                "http://a.test/2",
+                "http://b.test/3",
+                "http://b.test/4"
+            ]
+        );
+        assert_eq!(response.status_code(), 200);
+        assert_eq!(response.body(), b"done");
+        let chain: Vec<String> = response
+            .redirect_chain()
+            .iter()
+            .map(|u| u.to_string())
+            .collect();
+        assert_eq!(
+            chain,
+            ["http://a.test/1", "http://a.test/2", "http://b.test/3"]
+        );
+        assert_eq!(
+            response.url().map(|u| u.to_string()).as_deref(),
+            Some("http://b.test/4")
+        );
+    }
+
+    #[test]
+    fn test_max_redirects() {
+        // 自分自身へのリダイレクトは上限に達して止まる
+        let mut count = 0;
+        let result = follow_redirects(get("http://a.test/loop"), 5, |_| {
+            count += 1;
+            Ok(redirect(302, "/loop"))
+        });
+        assert_eq!(result.err(), Some(Error::Http(HttpError::TooManyRedirects)));
+        assert_eq!(count, 6);
+
+        // 上限ちょうどなら成功する
+        let mut hops = vec![
+            redirect(302, "/b"),
+            redirect(302, "/c"),
+            response("HTTP/1.1 200 OK\r\n\r\n"),
+        ]
+        .into_iter();
+        let response = follow_redirects(get("http://a.test/a"), 2, |_| Ok(hops.next().unwrap()));
+        assert_eq!(response.unwrap().redirect_chain().len(), 2);
+
+        // 0 ならリダイレクトしない
+        let result = follow_redirects(get("http://a.test/"), 0, |_| Ok(redirect(302, "/x")));
+        assert_eq!(result.err(), Some(Error::Http(HttpError::TooManyRedirects)));
+    }
+
+    #[test]
+    fn test_send_error_is_returned() {
+        let result = follow_redirects(get("http://a.test/"), DEFAULT_MAX_REDIRECTS, |_| {
+            Err(Error::Network(NetworkError::Connect("refused".to_string())))
+        });
+        assert_eq!(
+            result.err(),
+            Some(Error::Network(NetworkError::Connect("refused".to_string())))
+        );
+    }
+}
@@ -0,0 +1,509 @@
+use super::chunked;
+use super::is_token_char;
+use super::HeaderMap;
+use crate::error::Error;
+use crate::error::HttpError;
+use crate::url::SearchParams;
+use crate::url::Url;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+/// An HTTP/1.1 request. Build one with `HttpRequest::builder` and write
+/// `to_bytes()` to the connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    method: String,
+    target: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    url: Option<Url>,
+}
+
+impl HttpRequest {
+    /// Starts a request for `target`, which is usually an origin-form path
+    /// such as `/index.html?q=1`.
+    pub fn builder(method: &str, target: &str) -> HttpRequestBuilder {
+        HttpRequestBuilder {
+            method: method.to_string(),
+            target: target.to_string(),
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+            url: None,
+        }
+    }
+
+    /// Starts a request for `url`, with the target in origin-form and the
+    /// Host header taken from `url`. The fragment is never sent.
+    pub fn builder_for_url(method: &str, url: &Url) -> HttpRequestBuilder {
+        let mut builder =
+            Self::builder(method, &origin_form(url)).header("Host", &host_header(url));
+        builder.url = Some(url.clone());
+        builder
+    }
+
+    /// Starts a builder with everything in this request, to send a changed
+    /// copy of it.
+    pub fn to_builder(&self) -> HttpRequestBuilder {
+        HttpRequestBuilder {
+            method: self.method.clone(),
+            target: self.target.clone(),
+            headers: self.headers.clone(),
+            body: self.body.clone(),
+            url: self.url.clone(),
+        }
+    }
+
+    pub fn method(&self) -> String {
+        self.method.clone()
+    }
+
+    pub fn target(&self) -> String {
+        self.target.clone()
+    }
+
+    pub fn headers(&self) -> HeaderMap {
+        self.headers.clone()
+    }
+
+    pub fn body(&self) -> Vec<u8> {
+        self.body.clone()
+    }
+
+    /// Returns the URL the request was built for with `builder_for_url`.
+    pub fn url(&self) -> Option<Url> {
+        self.url.clone()
+    }
+
+    /// Returns the host and port to connect to, taken from the URL or else
+    /// from the Host header. IPv6 addresses are returned without brackets.
+    pub fn authority(&self) -> Option<(String, u16)> {
+        if let Some(url) = &self.url {
+            let host = url.host();
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            return Some((host.to_string(), url.port()?));
+        }
+
+        let host = self.headers.get("Host")?;
+        // "[::1]:8080" のように IPv6 アドレスにもコロンが含まれる
+        let (name, port) = match host.rfind(':') {
+            Some(i) if !host[i..].contains(']') => (&host[..i], Some(&host[i + 1..])),
+            _ => (host.as_str(), None),
+        };
+        let port = match port {
+            Some(port) => port.parse().ok()?,
+            None => 80,
+        };
+        let name = name.trim_start_matches('[').trim_end_matches(']');
+        if name.is_empty() {
+            return None;
+        }
+        Some((name.to_string(), port))
+    }
+
+    /// Returns true if the body is sent with the chunked transfer coding.
+    fn is_chunked(&self) -> bool {
+        self.headers
+            .get_list("Transfer-Encoding")
+            .last()
+            .is_some_and(|coding| coding.eq_ignore_ascii_case("chunked"))
+    }
+
+    /// Serializes the request in the HTTP/1.1 wire format. If the request has
+    /// `Transfer-Encoding: chunked`, the body is written as chunks.
+    ///
+    /// https://www.rfc-editor.org/rfc/rfc9112#section-2.1
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(format!("{} {} HTTP/1.1\r\n", self.method, self.target).as_bytes());
+        for header in &self.headers {
+            bytes.extend_from_slice(header.name().as_bytes());
+            bytes.extend_from_slice(b": ");
+            bytes.extend_from_slice(header.value().as_bytes());
+            bytes.extend_from_slice(b"\r\n");
+        }
+        bytes.extend_from_slice(b"\r\n");
+        if self.is_chunked() {
+            bytes.extend_from_slice(&chunked::encode(&self.body));
+        } else {
+            bytes.extend_from_slice(&self.body);
+        }
+        bytes
+    }
+}
+
+/// Builds an `HttpRequest`, checking its parts in `build`.
+#[derive(Debug, Clone)]
+pub struct HttpRequestBuilder {
+    method: String,
+    target: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    url: Option<Url>,
+}
+
+impl HttpRequestBuilder {
+    /// Appends a header. Use it more than once to send repeated fields.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Removes all fields named `name`.
+    pub fn remove_header(mut self, name: &str) -> Self {
+        self.headers.remove(name);
+        self
+    }
+
+    pub fn body(mut self, body: Vec<u8>) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Sets the body to `params` encoded as `application/x-www-form-urlencoded`,
+    /// the way an HTML form is submitted with POST.
+    pub fn form(self, params: &SearchParams) -> Self {
+        self.header("Content-Type", "application/x-www-form-urlencoded")
+            .body(params.to_string().into_bytes())
+    }
+
+    /// Sends the body with the chunked transfer coding instead of
+    /// Content-Length.
+    pub fn chunked(self) -> Self {
+        self.header("Transfer-Encoding", "chunked")
+    }
+
+    /// Checks that the method, target and headers can be written without
+    /// breaking the message framing, and adds Content-Length if a body is
+    /// given without one.
+    pub fn build(mut self) -> Result<HttpRequest, Error> {
+        if self.method.is_empty() || !self.method.bytes().all(is_token_char) {
+            return Err(HttpError::InvalidMethod(self.method).into());
+        }
+        // 空白や制御文字があると要求行が壊れる
+        if self.target.is_empty() || !self.target.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(HttpError::InvalidRequestTarget(self.target).into());
+        }
+        for header in &self.headers {
+            let name = header.name();
+            if name.is_empty() || !name.bytes().all(is_token_char) {
+                return Err(HttpError::InvalidHeaderName(name).into());
+            }
+            let value = header.value();
+            if value.bytes().any(|b| b.is_ascii_control() && b != b'\t') {
+                return Err(HttpError::InvalidHeaderValue(name).into());
+            }
+        }
+
+        // Content-Length と Transfer-Encoding を両方送ってはいけない
+        let chunked = self.headers.contains("Transfer-Encoding");
+        let content_length = self.headers.content_length().map_err(|_| {
+            HttpError::InvalidContentLength(self.headers.get("Content-Length").unwrap_or_default())
+        })?;
+        match content_length {
+            Some(length) if chunked || length != self.body.len() => {
+                return Err(HttpError::InvalidContentLength(length.to_string()).into())
+            }
+            Some(_) => {}
+            // POST や PUT では本文が空でも長さを送る
+            None if !chunked
+                && (!self.body.is_empty()
+                    || ["POST", "PUT", "PATCH"].contains(&self.method.as_str())) =>
+            {
+                self.headers
+                    .append("Content-Length", &self.body.len().to_string());
+            }
+            None => {}
+        }
+
+        Ok(HttpRequest {
+            method: self.method,
+            target: self.target,
+            headers: self.headers,
+            body: self.body,
+            url: self.url,
+        })
+    }
+}
+
+/// Returns the origin-form request target for `url`: the path and query.
+///
+/// https://www.rfc-editor.org/rfc/rfc9112#section-3.2.1
+pub fn origin_form(url: &Url) -> String {
+    let mut target = url.path();
+    if target.is_empty() {
+        target.push('/');
+    }
+    if let Some(query) = url.query() {
+        target.push('?');
+        target.push_str(&query);
+    }
+    target
+}
+
+/// Returns the Host header value for `url`, which includes the port only if
+/// it is not the scheme's default.
+fn host_header(url: &Url) -> String {
+    let mut host = url.host();
+    if let Some(port) = url.explicit_port() {
+        host.push_str(&format!(":{}", port));
+    }
+    host
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_bytes() {
+        let request = HttpRequest::builder("GET", "/index.html")
+            .header("Host", "example.com")
+            .header("Accept", "text/html")
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.to_bytes(),
+            b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nAccept: text/html\r\n\r\n"
+        );
+
+        let request = HttpRequest::builder("POST", "/form")
+            .header("Host", "example.com")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(b"a=1&b=2".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.to_bytes(),
+            b"POST /form HTTP/1.1\r\nHost: example.com\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: 7\r\n\r\na=1&b=2"
+        );
+
+        let request = HttpRequest::builder("OPTIONS", "*").build().unwrap();
+        assert_eq!(request.to_bytes(), b"OPTIONS * HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn test_repeated_headers_and_explicit_length() {
+        let request = HttpRequest::builder("PUT", "/")
+            .header("Cookie", "a=1")
+            .header("Cookie", "b=2")
+            .header("content-length", "3")
+            .body(b"abc".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(request.headers().get_all("Cookie"), ["a=1", "b=2"]);
+        assert_eq!(request.headers().get_all("Content-Length"), ["3"]);
+        assert_eq!(request.method(), "PUT");
+        assert_eq!(request.target(), "/");
+        assert_eq!(request.body(), b"abc");
+    }
+
+    #[test]
+    fn test_builder_for_url() {
+        // (url, target, host)
+        let cases: &[(&str, &str, &str)] = &[
+            ("http://example.com", "/", "example.com"),
+            ("http://example.com:80/a", "/a", "example.com"),
+            (
+                "http://example.com:8000/a/b?q=1#f",
+                "/a/b?q=1",
+                "example.com:8000",
+            ),
+            ("http://[::1]:8080/?", "/?", "[::1]:8080"),
+            ("https://example.com:443/x", "/x", "example.com"),
+            (
+                "http://host.test:8000/test.html",
+                "/test.html",
+                "host.test:8000",
+            ),
+        ];
+        for (url, target, host) in cases {
+            let url = Url::new(url.to_string()).unwrap();
+            let request = HttpRequest::builder_for_url("GET", &url).build().unwrap();
+            assert_eq!(request.target(), *target, "{}", url);
+            assert_eq!(
+                request.headers().get("Host").as_deref(),
+                Some(*host),
+                "{}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let cases: &[(HttpRequestBuilder, Error)] = &[
+            (
+                HttpRequest::builder("", "/"),
+                Error::Http(HttpError::InvalidMethod("".to_string())),
+            ),
+            (
+                HttpRequest::builder("GET /", "/"),
+                Error::Http(HttpError::InvalidMethod("GET /".to_string())),
+            ),
+            (
+                HttpRequest::builder("GET", ""),
+                Error::Http(HttpError::InvalidRequestTarget("".to_string())),
+            ),
+            (
+                HttpRequest::builder("GET", "/a b"),
+                Error::Http(HttpError::InvalidRequestTarget("/a b".to_string())),
+            ),
+            (
+                HttpRequest::builder("GET", "/\r\nX: y"),
+                Error::Http(HttpError::InvalidRequestTarget("/\r\nX: y".to_string())),
+            ),
+            (
+                HttpRequest::builder("GET", "/").header("Bad Name", "x"),
+                Error::Http(HttpError::InvalidHeaderName("Bad Name".to_string())),
+            ),
+            (
+                HttpRequest::builder("GET", "/").header("", "x"),
+                Error::Http(HttpError::InvalidHeaderName("".to_string())),
+            ),
+            (
+                HttpRequest::builder("GET", "/").header("X", "a\r\nInjected: 1"),
+                Error::Http(HttpError::InvalidHeaderValue("X".to_string())),
+            ),
+        ];
+        for (builder, expected) in cases {
+            assert_eq!(
+                builder.clone().build().as_ref(),
+                Err(expected),
+                "{:?}",
+                builder
+            );
+        }
+    }
+
+    #[test]
+    fn test_methods_and_bodies() {
+        // (request, wire format)
+        let cases: &[(HttpRequestBuilder, &[u8])] = &[
+            (
+                HttpRequest::builder("HEAD", "/"),
+                b"HEAD / HTTP/1.1\r\n\r\n",
+            ),
+            (
+                HttpRequest::builder("DELETE", "/items/1"),
+                b"DELETE /items/1 HTTP/1.1\r\n\r\n",
+            ),
+            (
+                HttpRequest::builder("POST", "/empty"),
+                b"POST /empty HTTP/1.1\r\nContent-Length: 0\r\n\r\n",
+            ),
+            (
+                HttpRequest::builder("PUT", "/items/1").body(b"{}".to_vec()),
+                b"PUT /items/1 HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}",
+            ),
+            (
+                HttpRequest::builder("POST", "/upload")
+                    .chunked()
+                    .body(b"hello".to_vec()),
+                b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+            ),
+            (
+                HttpRequest::builder("POST", "/upload").chunked(),
+                b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n",
+            ),
+        ];
+        for (builder, expected) in cases {
+            let request = builder.clone().build().unwrap();
+            assert_eq!(
+                request.to_bytes(),
+                *expected,
+                "{:?}",
+                String::from_utf8_lossy(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_form() {
+        let mut params = SearchParams::new();
+        params.append("name", "山田 太郎");
+        params.append("q", "a&b");
+        let request = HttpRequest::builder("POST", "/submit")
+            .form(&params)
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.headers().get("Content-Type").as_deref(),
+            Some("application/x-www-form-urlencoded")
+        );
+        assert_eq!(
+            request.body(),
+            b"name=%E5%B1%B1%E7%94%B0+%E5%A4%AA%E9%83%8E&q=a%26b"
+        );
+        assert_eq!(request.headers().content_length(), Ok(Some(50)));
+    }
+
+    #[test]
+    fn test_framing_errors() {
+        let cases: &[(HttpRequestBuilder, Error)] = &[
+            (
+                HttpRequest::builder("POST", "/")
+                    .header("Content-Length", "3")
+                    .body(b"abcd".to_vec()),
+                Error::Http(HttpError::InvalidContentLength("3".to_string())),
+            ),
+            (
+                HttpRequest::builder("POST", "/")
+                    .header("Content-Length", "4")
+                    .chunked()
+                    .body(b"abcd".to_vec()),
+                Error::Http(HttpError::InvalidContentLength("4".to_string())),
+            ),
+            (
+                HttpRequest::builder("POST", "/").header("Content-Length", "x"),
+                Error::Http(HttpError::InvalidContentLength("x".to_string())),
+            ),
+        ];
+        for (builder, expected) in cases {
+            assert_eq!(
+                builder.clone().build().as_ref(),
+                Err(expected),
+                "{:?}",
+                builder
+            );
+        }
+    }
+
+    #[test]
+    fn test_authority() {
+        let url = Url::new("http://[::1]:8080/a".to_string()).unwrap();
+        let request = HttpRequest::builder_for_url("GET", &url).build().unwrap();
+        assert_eq!(request.url(), Some(url));
+        assert_eq!(request.authority(), Some(("::1".to_string(), 8080)));
+
+        let url = Url::new("http://example.com/a".to_string()).unwrap();
+        let request = HttpRequest::builder_for_url("GET", &url).build().unwrap();
+        assert_eq!(request.authority(), Some(("example.com".to_string(), 80)));
+
+        // (Host header, authority)
+        let cases: &[(&str, Option<(&str, u16)>)] = &[
+            ("example.com", Some(("example.com", 80))),
+            ("example.com:8000", Some(("example.com", 8000))),
+            ("[::1]", Some(("::1", 80))),
+            ("[::1]:81", Some(("::1", 81))),
+            ("example.com:x", None),
+            ("example.com:99999", None),
+            ("", None),
+        ];
+        for (host, expected) in cases {
+            let request = HttpRequest::builder("GET", "/")
+                .header("Host", host)
+                .build()
+                .unwrap();
+            assert_eq!(
+                request.authority(),
+                expected.map(|(h, p)| (h.to_string(), p)),
+                "{:?}",
+                host
+            );
+        }
+        let request = HttpRequest::builder("GET", "/").build().unwrap();
+        assert_eq!(request.authority(), None);
+    }
+}
@@ -0,0 +1,269 @@
+//! Status codes and their classes.
+//!
+//! https://www.rfc-editor.org/rfc/rfc9110#section-15
+
+use crate::error::Error;
+use crate::error::ParseErrorKind;
+use alloc::string::ToString;
+use core::fmt;
+use core::str::FromStr;
+
+/// An HTTP status code: a three-digit number from 100 to 999. Codes that
+/// are not registered are allowed, since a client must treat them like the
+/// x00 code of their class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StatusCode(u16);
+
+/// Defines the constants for the registered codes and their reason phrases.
+macro_rules! status_codes {
+    ($(($code:expr, $name:ident, $reason:expr),)+) => {
+        impl StatusCode {
+            $(
+                #[doc = concat!(stringify!($code), " ", $reason)]
+                pub const $name: StatusCode = StatusCode($code);
+            )+
+
+            /// Returns the reason phrase the registry gives the code, e.g.
+            /// "Not Found" for 404, or `None` if the code is not registered.
+            /// The phrase a server actually sent is `HttpResponse::reason`.
+            pub fn canonical_reason(&self) -> Option<&'static str> {
+                match self.0 {
+                    $($code => Some($reason),)+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+// https://www.iana.org/assignments/http-status-codes/http-status-codes.xhtml
+status_codes! {
+    (100, CONTINUE, "Continue"),
+    (101, SWITCHING_PROTOCOLS, "Switching Protocols"),
+    (102, PROCESSING, "Processing"),
+    (103, EARLY_HINTS, "Early Hints"),
+    (200, OK, "OK"),
+    (201, CREATED, "Created"),
+    (202, ACCEPTED, "Accepted"),
+    (203, NON_AUTHORITATIVE_INFORMATION, "Non-Authoritative Information"),
+    (204, NO_CONTENT, "No Content"),
+    (205, RESET_CONTENT, "Reset Content"),
+    (206, PARTIAL_CONTENT, "Partial Content"),
+    (207, MULTI_STATUS, "Multi-Status"),
+    (208, ALREADY_REPORTED, "Already Reported"),
+    (226, IM_USED, "IM Used"),
+    (300, MULTIPLE_CHOICES, "Multiple Choices"),
+    (301, MOVED_PERMANENTLY, "Moved Permanently"),
+    (302, FOUND, "Found"),
+    (303, SEE_OTHER, "See Other"),
+    (304, NOT_MODIFIED, "Not Modified"),
+    (305, USE_PROXY, "Use Proxy"),
+    (307, TEMPORARY_REDIRECT, "Temporary Redirect"),
+    (308, PERMANENT_REDIRECT, "Permanent Redirect"),
+    (400, BAD_REQUEST, "Bad Request"),
+    (401, UNAUTHORIZED, "Unauthorized"),
+    (402, PAYMENT_REQUIRED, "Payment Required"),
+    (403, FORBIDDEN, "Forbidden"),
+    (404, NOT_FOUND, "Not Found"),
+    (405, METHOD_NOT_ALLOWED, "Method Not Allowed"),
+    (406, NOT_ACCEPTABLE, "Not Acceptable"),
+    (407, PROXY_AUTHENTICATION_REQUIRED, "Proxy Authentication Required"),
+    (408, REQUEST_TIMEOUT, "Request Timeout"),
+    (409, CONFLICT, "Conflict"),
+    (410, GONE, "Gone"),
+    (411, LENGTH_REQUIRED, "Length Required"),
+    (412, PRECONDITION_FAILED, "Precondition Failed"),
+    (413, CONTENT_TOO_LARGE, "Content Too Large"),
+    (414, URI_TOO_LONG, "URI Too Long"),
+    (415, UNSUPPORTED_MEDIA_TYPE, "Unsupported Media Type"),
+    (416, RANGE_NOT_SATISFIABLE, "Range Not Satisfiable"),
+    (417, EXPECTATION_FAILED, "Expectation Failed"),
+    (421, MISDIRECTED_REQUEST, "Misdirected Request"),
+    (422, UNPROCESSABLE_CONTENT, "Unprocessable Content"),
+    (423, LOCKED, "Locked"),
+    (424, FAILED_DEPENDENCY, "Failed Dependency"),
+    (425, TOO_EARLY, "Too Early"),
+    (426, UPGRADE_REQUIRED, "Upgrade Required"),
+    (428, PRECONDITION_REQUIRED, "Precondition Required"),
+    (429, TOO_MANY_REQUESTS, "Too Many Requests"),
+    (431, REQUEST_HEADER_FIELDS_TOO_LARGE, "Request Header Fields Too Large"),
+    (451, UNAVAILABLE_FOR_LEGAL_REASONS, "Unavailable For Legal Reasons"),
+    (500, INTERNAL_SERVER_ERROR, "Internal Server Error"),
+    (501, NOT_IMPLEMENTED, "Not Implemented"),
+    (502, BAD_GATEWAY, "Bad Gateway"),
+    (503, SERVICE_UNAVAILABLE, "Service Unavailable"),
+    (504, GATEWAY_TIMEOUT, "Gateway Timeout"),
+    (505, HTTP_VERSION_NOT_SUPPORTED, "HTTP Version Not Supported"),
+    (506, VARIANT_ALSO_NEGOTIATES, "Variant Also Negotiates"),
+    (507, INSUFFICIENT_STORAGE, "Insufficient Storage"),
+    (508, LOOP_DETECTED, "Loop Detected"),
+    (511, NETWORK_AUTHENTICATION_REQUIRED, "Network Authentication Required"),
+}
+
+impl StatusCode {
+    /// Returns the status code `code`, or `InvalidStatusCode` if it is not
+    /// between 100 and 999.
+    pub fn from_u16(code: u16) -> Result<Self, Error> {
+        if !(100..1000).contains(&code) {
+            return Err(invalid(&code.to_string()));
+        }
+        Ok(Self(code))
+    }
+
+    pub fn as_u16(&self) -> u16 {
+        self.0
+    }
+
+    /// 1xx: the request was received and the final response is still to
+    /// come.
+    pub fn is_informational(&self) -> bool {
+        (100..200).contains(&self.0)
+    }
+
+    /// 2xx: the request succeeded.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.0)
+    }
+
+    /// 3xx: more has to be done to complete the request, which is usually a
+    /// redirect to follow. 304 Not Modified is in this class too.
+    pub fn is_redirect(&self) -> bool {
+        (300..400).contains(&self.0)
+    }
+
+    /// 4xx: the request was wrong.
+    pub fn is_client_error(&self) -> bool {
+        (400..500).contains(&self.0)
+    }
+
+    /// 5xx: the server failed to answer a valid request.
+    pub fn is_server_error(&self) -> bool {
+        (500..600).contains(&self.0)
+    }
+}
+
+/// Parses exactly three digits, as in a status line.
+impl FromStr for StatusCode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 3 || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid(s));
+        }
+        let code = s
+            .bytes()
+            .fold(0, |code, digit| code * 10 + u16::from(digit - b'0'));
+        Self::from_u16(code).map_err(|_| invalid(s))
+    }
+}
+
+impl TryFrom<u16> for StatusCode {
+    type Error = Error;
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        Self::from_u16(code)
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(status: StatusCode) -> u16 {
+        status.0
+    }
+}
+
+impl PartialEq<u16> for StatusCode {
+    fn eq(&self, other: &u16) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<StatusCode> for u16 {
+    fn eq(&self, other: &StatusCode) -> bool {
+        *self == other.0
+    }
+}
+
+/// Writes the three digits, as they appear in a status line.
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn invalid(code: &str) -> Error {
+    Error::parse(ParseErrorKind::InvalidStatusCode(code.to_string()), 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classes() {
+        // (code, informational, success, redirect, client error, server error)
+        let cases: &[(u16, [bool; 5])] = &[
+            (100, [true, false, false, false, false]),
+            (199, [true, false, false, false, false]),
+            (200, [false, true, false, false, false]),
+            (204, [false, true, false, false, false]),
+            (301, [false, false, true, false, false]),
+            (304, [false, false, true, false, false]),
+            (404, [false, false, false, true, false]),
+            (499, [false, false, false, true, false]),
+            (503, [false, false, false, false, true]),
+            (600, [false, false, false, false, false]),
+            (999, [false, false, false, false, false]),
+        ];
+        for (code, expected) in cases {
+            let status = StatusCode::from_u16(*code).unwrap();
+            let classes = [
+                status.is_informational(),
+                status.is_success(),
+                status.is_redirect(),
+                status.is_client_error(),
+                status.is_server_error(),
+            ];
+            assert_eq!(classes, *expected, "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        // (input, expected)
+        let cases: &[(&str, Result<u16, Error>)] = &[
+            ("200", Ok(200)),
+            ("100", Ok(100)),
+            ("999", Ok(999)),
+            ("099", Err(invalid("099"))),
+            ("000", Err(invalid("000"))),
+            ("20", Err(invalid("20"))),
+            ("2000", Err(invalid("2000"))),
+            ("+20", Err(invalid("+20"))),
+            ("abc", Err(invalid("abc"))),
+            ("", Err(invalid(""))),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                input.parse::<StatusCode>().map(u16::from),
+                *expected,
+                "{:?}",
+                input
+            );
+        }
+
+        assert_eq!(StatusCode::from_u16(404), Ok(StatusCode::NOT_FOUND));
+        assert_eq!(StatusCode::try_from(1000), Err(invalid("1000")));
+        assert_eq!(StatusCode::from_u16(99), Err(invalid("99")));
+    }
+
+    #[test]
+    fn test_canonical_reason() {
+        assert_eq!(StatusCode::OK.canonical_reason(), Some("OK"));
+        assert_eq!(StatusCode::NOT_FOUND.canonical_reason(), Some("Not Found"));
+        assert_eq!(StatusCode::from_u16(418).unwrap().canonical_reason(), None);
+        assert_eq!(StatusCode::PERMANENT_REDIRECT.as_u16(), 308);
+        assert_eq!(StatusCode::SEE_OTHER.to_string(), "303");
+        assert_eq!(StatusCode::OK, 200);
+        assert_eq!(200, StatusCode::OK);
+    }
+}
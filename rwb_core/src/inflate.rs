@@ -0,0 +1,792 @@
+//! Decompression of DEFLATE data and the zlib and gzip formats that wrap it,
+//! as used by `Content-Encoding: deflate` and `Content-Encoding: gzip`.
+//!
+//! https://www.rfc-editor.org/rfc/rfc1951
+//! https://www.rfc-editor.org/rfc/rfc1950
+//! https://www.rfc-editor.org/rfc/rfc1952
+
+use crate::error::Error;
+use crate::error::ParseErrorKind;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+const MAX_BITS: usize = 15;
+const MAX_LITERAL_CODES: usize = 288;
+const MAX_DISTANCE_CODES: usize = 30;
+
+/// Base lengths for length codes 257..=285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distances for distance codes 0..=29.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order code length code lengths are sent in a dynamic block.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const GZIP_FTEXT: u8 = 0x01;
+const GZIP_FHCRC: u8 = 0x02;
+const GZIP_FEXTRA: u8 = 0x04;
+const GZIP_FNAME: u8 = 0x08;
+const GZIP_FCOMMENT: u8 = 0x10;
+
+/// Decompresses raw DEFLATE data with no header or checksum.
+///
+/// Like the other functions here, errors are reported at the offset in
+/// `data` where the problem was found, and output longer than `max_size`
+/// bytes fails with `BodyTooLarge`, so that a small body cannot expand to
+/// exhaust memory.
+pub fn inflate(data: &[u8], max_size: usize) -> Result<Vec<u8>, Error> {
+    inflate_with_length(data, max_size).map(|(output, _)| output)
+}
+
+/// Decompresses zlib data: a two-byte header, DEFLATE data and an Adler-32
+/// checksum of the output.
+pub fn zlib_decompress(data: &[u8], max_size: usize) -> Result<Vec<u8>, Error> {
+    if data.len() < 2 {
+        return Err(invalid_at("zlib header is truncated", data.len()));
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 {
+        return Err(invalid_at(
+            &format!("unsupported zlib compression method: {:#04x}", cmf),
+            0,
+        ));
+    }
+    if (u16::from(cmf) << 8 | u16::from(flg)) % 31 != 0 {
+        return Err(invalid_at("zlib header check bits are wrong", 1));
+    }
+    // プリセット辞書は HTTP では使われない
+    if flg & 0x20 != 0 {
+        return Err(invalid_at("zlib preset dictionaries are not supported", 1));
+    }
+
+    let (output, consumed) =
+        inflate_with_length(&data[2..], max_size).map_err(|e| e.offset_by(2))?;
+    let trailer = data
+        .get(2 + consumed..2 + consumed + 4)
+        .ok_or_else(|| invalid_at("zlib checksum is truncated", data.len()))?;
+    let expected = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    if adler32(&output) != expected {
+        return Err(invalid_at("zlib checksum does not match", 2 + consumed));
+    }
+    Ok(output)
+}
+
+/// Decompresses gzip data. Concatenated members are decompressed one after
+/// another; bytes after the last member that do not start another member
+/// are ignored.
+pub fn gzip_decompress(data: &[u8], max_size: usize) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::new();
+    let mut start = 0;
+    loop {
+        start +=
+            gzip_member(&data[start..], &mut output, max_size).map_err(|e| e.offset_by(start))?;
+        if !data[start..].starts_with(&[0x1f, 0x8b]) {
+            return Ok(output);
+        }
+    }
+}
+
+/// Decompresses one gzip member onto `output` and returns the number of
+/// bytes it took up. `max_size` limits the whole output, not the member.
+fn gzip_member(data: &[u8], output: &mut Vec<u8>, max_size: usize) -> Result<usize, Error> {
+    let truncated = || invalid_at("gzip header is truncated", data.len());
+    if data.len() < 10 {
+        return Err(truncated());
+    }
+    if data[0] != 0x1f || data[1] != 0x8b {
+        return Err(invalid_at("not gzip data", 0));
+    }
+    if data[2] != 8 {
+        return Err(invalid_at(
+            &format!("unsupported gzip compression method: {}", data[2]),
+            2,
+        ));
+    }
+    let flags = data[3];
+    if flags & !(GZIP_FTEXT | GZIP_FHCRC | GZIP_FEXTRA | GZIP_FNAME | GZIP_FCOMMENT) != 0 {
+        return Err(invalid_at("gzip header has reserved flags set", 3));
+    }
+
+    // MTIME, XFL, OS は使わない
+    let mut pos = 10;
+    if flags & GZIP_FEXTRA != 0 {
+        let xlen = data.get(pos..pos + 2).ok_or_else(truncated)?;
+        pos += 2 + usize::from(u16::from_le_bytes([xlen[0], xlen[1]]));
+    }
+    for flag in [GZIP_FNAME, GZIP_FCOMMENT] {
+        if flags & flag != 0 {
+            let len = data
+                .get(pos..)
+                .and_then(|rest| rest.iter().position(|b| *b == 0))
+                .ok_or_else(truncated)?;
+            pos += len + 1;
+        }
+    }
+    if flags & GZIP_FHCRC != 0 {
+        let crc = data.get(pos..pos + 2).ok_or_else(truncated)?;
+        if crc32(&data[..pos]) as u16 != u16::from_le_bytes([crc[0], crc[1]]) {
+            return Err(invalid_at("gzip header checksum does not match", pos));
+        }
+        pos += 2;
+    }
+    if pos > data.len() {
+        return Err(truncated());
+    }
+
+    let (member, consumed) =
+        inflate_with_length(&data[pos..], max_size - output.len()).map_err(|e| e.offset_by(pos))?;
+    pos += consumed;
+    let trailer = data
+        .get(pos..pos + 8)
+        .ok_or_else(|| invalid_at("gzip trailer is truncated", data.len()))?;
+    let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+    if crc32(&member) != crc {
+        return Err(invalid_at("gzip checksum does not match", pos));
+    }
+    // ISIZE は元の長さを 2^32 で割った余り
+    if member.len() as u32 != size {
+        return Err(invalid_at("gzip length does not match", pos + 4));
+    }
+    output.extend_from_slice(&member);
+    Ok(pos + 8)
+}
+
+/// Decompresses raw DEFLATE data and returns the output with the number of
+/// input bytes used, counting a partly used last byte.
+fn inflate_with_length(data: &[u8], max_size: usize) -> Result<(Vec<u8>, usize), Error> {
+    let mut input = BitReader::new(data);
+    let mut output = Vec::new();
+    // ブロックの中の誤りは、そのとき読んでいたバイトの位置で報告する
+    inflate_blocks(&mut input, &mut output, max_size).map_err(|e| e.offset_by(input.pos))?;
+    Ok((output, input.consumed()))
+}
+
+fn inflate_blocks(
+    input: &mut BitReader,
+    output: &mut Vec<u8>,
+    max_size: usize,
+) -> Result<(), Error> {
+    loop {
+        let last = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => stored_block(input, output, max_size)?,
+            1 => {
+                let (literals, distances) = fixed_codes()?;
+                compressed_block(input, output, max_size, &literals, &distances)?
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(input)?;
+                compressed_block(input, output, max_size, &literals, &distances)?
+            }
+            _ => return Err(invalid("invalid deflate block type")),
+        }
+        if last {
+            return Ok(());
+        }
+    }
+}
+
+fn stored_block(input: &mut BitReader, output: &mut Vec<u8>, max_size: usize) -> Result<(), Error> {
+    input.align();
+    let len = input.bits(16)? as u16;
+    let nlen = input.bits(16)? as u16;
+    if len != !nlen {
+        return Err(invalid("stored block length does not match its complement"));
+    }
+    check_size(output, usize::from(len), max_size)?;
+    output.extend_from_slice(input.bytes(usize::from(len))?);
+    Ok(())
+}
+
+fn compressed_block(
+    input: &mut BitReader,
+    output: &mut Vec<u8>,
+    max_size: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), Error> {
+    loop {
+        let symbol = literals.decode(input)?;
+        match symbol {
+            0..=255 => {
+                check_size(output, 1, max_size)?;
+                output.push(symbol as u8)
+            }
+            256 => return Ok(()),
+            257..=285 => {
+                let i = usize::from(symbol - 257);
+                let length = usize::from(LENGTH_BASE[i]) + input.bits(LENGTH_EXTRA[i])? as usize;
+
+                let i = usize::from(distances.decode(input)?);
+                if i >= MAX_DISTANCE_CODES {
+                    return Err(invalid("invalid distance code"));
+                }
+                let distance =
+                    usize::from(DISTANCE_BASE[i]) + input.bits(DISTANCE_EXTRA[i])? as usize;
+                if distance > output.len() {
+                    return Err(invalid("distance is too far back"));
+                }
+
+                check_size(output, length, max_size)?;
+                // 重なっていることがあるので 1 バイトずつコピーする
+                let start = output.len() - distance;
+                for j in 0..length {
+                    output.push(output[start + j]);
+                }
+            }
+            _ => return Err(invalid("invalid literal/length code")),
+        }
+    }
+}
+
+/// The codes of a block with fixed Huffman codes.
+fn fixed_codes() -> Result<(Huffman, Huffman), Error> {
+    let mut lengths = [0u8; MAX_LITERAL_CODES];
+    for (symbol, length) in lengths.iter_mut().enumerate() {
+        *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    Ok((
+        Huffman::new(&lengths)?,
+        Huffman::new(&[5; MAX_DISTANCE_CODES])?,
+    ))
+}
+
+/// Reads the code lengths at the start of a block with dynamic Huffman
+/// codes.
+fn dynamic_codes(input: &mut BitReader) -> Result<(Huffman, Huffman), Error> {
+    let literal_count = input.bits(5)? as usize + 257;
+    let distance_count = input.bits(5)? as usize + 1;
+    let code_length_count = input.bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > MAX_DISTANCE_CODES {
+        return Err(invalid("too many codes in dynamic block"));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &i in CODE_LENGTH_ORDER.iter().take(code_length_count) {
+        code_lengths[i] = input.bits(3)? as u8;
+    }
+    let code_length_code = Huffman::new(&code_lengths)?;
+
+    // リテラルと距離の符号長は続けて送られ、繰り返しが境界をまたぐこともある
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_length_code.decode(input)?;
+        let (length, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                if i == 0 {
+                    return Err(invalid("repeat with no previous code length"));
+                }
+                (lengths[i - 1], 3 + input.bits(2)? as usize)
+            }
+            17 => (0, 3 + input.bits(3)? as usize),
+            _ => (0, 11 + input.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(invalid("code lengths overrun the code count"));
+        }
+        lengths[i..i + repeat].fill(length);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(invalid("dynamic block has no end-of-block code"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..literal_count])?,
+        Huffman::new(&lengths[literal_count..])?,
+    ))
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length
+/// and the symbols in code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    /// Builds the code from the code length of each symbol, 0 meaning the
+    /// symbol is not used. Incomplete codes are allowed, since a block may
+    /// use a single distance code.
+    fn new(lengths: &[u8]) -> Result<Self, Error> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &length in lengths {
+            counts[usize::from(length)] += 1;
+        }
+
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - i32::from(count);
+            if left < 0 {
+                return Err(invalid("over-subscribed Huffman code"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for length in 1..MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[usize::from(offsets[usize::from(length)])] = symbol as u16;
+                offsets[usize::from(length)] += 1;
+            }
+        }
+
+        Ok(Self { counts, symbols })
+    }
+
+    /// Reads one code bit by bit. Codes of each length are consecutive
+    /// numbers, so it is enough to compare against the first code of the
+    /// current length.
+    fn decode(&self, input: &mut BitReader) -> Result<u16, Error> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for &count in &self.counts[1..] {
+            code |= input.bits(1)? as i32;
+            let count = i32::from(count);
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+/// Reads bits least significant first, as DEFLATE packs them.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            bit: 0,
+        }
+    }
+
+    fn bits(&mut self, count: u8) -> Result<u32, Error> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self.data.get(self.pos).ok_or_else(truncated)?;
+            value |= u32::from((byte >> self.bit) & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    /// Skips to the next byte boundary.
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+
+    /// Reads whole bytes. The reader must be aligned.
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + count)
+            .ok_or_else(truncated)?;
+        self.pos += count;
+        Ok(bytes)
+    }
+
+    fn consumed(&self) -> usize {
+        self.pos + usize::from(self.bit != 0)
+    }
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65521;
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    // 5552 バイトまでなら u32 があふれない
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            k += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc = CRC32_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// An error in DEFLATE data, which `inflate_with_length` moves to where it
+/// was found.
+/// Fails if adding `additional` bytes would make `output` longer than
+/// `max_size`.
+fn check_size(output: &[u8], additional: usize, max_size: usize) -> Result<(), Error> {
+    if additional > max_size - output.len() {
+        return Err(Error::parse(ParseErrorKind::BodyTooLarge, 0));
+    }
+    Ok(())
+}
+
+fn invalid(message: &str) -> Error {
+    invalid_at(message, 0)
+}
+
+fn invalid_at(message: &str, offset: usize) -> Error {
+    Error::parse(
+        ParseErrorKind::InvalidCompressedData(message.to_string()),
+        offset,
+    )
+}
+
+fn truncated() -> Error {
+    invalid("compressed data is truncated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HTML: &[u8] = include_bytes!("inflate/fixture.html");
+
+    /// The same xorshift the random fixture was generated with.
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut x: u64 = 0x2545_f491_4f6c_dd1d;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_fixtures() {
+        type Decompress = fn(&[u8], usize) -> Result<Vec<u8>, Error>;
+        // (name, compressed, decompress)
+        let cases: &[(&str, &[u8], Decompress)] = &[
+            (
+                "dynamic",
+                include_bytes!("inflate/fixture.html.deflate"),
+                inflate,
+            ),
+            (
+                "fixed",
+                include_bytes!("inflate/fixture.html.fixed.deflate"),
+                inflate,
+            ),
+            (
+                "stored",
+                include_bytes!("inflate/fixture.html.stored.deflate"),
+                inflate,
+            ),
+            (
+                "zlib",
+                include_bytes!("inflate/fixture.html.zlib"),
+                zlib_decompress,
+            ),
+            (
+                "gzip",
+                include_bytes!("inflate/fixture.html.gz"),
+                gzip_decompress,
+            ),
+        ];
+        for (name, compressed, decompress) in cases {
+            assert_eq!(
+                decompress(compressed, usize::MAX).as_deref(),
+                Ok(HTML),
+                "{}",
+                name
+            );
+        }
+
+        assert_eq!(
+            gzip_decompress(include_bytes!("inflate/random.bin.gz"), usize::MAX),
+            Ok(random_bytes(4096))
+        );
+    }
+
+    #[test]
+    fn test_inflate_small() {
+        // (compressed, expected)
+        let cases: &[(&[u8], &[u8])] = &[
+            // 空の固定ブロック
+            (&[0x03, 0x00], b""),
+            // 空の非圧縮ブロック
+            (&[0x01, 0x00, 0x00, 0xff, 0xff], b""),
+            (&[0x01, 0x02, 0x00, 0xfd, 0xff, b'h', b'i'], b"hi"),
+            // 非圧縮ブロックが二つ続く
+            (
+                &[
+                    0x00, 0x01, 0x00, 0xfe, 0xff, b'a', 0x01, 0x01, 0x00, 0xfe, 0xff, b'b',
+                ],
+                b"ab",
+            ),
+            // zlib.compress(b"aaaaaaaaaa", wbits=-15): 距離 1 の重なったコピー
+            (&[0x4b, 0x4c, 0x84, 0x01, 0x00], b"aaaaaaaaaa"),
+        ];
+        for (compressed, expected) in cases {
+            assert_eq!(inflate(compressed, usize::MAX).as_deref(), Ok(*expected));
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut bad_zlib_checksum = include_bytes!("inflate/fixture.html.zlib").to_vec();
+        let last = bad_zlib_checksum.len() - 1;
+        bad_zlib_checksum[last] ^= 1;
+        let mut bad_gzip_crc = include_bytes!("inflate/fixture.html.gz").to_vec();
+        let crc = bad_gzip_crc.len() - 8;
+        bad_gzip_crc[crc] ^= 1;
+        let mut bad_gzip_size = include_bytes!("inflate/fixture.html.gz").to_vec();
+        let size = bad_gzip_size.len() - 4;
+        bad_gzip_size[size] ^= 1;
+        let gzip = include_bytes!("inflate/fixture.html.gz");
+        let truncated_gzip = &gzip[..gzip.len() - 1];
+        let deflate = include_bytes!("inflate/fixture.html.deflate");
+        let truncated_deflate = &deflate[..deflate.len() / 2];
+
+        type Decompress = fn(&[u8], usize) -> Result<Vec<u8>, Error>;
+        // (name, input, decompress, offset)
+        let cases: &[(&str, &[u8], Decompress, usize)] = &[
+            ("empty", b"", inflate, 0),
+            ("reserved block type", &[0x07], inflate, 0),
+            (
+                "bad stored length",
+                &[0x01, 0x02, 0x00, 0x00, 0x00],
+                inflate,
+                5,
+            ),
+            (
+                "short stored block",
+                &[0x01, 0x05, 0x00, 0xfa, 0xff, b'a'],
+                inflate,
+                5,
+            ),
+            // 固定ブロックで最初に距離 1 のコピー
+            ("distance too far", &[0x03, 0x02, 0x00], inflate, 1),
+            (
+                "truncated deflate",
+                truncated_deflate,
+                inflate,
+                truncated_deflate.len(),
+            ),
+            ("zlib empty", b"", zlib_decompress, 0),
+            ("zlib method", &[0x79, 0x9c], zlib_decompress, 0),
+            ("zlib check bits", &[0x78, 0x9d], zlib_decompress, 1),
+            ("zlib dictionary", &[0x78, 0xbb], zlib_decompress, 1),
+            (
+                "zlib checksum",
+                &bad_zlib_checksum,
+                zlib_decompress,
+                last - 3,
+            ),
+            ("gzip magic", b"not gzip data", gzip_decompress, 0),
+            ("gzip crc", &bad_gzip_crc, gzip_decompress, crc),
+            ("gzip size", &bad_gzip_size, gzip_decompress, size),
+            (
+                "gzip truncated",
+                truncated_gzip,
+                gzip_decompress,
+                truncated_gzip.len(),
+            ),
+            (
+                "gzip reserved flags",
+                &[0x1f, 0x8b, 0x08, 0x20, 0, 0, 0, 0, 0, 0xff],
+                gzip_decompress,
+                3,
+            ),
+        ];
+        for (name, input, decompress, offset) in cases {
+            match decompress(input, usize::MAX) {
+                Err(Error::Parse(e)) => {
+                    assert!(
+                        matches!(e.kind(), ParseErrorKind::InvalidCompressedData(_)),
+                        "{}",
+                        name
+                    );
+                    assert_eq!(e.offset(), *offset, "{}", name);
+                }
+                result => panic!("{}: {:?}", name, result),
+            }
+        }
+
+        // 2 つ目のメンバの誤りはデータ全体での位置で報告する
+        let mut data = gzip.to_vec();
+        data.extend_from_slice(&bad_gzip_crc);
+        assert_eq!(
+            gzip_decompress(&data, usize::MAX)
+                .err()
+                .map(|e| e.to_string()),
+            Some(format!(
+                "parse error: gzip checksum does not match at byte {}",
+                gzip.len() + crc
+            ))
+        );
+    }
+
+    #[test]
+    fn test_gzip_members() {
+        let gzip = include_bytes!("inflate/fixture.html.gz");
+        let mut data = gzip.to_vec();
+        data.extend_from_slice(gzip);
+        let mut expected = HTML.to_vec();
+        expected.extend_from_slice(HTML);
+        assert_eq!(gzip_decompress(&data, usize::MAX), Ok(expected));
+
+        // 後ろのゴミは無視する
+        let mut data = gzip.to_vec();
+        data.extend_from_slice(b"\0\0\0\0");
+        assert_eq!(gzip_decompress(&data, usize::MAX).as_deref(), Ok(HTML));
+    }
+
+    #[test]
+    fn test_gzip_header_fields() {
+        // FEXTRA, FNAME, FCOMMENT, FHCRC をすべて持つヘッダ
+        let deflate = include_bytes!("inflate/fixture.html.deflate");
+        let mut data = vec![
+            0x1f,
+            0x8b,
+            0x08,
+            GZIP_FHCRC | GZIP_FEXTRA | GZIP_FNAME | GZIP_FCOMMENT,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0xff,
+        ];
+        data.extend_from_slice(&[3, 0, b'a', b'b', b'c']);
+        data.extend_from_slice(b"fixture.html\0");
+        data.extend_from_slice(b"a comment\0");
+        let header_crc = crc32(&data) as u16;
+        data.extend_from_slice(&header_crc.to_le_bytes());
+        data.extend_from_slice(deflate);
+        data.extend_from_slice(&crc32(HTML).to_le_bytes());
+        data.extend_from_slice(&(HTML.len() as u32).to_le_bytes());
+        assert_eq!(gzip_decompress(&data, usize::MAX).as_deref(), Ok(HTML));
+
+        let fhcrc = 10 + 5 + 13 + 10;
+        data[fhcrc] ^= 1;
+        assert_eq!(
+            gzip_decompress(&data, usize::MAX),
+            Err(invalid_at("gzip header checksum does not match", fhcrc))
+        );
+    }
+
+    #[test]
+    fn test_max_size() {
+        // 16 MiB のゼロが 16 KB ほどに縮んでいる
+        const ZEROS: &[u8] = include_bytes!("inflate/zeros.bin.gz");
+        const ZEROS_LEN: usize = 16 << 20;
+        let mut members = include_bytes!("inflate/fixture.html.gz").to_vec();
+        members.extend_from_slice(include_bytes!("inflate/fixture.html.gz"));
+        let stored: &[u8] = &[0x01, 0x02, 0x00, 0xfd, 0xff, b'h', b'i'];
+
+        assert_eq!(
+            gzip_decompress(ZEROS, ZEROS_LEN).map(|output| output.len()),
+            Ok(ZEROS_LEN)
+        );
+        assert_eq!(inflate(stored, 2).as_deref(), Ok(&b"hi"[..]));
+
+        // (name, result)
+        let cases: &[(&str, Result<Vec<u8>, Error>)] = &[
+            ("zeros", gzip_decompress(ZEROS, ZEROS_LEN - 1)),
+            ("zeros 1 MiB", gzip_decompress(ZEROS, 1 << 20)),
+            ("stored", inflate(stored, 1)),
+            // 上限はメンバごとではなく出力全体にかかる
+            ("members", gzip_decompress(&members, HTML.len() + 1)),
+        ];
+        for (name, result) in cases {
+            match result {
+                Err(Error::Parse(e)) => {
+                    assert_eq!(e.kind(), ParseErrorKind::BodyTooLarge, "{}", name)
+                }
+                result => panic!("{}: {:?}", name, result.as_ref().map(|o| o.len())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(&random_bytes(20_000)), {
+            // 素朴な計算と比べる
+            let (mut a, mut b) = (1u32, 0u32);
+            for byte in random_bytes(20_000) {
+                a = (a + u32::from(byte)) % 65521;
+                b = (b + a) % 65521;
+            }
+            (b << 16) | a
+        });
+    }
+}
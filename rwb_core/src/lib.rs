@@ -1,7 +1,11 @@
 #![no_std]
+#![feature(error_in_core)]
 
 extern crate alloc;
 
 pub mod error;
 pub mod http;
+pub mod inflate;
+pub mod percent_encoding;
+pub mod transport;
 pub mod url;
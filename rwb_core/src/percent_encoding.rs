@@ -0,0 +1,172 @@
+//! Percent-encoding as defined by the URL Standard.
+//!
+//! https://url.spec.whatwg.org/#percent-encoded-bytes
+//!
+//! An encode set lists the printable ASCII bytes that must be encoded on top
+//! of the C0 controls and non-ASCII bytes, which are always encoded.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const C0_CONTROL_SET: &[u8] = b"";
+pub const FRAGMENT_SET: &[u8] = b" \"<>`";
+pub const QUERY_SET: &[u8] = b" \"#<>";
+pub const SPECIAL_QUERY_SET: &[u8] = b" \"#<>'";
+pub const PATH_SET: &[u8] = b" \"#<>?`{}";
+pub const USERINFO_SET: &[u8] = b" \"#<>?`{}/:;=@[\\]^|";
+pub const COMPONENT_SET: &[u8] = b" \"#<>?`{}/:;=@[\\]^|$%&+,";
+pub const FORM_URLENCODED_SET: &[u8] = b" \"#<>?`{}/:;=@[\\]^|$%&+,!'()~";
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+fn should_encode(b: u8, set: &[u8]) -> bool {
+    !(0x20..0x7f).contains(&b) || set.contains(&b)
+}
+
+fn push_encoded_byte(b: u8, output: &mut String) {
+    output.push('%');
+    output.push(char::from(HEX_DIGITS[(b >> 4) as usize]));
+    output.push(char::from(HEX_DIGITS[(b & 0x0f) as usize]));
+}
+
+/// Appends the UTF-8 bytes of `c` to `output`, encoding those in `set`.
+pub fn percent_encode_char(c: char, set: &[u8], output: &mut String) {
+    let mut buf = [0u8; 4];
+    for b in c.encode_utf8(&mut buf).bytes() {
+        if should_encode(b, set) {
+            push_encoded_byte(b, output);
+        } else {
+            output.push(char::from(b));
+        }
+    }
+}
+
+pub fn percent_encode(input: &str, set: &[u8]) -> String {
+    let mut output = String::with_capacity(input.len());
+    for c in input.chars() {
+        percent_encode_char(c, set, &mut output);
+    }
+    output
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes every `%XX` triplet in `input`. A `%` that is not followed by two
+/// hex digits is kept as is.
+pub fn percent_decode(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len());
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == b'%' && i + 2 < input.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(input[i + 1]), hex_value(input[i + 2])) {
+                output.push(hi << 4 | lo);
+                i += 3;
+                continue;
+            }
+        }
+        output.push(input[i]);
+        i += 1;
+    }
+    output
+}
+
+/// Decodes `input` and interprets the result as UTF-8, replacing invalid
+/// sequences with U+FFFD.
+pub fn percent_decode_str(input: &str) -> String {
+    String::from_utf8_lossy(&percent_decode(input.as_bytes())).into_owned()
+}
+
+/// Rewrites percent-encoded triplets into the canonical form of RFC 3986
+/// 6.2.2: hex digits are uppercased and unreserved characters are decoded.
+pub fn normalize(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex_value(bytes[i + 1]), hex_value(bytes[i + 2])) {
+                let b = hi << 4 | lo;
+                if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_' | b'~') {
+                    output.push(char::from(b));
+                } else {
+                    push_encoded_byte(b, &mut output);
+                }
+                i += 3;
+                continue;
+            }
+        }
+        let c = input[i..].chars().next().expect("i is on a char boundary");
+        output.push(c);
+        i += c.len_utf8();
+    }
+    output
+}
+
+// https://url.spec.whatwg.org/#urlencoded-serializing
+pub fn form_urlencode(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for b in input.bytes() {
+        if b == b' ' {
+            output.push('+');
+        } else if should_encode(b, FORM_URLENCODED_SET) {
+            push_encoded_byte(b, &mut output);
+        } else {
+            output.push(char::from(b));
+        }
+    }
+    output
+}
+
+// https://url.spec.whatwg.org/#urlencoded-parsing
+pub fn form_urldecode(input: &str) -> String {
+    let bytes: Vec<u8> = input
+        .bytes()
+        .map(|b| if b == b'+' { b' ' } else { b })
+        .collect();
+    String::from_utf8_lossy(&percent_decode(&bytes)).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(percent_encode("a b", PATH_SET), "a%20b");
+        assert_eq!(percent_encode("a/b?c", PATH_SET), "a/b%3Fc");
+        assert_eq!(percent_encode("a/b?c", COMPONENT_SET), "a%2Fb%3Fc");
+        assert_eq!(percent_encode("\u{3042}", FRAGMENT_SET), "%E3%81%82");
+        assert_eq!(percent_encode("\x00\x7f", FRAGMENT_SET), "%00%7F");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(percent_decode(b"a%20b"), b"a b".to_vec());
+        assert_eq!(percent_decode(b"%e3%81%82"), "\u{3042}".as_bytes().to_vec());
+        assert_eq!(percent_decode(b"100%"), b"100%".to_vec());
+        assert_eq!(percent_decode(b"%4"), b"%4".to_vec());
+        assert_eq!(percent_decode(b"%zz%41"), b"%zzA".to_vec());
+        assert_eq!(percent_decode_str("%FF"), "\u{FFFD}".to_string());
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("%7e%2f%e3%81%82"), "~%2F%E3%81%82");
+        assert_eq!(normalize("%41%zz%"), "A%zz%");
+    }
+
+    #[test]
+    fn test_form_urlencoded() {
+        assert_eq!(form_urlencode("a b&c=d*~"), "a+b%26c%3Dd*%7E");
+        assert_eq!(form_urldecode("a+b%26c%3Dd*%7E"), "a b&c=d*~");
+        assert_eq!(form_urldecode("%2B+"), "+ ");
+    }
+}
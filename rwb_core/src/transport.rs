@@ -0,0 +1,60 @@
+//! The network operations the browser needs from the platform. `HttpClient`
+//! is written against these traits, so the same fetching logic runs on any
+//! network stack that implements them.
+
+use crate::error::Error;
+use crate::error::TlsError;
+use alloc::vec::Vec;
+
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+
+/// Opens connections. An implementation usually wraps the platform's name
+/// resolver and TCP sockets.
+pub trait Transport {
+    /// An address that `resolve` returns and `connect` accepts, such as an
+    /// IPv4 address.
+    type Address: Clone;
+    type Connection: Connection;
+
+    /// Looks up the addresses of `host`, which may also be an IP address
+    /// literal. Fails with `NetworkError::Dns` if there are none.
+    fn resolve(&self, host: &str) -> Result<Vec<Self::Address>, Error>;
+
+    /// Opens a connection to `port` at `address`. Fails with
+    /// `NetworkError::Connect`.
+    fn connect(&self, address: &Self::Address, port: u16) -> Result<Self::Connection, Error>;
+
+    /// Opens a connection to `port` at `address` and sets up TLS on it for
+    /// https. `server_name` is the host from the URL: it is sent with SNI
+    /// and the server's certificate must be valid for it. Fails with
+    /// `NetworkError::Connect` or an `Error::Tls`.
+    ///
+    /// Transports without TLS keep this default, which fails with
+    /// `TlsError::Unsupported`.
+    fn connect_tls(
+        &self,
+        address: &Self::Address,
+        port: u16,
+        server_name: &str,
+    ) -> Result<Self::Connection, Error> {
+        let _ = (address, port, server_name);
+        Err(TlsError::Unsupported.into())
+    }
+}
+
+/// A byte stream to a server.
+pub trait Connection {
+    /// Reads up to `buf.len()` bytes and returns how many were read. 0 means
+    /// the server closed the connection. Fails with `NetworkError::Read` or
+    /// `NetworkError::Timeout`.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error>;
+
+    /// Writes some of `buf` and returns how many bytes were written. Fails
+    /// with `NetworkError::Write` or `NetworkError::Timeout`.
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error>;
+
+    /// Closes the connection. A connection that is dropped without this
+    /// should be closed as well; calling it lets the caller say when.
+    fn close(&mut self) -> Result<(), Error>;
+}
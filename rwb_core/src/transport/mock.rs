@@ -0,0 +1,523 @@
+//! A transport that serves scripted responses from memory, so that
+//! `HttpClient` and everything it drives can be tested without a network.
+//!
+//! Responses are keyed by host, port and request target. How each response
+//! is delivered can be scripted too: in small reads, one byte at a time, or
+//! cut off by a connection reset. TLS is not encrypted, but its failures
+//! can be scripted.
+//!
+//! Outside this crate's own tests it needs the `mock` feature, e.g.
+//! `rwb_core = { path = "../../rwb_core", features = ["mock"] }` under
+//! `[dev-dependencies]`.
+
+use crate::error::Error;
+use crate::error::NetworkError;
+use crate::error::TlsError;
+use crate::http::decode_chunked;
+use crate::transport::Connection;
+use crate::transport::Transport;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+/// A scripted response and how the mock server sends it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockResponse {
+    bytes: Vec<u8>,
+    read_size: usize,
+    reset_after: Option<usize>,
+    close: bool,
+}
+
+impl MockResponse {
+    /// Sends `bytes` as they are, as much as the reader asks for at once.
+    pub fn new(bytes: &[u8]) -> Self {
+        Self {
+            bytes: bytes.to_vec(),
+            read_size: usize::MAX,
+            reset_after: None,
+            close: false,
+        }
+    }
+
+    /// Returns at most `read_size` bytes from each read, like a response
+    /// that arrives in several segments.
+    pub fn read_size(mut self, read_size: usize) -> Self {
+        self.read_size = read_size.max(1);
+        self
+    }
+
+    /// Returns one byte from each read, like a very slow server.
+    pub fn slow(self) -> Self {
+        self.read_size(1)
+    }
+
+    /// Resets the connection once `sent` bytes of the response have been
+    /// read. Reads and writes after that fail.
+    pub fn reset_after(mut self, sent: usize) -> Self {
+        self.reset_after = Some(sent);
+        self
+    }
+
+    /// Closes the connection after the response, whatever its headers say.
+    /// Writing to the connection after that fails, as it does when a
+    /// server closes a kept-alive connection while it is idle.
+    pub fn close(mut self) -> Self {
+        self.close = true;
+        self
+    }
+
+    pub fn bytes(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Route {
+    host: String,
+    port: u16,
+    target: String,
+    responses: Vec<MockResponse>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    routes: Vec<Route>,
+    unresolvable: Vec<String>,
+    tls_failures: Vec<(String, TlsError)>,
+    requests: Vec<Vec<u8>>,
+    connections: usize,
+    tls_connections: usize,
+}
+
+impl State {
+    /// Records `request` and picks the response to it.
+    fn respond(&mut self, host: &str, port: u16, request: Vec<u8>) -> MockResponse {
+        // リクエストラインの 2 番目がリクエストターゲット
+        let target = request
+            .split(|b| *b == b' ')
+            .nth(1)
+            .map(|target| String::from_utf8_lossy(target).to_string())
+            .unwrap_or_default();
+        self.requests.push(request);
+
+        let route = self
+            .routes
+            .iter_mut()
+            .find(|r| r.host == host && r.port == port && r.target == target);
+        match route {
+            // 最後の応答はその後も繰り返し返す
+            Some(route) if route.responses.len() > 1 => route.responses.remove(0),
+            Some(route) => route.responses[0].clone(),
+            None => MockResponse::new(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n"),
+        }
+    }
+}
+
+/// Serves scripted responses. Clones share the script and the record of
+/// requests, and scripting takes `&self`, so a transport already handed to
+/// an `HttpClient` can be scripted through `HttpClient::transport`.
+///
+/// A host resolves if any response is scripted for it and it has not been
+/// made to fail with `fail_dns`. Connecting to a port with no scripted
+/// responses is refused. A request for a target with no scripted response
+/// gets 404 Not Found.
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    state: Rc<RefCell<State>>,
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `response` for requests to `host` and `port` whose request
+    /// target is `target`, e.g. "/index.html?q=1". Responses added for the
+    /// same target are sent in turn, and the last one for every request
+    /// after that.
+    pub fn respond(&self, host: &str, port: u16, target: &str, response: MockResponse) {
+        let mut state = self.state.borrow_mut();
+        let route = state
+            .routes
+            .iter_mut()
+            .find(|r| r.host == host && r.port == port && r.target == target);
+        match route {
+            Some(route) => route.responses.push(response),
+            None => state.routes.push(Route {
+                host: host.to_string(),
+                port,
+                target: target.to_string(),
+                responses: vec![response],
+            }),
+        }
+    }
+
+    /// Makes resolving `host` fail even if responses are scripted for it.
+    pub fn fail_dns(&self, host: &str) {
+        self.state.borrow_mut().unresolvable.push(host.to_string());
+    }
+
+    /// Makes setting up TLS with `host` fail with `error`.
+    pub fn fail_tls(&self, host: &str, error: TlsError) {
+        self.state
+            .borrow_mut()
+            .tls_failures
+            .push((host.to_string(), error));
+    }
+
+    /// Returns every complete request received so far, in order.
+    pub fn requests(&self) -> Vec<Vec<u8>> {
+        self.state.borrow().requests.clone()
+    }
+
+    /// Returns how many connections have been opened, with TLS or not.
+    pub fn connections(&self) -> usize {
+        self.state.borrow().connections
+    }
+
+    /// Returns how many of the connections were opened with TLS.
+    pub fn tls_connections(&self) -> usize {
+        self.state.borrow().tls_connections
+    }
+}
+
+impl Transport for MockTransport {
+    /// The host name itself; the mock has no addresses.
+    type Address = String;
+    type Connection = MockConnection;
+
+    fn resolve(&self, host: &str) -> Result<Vec<String>, Error> {
+        let state = self.state.borrow();
+        let known = state.routes.iter().any(|r| r.host == host);
+        if !known || state.unresolvable.iter().any(|h| h == host) {
+            return Err(NetworkError::Dns(format!("{}: no such host", host)).into());
+        }
+        Ok(vec![host.to_string()])
+    }
+
+    fn connect(&self, address: &String, port: u16) -> Result<MockConnection, Error> {
+        let mut state = self.state.borrow_mut();
+        if !state
+            .routes
+            .iter()
+            .any(|r| &r.host == address && r.port == port)
+        {
+            return Err(
+                NetworkError::Connect(format!("{}:{}: connection refused", address, port)).into(),
+            );
+        }
+        state.connections += 1;
+        Ok(MockConnection {
+            host: address.clone(),
+            port,
+            state: self.state.clone(),
+            received: Vec::new(),
+            pending: Vec::new(),
+            sent: 0,
+            closed: false,
+            reset: false,
+        })
+    }
+
+    /// Connects as `connect` does and pretends that the handshake succeeded,
+    /// unless `fail_tls` was called for `server_name`.
+    fn connect_tls(
+        &self,
+        address: &String,
+        port: u16,
+        server_name: &str,
+    ) -> Result<MockConnection, Error> {
+        let connection = self.connect(address, port)?;
+        let mut state = self.state.borrow_mut();
+        if let Some((_, e)) = state.tls_failures.iter().find(|(h, _)| h == server_name) {
+            return Err(e.clone().into());
+        }
+        state.tls_connections += 1;
+        Ok(connection)
+    }
+}
+
+/// One connection to the mock server. Each complete request written to it
+/// queues its response, so requests may also be pipelined.
+#[derive(Debug)]
+pub struct MockConnection {
+    host: String,
+    port: u16,
+    state: Rc<RefCell<State>>,
+    /// Bytes written that do not make a complete request yet.
+    received: Vec<u8>,
+    /// Responses not yet read in full, the first one being read.
+    pending: Vec<MockResponse>,
+    /// How much of the first pending response has been read.
+    sent: usize,
+    closed: bool,
+    reset: bool,
+}
+
+impl Connection for MockConnection {
+    /// Returns the next part of the pending responses. With nothing pending
+    /// a real server would wait for a request; the mock closes instead so
+    /// that tests cannot hang.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.reset {
+            return Err(NetworkError::Read("connection reset".to_string()).into());
+        }
+        let response = match self.pending.first() {
+            Some(response) => response,
+            None => {
+                self.closed = true;
+                return Ok(0);
+            }
+        };
+        if response.reset_after.is_some_and(|n| self.sent >= n) {
+            self.reset = true;
+            self.pending.clear();
+            return Err(NetworkError::Read("connection reset".to_string()).into());
+        }
+
+        let mut n = (response.bytes.len() - self.sent)
+            .min(response.read_size)
+            .min(buf.len());
+        if let Some(reset_after) = response.reset_after {
+            n = n.min(reset_after - self.sent);
+        }
+        buf[..n].copy_from_slice(&response.bytes[self.sent..self.sent + n]);
+        self.sent += n;
+
+        if self.sent == response.bytes.len() && response.reset_after.map_or(true, |n| n > self.sent)
+        {
+            if response.close {
+                self.closed = true;
+                self.pending.clear();
+            } else {
+                self.pending.remove(0);
+            }
+            self.sent = 0;
+        }
+        Ok(n)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        if self.reset || self.closed {
+            return Err(NetworkError::Write("connection reset".to_string()).into());
+        }
+        self.received.extend_from_slice(buf);
+        while let Some(len) = request_length(&self.received) {
+            let request = self.received.drain(..len).collect();
+            let response = self
+                .state
+                .borrow_mut()
+                .respond(&self.host, self.port, request);
+            self.pending.push(response);
+        }
+        Ok(buf.len())
+    }
+
+    fn close(&mut self) -> Result<(), Error> {
+        self.closed = true;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/// Returns the length of the request at the start of `bytes`, or `None` if
+/// it has not been received in full. The body is framed by Content-Length
+/// or the chunked transfer coding.
+///
+/// https://www.rfc-editor.org/rfc/rfc9112#section-6.3
+fn request_length(bytes: &[u8]) -> Option<usize> {
+    let head_len = bytes.windows(4).position(|w| w == b"\r\n\r\n")? + 4;
+    let head = String::from_utf8_lossy(&bytes[..head_len]);
+    let mut content_length = 0;
+    for line in head.split("\r\n").skip(1) {
+        let (name, value) = match line.split_once(':') {
+            Some(field) => field,
+            None => continue,
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("Transfer-Encoding")
+            && value.to_ascii_lowercase().ends_with("chunked")
+        {
+            let (_, _, consumed) = decode_chunked(&bytes[head_len..]).ok()?;
+            return Some(head_len + consumed);
+        }
+        if name.eq_ignore_ascii_case("Content-Length") {
+            content_length = value.parse().ok()?;
+        }
+    }
+    (bytes.len() >= head_len + content_length).then_some(head_len + content_length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorKind;
+    use crate::http::HttpClient;
+    use crate::http::HttpRequest;
+    use crate::url::Url;
+
+    const HELLO: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+    const CHUNKED: &[u8] =
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhe\r\n3\r\nllo\r\n0\r\n\r\n";
+    const UNTIL_CLOSE: &[u8] = b"HTTP/1.0 200 OK\r\n\r\nhello";
+
+    fn get(transport: &MockTransport, host: &str, port: u16, path: &str) -> Result<Vec<u8>, Error> {
+        let client = HttpClient::new(transport.clone());
+        let response = client.get(host.to_string(), port, path.to_string())?;
+        Ok(response.body())
+    }
+
+    #[test]
+    fn test_delivery() {
+        let tests = [
+            // (response, expected)
+            (MockResponse::new(HELLO), Ok(b"hello".to_vec())),
+            (MockResponse::new(HELLO).read_size(7), Ok(b"hello".to_vec())),
+            (MockResponse::new(HELLO).slow(), Ok(b"hello".to_vec())),
+            (MockResponse::new(CHUNKED).slow(), Ok(b"hello".to_vec())),
+            (
+                MockResponse::new(CHUNKED).read_size(5),
+                Ok(b"hello".to_vec()),
+            ),
+            (MockResponse::new(UNTIL_CLOSE).slow(), Ok(b"hello".to_vec())),
+            (
+                MockResponse::new(HELLO).reset_after(0),
+                Err(Error::Network(NetworkError::Read(
+                    "connection reset".to_string(),
+                ))),
+            ),
+            (
+                MockResponse::new(HELLO).slow().reset_after(40),
+                Err(Error::Network(NetworkError::Read(
+                    "connection reset".to_string(),
+                ))),
+            ),
+            (
+                // 応答を送り終えてから閉じても、その応答は読める
+                MockResponse::new(HELLO).close().read_size(4),
+                Ok(b"hello".to_vec()),
+            ),
+            (
+                MockResponse::new(b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nhello").slow(),
+                Err(Error::parse(ParseErrorKind::IncompleteBody, 43)),
+            ),
+        ];
+        for (response, expected) in tests {
+            let transport = MockTransport::new();
+            transport.respond("a.test", 80, "/", response.clone());
+            assert_eq!(
+                get(&transport, "a.test", 80, "/"),
+                expected,
+                "{:?}",
+                response
+            );
+        }
+    }
+
+    #[test]
+    fn test_keyed_by_host_port_and_target() {
+        let transport = MockTransport::new();
+        let ok = |body: &str| {
+            MockResponse::new(
+                format!("HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\n{}", body).as_bytes(),
+            )
+        };
+        transport.respond("a.test", 80, "/", ok("1"));
+        transport.respond("a.test", 8000, "/", ok("2"));
+        transport.respond("b.test", 80, "/", ok("3"));
+        transport.respond("a.test", 80, "/?q=1", ok("4"));
+
+        let tests = [
+            // (host, port, path, expected)
+            ("a.test", 80, "/", b"1".to_vec()),
+            ("a.test", 8000, "/", b"2".to_vec()),
+            ("b.test", 80, "/", b"3".to_vec()),
+            ("a.test", 80, "/?q=1", b"4".to_vec()),
+            ("a.test", 80, "/missing", b"".to_vec()),
+        ];
+        for (host, port, path, expected) in tests {
+            assert_eq!(get(&transport, host, port, path), Ok(expected), "{}", path);
+        }
+        assert_eq!(transport.requests().len(), 5);
+    }
+
+    #[test]
+    fn test_network_errors() {
+        let transport = MockTransport::new();
+        transport.respond("a.test", 80, "/", MockResponse::new(HELLO));
+        transport.respond("down.test", 80, "/", MockResponse::new(HELLO));
+        transport.fail_dns("down.test");
+
+        let tests = [
+            // (host, port, expected)
+            (
+                "b.test",
+                80,
+                NetworkError::Dns("b.test: no such host".to_string()),
+            ),
+            (
+                "down.test",
+                80,
+                NetworkError::Dns("down.test: no such host".to_string()),
+            ),
+            (
+                "a.test",
+                81,
+                NetworkError::Connect("a.test:81: connection refused".to_string()),
+            ),
+        ];
+        for (host, port, expected) in tests {
+            assert_eq!(
+                get(&transport, host, port, "/"),
+                Err(Error::Network(expected)),
+                "{}:{}",
+                host,
+                port
+            );
+        }
+        assert_eq!(transport.connections(), 0);
+    }
+
+    #[test]
+    fn test_responses_in_turn() {
+        let transport = MockTransport::new();
+        for body in ["a", "b"] {
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\n{}", body);
+            transport.respond("a.test", 80, "/", MockResponse::new(response.as_bytes()));
+        }
+        let client = HttpClient::new(transport.clone());
+        for expected in [b"a", b"b", b"b"] {
+            let response = client
+                .get("a.test".to_string(), 80, "/".to_string())
+                .unwrap();
+            assert_eq!(response.body(), expected);
+        }
+        assert_eq!(transport.connections(), 1);
+    }
+
+    #[test]
+    fn test_request_body_is_framed() {
+        let transport = MockTransport::new();
+        transport.respond("a.test", 80, "/post", MockResponse::new(HELLO));
+        let client = HttpClient::new(transport.clone());
+        let url = Url::new("http://a.test/post".to_string()).unwrap();
+        for chunked in [false, true] {
+            let mut builder = HttpRequest::builder_for_url("POST", &url).body(b"x=1".to_vec());
+            if chunked {
+                builder = builder.chunked();
+            }
+            let response = client.request(builder.build().unwrap()).unwrap();
+            assert_eq!(response.body(), b"hello");
+        }
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].ends_with(b"\r\n\r\nx=1"));
+        assert!(requests[1].ends_with(b"\r\n\r\n3\r\nx=1\r\n0\r\n\r\n"));
+    }
+}
@@ -0,0 +1,502 @@
+use super::public_suffix;
+use super::punycode;
+use super::ParseError;
+use crate::percent_encoding;
+use crate::percent_encoding::C0_CONTROL_SET;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The host of a URL.
+///
+/// https://url.spec.whatwg.org/#concept-host
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Host {
+    /// An ASCII domain such as `example.com` or `xn--wgv71a119e.jp`. The
+    /// empty host of `file:///` URLs is an empty domain.
+    Domain(String),
+    Ipv4([u8; 4]),
+    Ipv6([u16; 8]),
+    /// The host of a URL whose scheme is not special, kept as written apart
+    /// from percent-encoding.
+    Opaque(String),
+}
+
+// https://url.spec.whatwg.org/#forbidden-host-code-point
+const FORBIDDEN_HOST: &[char] = &[
+    '\0', '\t', '\n', '\r', ' ', '#', '/', ':', '<', '>', '?', '@', '[', '\\', ']', '^', '|',
+];
+
+fn is_forbidden_domain_code_point(c: char) -> bool {
+    FORBIDDEN_HOST.contains(&c) || c.is_ascii_control() || c == '%'
+}
+
+impl Host {
+    /// Parses `input` as the host of a URL with a special scheme when
+    /// `is_special` is true, or as an opaque host otherwise.
+    ///
+    /// https://url.spec.whatwg.org/#host-parsing
+    pub fn parse(input: &str, is_special: bool) -> Result<Self, ParseError> {
+        if let Some(address) = input.strip_prefix('[') {
+            let address = address
+                .strip_suffix(']')
+                .ok_or_else(|| ParseError::InvalidIpv6(input.to_string()))?;
+            return parse_ipv6(address)
+                .map(Host::Ipv6)
+                .ok_or_else(|| ParseError::InvalidIpv6(input.to_string()));
+        }
+
+        if !is_special {
+            if input.contains(FORBIDDEN_HOST) {
+                return Err(ParseError::InvalidHost(input.to_string()));
+            }
+            return Ok(Host::Opaque(percent_encoding::percent_encode(
+                input,
+                C0_CONTROL_SET,
+            )));
+        }
+
+        let domain = percent_encoding::percent_decode_str(input);
+        let ascii_domain =
+            domain_to_ascii(&domain).ok_or_else(|| ParseError::InvalidHost(input.to_string()))?;
+        if ascii_domain.contains(is_forbidden_domain_code_point) {
+            return Err(ParseError::InvalidHost(input.to_string()));
+        }
+        if ends_in_a_number(&ascii_domain) {
+            return parse_ipv4(&ascii_domain)
+                .map(|address| Host::Ipv4(address.to_be_bytes()))
+                .ok_or_else(|| ParseError::InvalidIpv4(input.to_string()));
+        }
+        Ok(Host::Domain(ascii_domain))
+    }
+
+    /// Returns the public suffix of a domain, e.g. `co.jp` for
+    /// `www.example.co.jp`. `None` for IP addresses and opaque hosts.
+    pub fn public_suffix(&self) -> Option<&str> {
+        match self {
+            Host::Domain(domain) => public_suffix::public_suffix(domain),
+            _ => None,
+        }
+    }
+
+    /// Returns the registrable domain, e.g. `example.co.jp` for
+    /// `www.example.co.jp`. `None` for IP addresses, opaque hosts and domains
+    /// that are themselves public suffixes.
+    pub fn registrable_domain(&self) -> Option<&str> {
+        match self {
+            Host::Domain(domain) => public_suffix::registrable_domain(domain),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Host {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Host::Domain(domain) => f.write_str(domain),
+            Host::Opaque(host) => f.write_str(host),
+            Host::Ipv4([a, b, c, d]) => write!(f, "{}.{}.{}.{}", a, b, c, d),
+            Host::Ipv6(pieces) => {
+                // https://url.spec.whatwg.org/#concept-ipv6-serializer
+                let compress = longest_zero_run(pieces);
+                f.write_str("[")?;
+                let mut i = 0;
+                while i < 8 {
+                    match compress {
+                        Some((start, len)) if i == start => {
+                            f.write_str(if i == 0 { "::" } else { ":" })?;
+                            i += len;
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    write!(f, "{:x}", pieces[i])?;
+                    if i != 7 {
+                        f.write_str(":")?;
+                    }
+                    i += 1;
+                }
+                f.write_str("]")
+            }
+        }
+    }
+}
+
+/// Finds the first longest run of two or more zero pieces, which the
+/// serializer replaces with `::`.
+fn longest_zero_run(pieces: &[u16; 8]) -> Option<(usize, usize)> {
+    let mut longest: Option<(usize, usize)> = None;
+    let mut i = 0;
+    while i < 8 {
+        if pieces[i] != 0 {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < 8 && pieces[i] == 0 {
+            i += 1;
+        }
+        let len = i - start;
+        if len >= 2 && longest.map_or(true, |(_, l)| len > l) {
+            longest = Some((start, len));
+        }
+    }
+    longest
+}
+
+/// Converts a Unicode domain into its ASCII form, the subset of UTS #46
+/// ToASCII that this browser needs: full-width characters and ideographic
+/// full stops are mapped, labels are lowercased, and non-ASCII labels are
+/// Punycode-encoded with the `xn--` prefix.
+///
+/// https://url.spec.whatwg.org/#concept-domain-to-ascii
+fn domain_to_ascii(domain: &str) -> Option<String> {
+    let mut mapped = String::with_capacity(domain.len());
+    for c in domain.chars() {
+        match c {
+            // 。 ． ｡
+            '\u{3002}' | '\u{ff0e}' | '\u{ff61}' => mapped.push('.'),
+            // 全角英数字と記号は半角にする
+            '\u{ff01}'..='\u{ff5e}' => {
+                mapped.extend(char::from_u32(c as u32 - 0xfee0)?.to_lowercase())
+            }
+            _ => mapped.extend(c.to_lowercase()),
+        }
+    }
+
+    let labels: Vec<String> = mapped
+        .split('.')
+        .map(|label| {
+            if label.is_ascii() {
+                // 既に xn-- 形式のラベルは正しい Punycode であることだけ確かめる
+                match label.strip_prefix("xn--") {
+                    Some(encoded) => punycode::decode(encoded).map(|_| label.to_string()),
+                    None => Some(label.to_string()),
+                }
+            } else {
+                punycode::encode(label).map(|encoded| format!("xn--{}", encoded))
+            }
+        })
+        .collect::<Option<_>>()?;
+    Some(labels.join("."))
+}
+
+// https://url.spec.whatwg.org/#ends-in-a-number-checker
+fn ends_in_a_number(domain: &str) -> bool {
+    let mut parts: Vec<&str> = domain.split('.').collect();
+    if parts.last() == Some(&"") {
+        if parts.len() == 1 {
+            return false;
+        }
+        parts.pop();
+    }
+    let last = parts.last().copied().unwrap_or("");
+    if !last.is_empty() && last.bytes().all(|b| b.is_ascii_digit()) {
+        return true;
+    }
+    parse_ipv4_number(last).is_some()
+}
+
+/// Parses one dot-separated part of an IPv4 address, which may be written in
+/// decimal, in octal with a leading `0`, or in hex with a leading `0x`.
+fn parse_ipv4_number(input: &str) -> Option<u64> {
+    if input.is_empty() {
+        return None;
+    }
+    let (digits, radix) = if let Some(hex) = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+    {
+        (hex, 16)
+    } else if input.len() > 1 && input.starts_with('0') {
+        (&input[1..], 8)
+    } else {
+        (input, 10)
+    };
+    if digits.is_empty() {
+        return Some(0);
+    }
+    if !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    // 2^32 を超える値はどのみち不正なので、桁あふれは上限で止める
+    Some(u64::from_str_radix(digits, radix).unwrap_or(u64::MAX))
+}
+
+// https://url.spec.whatwg.org/#concept-ipv4-parser
+fn parse_ipv4(input: &str) -> Option<u32> {
+    let mut parts: Vec<&str> = input.split('.').collect();
+    if parts.last() == Some(&"") && parts.len() > 1 {
+        parts.pop();
+    }
+    if parts.len() > 4 {
+        return None;
+    }
+    let numbers: Vec<u64> = parts
+        .iter()
+        .map(|part| parse_ipv4_number(part))
+        .collect::<Option<_>>()?;
+    let (last, init) = numbers.split_last()?;
+    if init.iter().any(|n| *n > 255) {
+        return None;
+    }
+    if *last >= 256u64.pow(5 - numbers.len() as u32) {
+        return None;
+    }
+    let mut ipv4 = *last;
+    for (i, n) in init.iter().enumerate() {
+        ipv4 += n * 256u64.pow(3 - i as u32);
+    }
+    u32::try_from(ipv4).ok()
+}
+
+// https://url.spec.whatwg.org/#concept-ipv6-parser
+fn parse_ipv6(input: &str) -> Option<[u16; 8]> {
+    let input: Vec<char> = input.chars().collect();
+    let at = |pointer: usize| input.get(pointer).copied();
+    let mut address = [0u16; 8];
+    let mut piece_index = 0;
+    let mut compress: Option<usize> = None;
+    let mut pointer = 0;
+
+    if at(pointer) == Some(':') {
+        if at(pointer + 1) != Some(':') {
+            return None;
+        }
+        pointer += 2;
+        piece_index += 1;
+        compress = Some(piece_index);
+    }
+
+    while let Some(c) = at(pointer) {
+        if piece_index == 8 {
+            return None;
+        }
+        if c == ':' {
+            if compress.is_some() {
+                return None;
+            }
+            pointer += 1;
+            piece_index += 1;
+            compress = Some(piece_index);
+            continue;
+        }
+
+        let mut value: u16 = 0;
+        let mut length = 0;
+        while length < 4 {
+            match at(pointer).and_then(|c| c.to_digit(16)) {
+                Some(digit) => {
+                    value = value * 0x10 + digit as u16;
+                    pointer += 1;
+                    length += 1;
+                }
+                None => break,
+            }
+        }
+
+        match at(pointer) {
+            Some('.') => {
+                // 末尾に埋め込まれた IPv4 アドレス (::ffff:192.0.2.1 など)
+                if length == 0 {
+                    return None;
+                }
+                pointer -= length;
+                if piece_index > 6 {
+                    return None;
+                }
+                let mut numbers_seen = 0;
+                while at(pointer).is_some() {
+                    if numbers_seen > 0 {
+                        if at(pointer) == Some('.') && numbers_seen < 4 {
+                            pointer += 1;
+                        } else {
+                            return None;
+                        }
+                    }
+                    let mut ipv4_piece: Option<u16> = None;
+                    while let Some(digit) = at(pointer).and_then(|c| c.to_digit(10)) {
+                        ipv4_piece = match ipv4_piece {
+                            None => Some(digit as u16),
+                            Some(0) => return None,
+                            Some(piece) => Some(piece * 10 + digit as u16),
+                        };
+                        if ipv4_piece > Some(255) {
+                            return None;
+                        }
+                        pointer += 1;
+                    }
+                    address[piece_index] = address[piece_index] * 0x100 + ipv4_piece?;
+                    numbers_seen += 1;
+                    if numbers_seen == 2 || numbers_seen == 4 {
+                        piece_index += 1;
+                    }
+                }
+                if numbers_seen != 4 {
+                    return None;
+                }
+                break;
+            }
+            Some(':') => {
+                pointer += 1;
+                at(pointer)?;
+            }
+            Some(_) => return None,
+            None => {}
+        }
+        address[piece_index] = value;
+        piece_index += 1;
+    }
+
+    if let Some(compress) = compress {
+        let mut swaps = piece_index - compress;
+        piece_index = 7;
+        while piece_index != 0 && swaps > 0 {
+            address.swap(piece_index, compress + swaps - 1);
+            piece_index -= 1;
+            swaps -= 1;
+        }
+    } else if piece_index != 8 {
+        return None;
+    }
+    Some(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ipv4() {
+        let cases: &[(&str, Option<[u8; 4]>)] = &[
+            ("127.0.0.1", Some([127, 0, 0, 1])),
+            ("127.0.0.1.", Some([127, 0, 0, 1])),
+            ("0x7f.1", Some([127, 0, 0, 1])),
+            ("0177.0.0.01", Some([127, 0, 0, 1])),
+            ("2130706433", Some([127, 0, 0, 1])),
+            ("0x7F000001", Some([127, 0, 0, 1])),
+            ("192.168.257", Some([192, 168, 1, 1])),
+            ("0x", Some([0, 0, 0, 0])),
+            ("256.0.0.1", None),
+            ("1.2.3.4.5", None),
+            ("4294967296", None),
+            ("1.2.3.09", None),
+            ("1..2", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                parse_ipv4(input).map(u32::to_be_bytes),
+                *expected,
+                "parsing {:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_ipv6() {
+        // (input, pieces, serialization)
+        let cases: &[(&str, [u16; 8], &str)] = &[
+            ("::1", [0, 0, 0, 0, 0, 0, 0, 1], "[::1]"),
+            ("::", [0; 8], "[::]"),
+            ("1::", [1, 0, 0, 0, 0, 0, 0, 0], "[1::]"),
+            (
+                "2001:DB8:0:0:1:0:0:1",
+                [0x2001, 0xdb8, 0, 0, 1, 0, 0, 1],
+                "[2001:db8::1:0:0:1]",
+            ),
+            (
+                "2001:db8::ff00:42:8329",
+                [0x2001, 0xdb8, 0, 0, 0, 0xff00, 0x42, 0x8329],
+                "[2001:db8::ff00:42:8329]",
+            ),
+            (
+                "1:0:2:3:4:5:6:7",
+                [1, 0, 2, 3, 4, 5, 6, 7],
+                "[1:0:2:3:4:5:6:7]",
+            ),
+            (
+                "::ffff:192.0.2.128",
+                [0, 0, 0, 0, 0, 0xffff, 0xc000, 0x0280],
+                "[::ffff:c000:280]",
+            ),
+        ];
+
+        for (input, pieces, serialization) in cases {
+            assert_eq!(parse_ipv6(input), Some(*pieces), "parsing {:?}", input);
+            assert_eq!(Host::Ipv6(*pieces).to_string(), *serialization);
+        }
+
+        for input in [
+            "",
+            ":1",
+            "1:",
+            "1:::2",
+            "1::2::3",
+            "12345::",
+            "1:2:3:4:5:6:7:8:9",
+            "1:2:3:4:5:6:7",
+            "::1.2.3",
+            "::1.2.3.04",
+            "::256.0.0.1",
+            "::1.2.3.4.5",
+            "g::",
+        ] {
+            assert_eq!(parse_ipv6(input), None, "parsing {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_domain_to_ascii() {
+        let cases: &[(&str, &str)] = &[
+            ("example.com", "example.com"),
+            ("EXAMPLE.com", "example.com"),
+            ("日本語.jp", "xn--wgv71a119e.jp"),
+            ("日本語。ＪＰ", "xn--wgv71a119e.jp"),
+            ("例え.テスト", "xn--r8jz45g.xn--zckzah"),
+            ("Bücher.example", "xn--bcher-kva.example"),
+            ("xn--wgv71a119e.jp", "xn--wgv71a119e.jp"),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(domain_to_ascii(input).as_deref(), Some(*expected));
+        }
+        assert_eq!(domain_to_ascii("xn--a!.jp"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Host::parse("%E6%97%A5%E6%9C%AC%E8%AA%9E.jp", true),
+            Ok(Host::Domain("xn--wgv71a119e.jp".to_string()))
+        );
+        assert_eq!(Host::parse("0x7f.1", true), Ok(Host::Ipv4([127, 0, 0, 1])));
+        assert_eq!(
+            Host::parse("1.2.3.256", true),
+            Err(ParseError::InvalidIpv4("1.2.3.256".to_string()))
+        );
+        assert_eq!(
+            Host::parse("foo.0x", true),
+            Err(ParseError::InvalidIpv4("foo.0x".to_string()))
+        );
+        assert_eq!(
+            Host::parse("1.2.3.4", false),
+            Ok(Host::Opaque("1.2.3.4".to_string()))
+        );
+        assert_eq!(
+            Host::parse("Ex%41mple", false),
+            Ok(Host::Opaque("Ex%41mple".to_string()))
+        );
+        assert_eq!(
+            Host::parse("[::1", true),
+            Err(ParseError::InvalidIpv6("[::1".to_string()))
+        );
+        assert_eq!(
+            Host::parse("a%25b", true),
+            Err(ParseError::InvalidHost("a%25b".to_string()))
+        );
+    }
+}
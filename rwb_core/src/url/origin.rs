@@ -0,0 +1,182 @@
+use super::Host;
+use super::Url;
+use alloc::string::String;
+use core::fmt;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+
+static NEXT_OPAQUE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The origin of a resource, the unit of isolation for cookies, storage and
+/// script access.
+///
+/// https://html.spec.whatwg.org/multipage/browsers.html#origin
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Origin {
+    /// A scheme, host and port triple. `port` is `None` when the URL uses the
+    /// default port of its scheme.
+    Tuple {
+        scheme: String,
+        host: Host,
+        port: Option<u16>,
+    },
+    /// An origin that is only ever same-origin with itself, given to
+    /// `data:`, `about:` and `file:` URLs among others. Each call to
+    /// [`Origin::new_opaque`] returns a distinct one.
+    Opaque(usize),
+}
+
+impl Origin {
+    pub fn new_opaque() -> Self {
+        Origin::Opaque(NEXT_OPAQUE_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    // https://url.spec.whatwg.org/#concept-url-origin
+    pub fn from_url(url: &Url) -> Self {
+        match (url.scheme.as_str(), &url.host) {
+            ("http" | "https", Some(host)) => Origin::Tuple {
+                scheme: url.scheme.clone(),
+                host: host.clone(),
+                port: url.port,
+            },
+            _ => Self::new_opaque(),
+        }
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        matches!(self, Origin::Opaque(_))
+    }
+
+    // https://html.spec.whatwg.org/multipage/browsers.html#same-origin
+    pub fn is_same_origin(&self, other: &Origin) -> bool {
+        self == other
+    }
+
+    /// Whether the two origins have the same scheme and hosts with the same
+    /// registrable domain, so that `https://a.example.co.jp` and
+    /// `https://b.example.co.jp` are same-site but `https://a.github.io` and
+    /// `https://b.github.io` are not.
+    ///
+    /// https://html.spec.whatwg.org/multipage/browsers.html#same-site
+    pub fn is_same_site(&self, other: &Origin) -> bool {
+        match (self, other) {
+            (Origin::Opaque(_), Origin::Opaque(_)) => self == other,
+            (
+                Origin::Tuple {
+                    scheme: scheme_a,
+                    host: host_a,
+                    ..
+                },
+                Origin::Tuple {
+                    scheme: scheme_b,
+                    host: host_b,
+                    ..
+                },
+            ) => scheme_a == scheme_b && is_same_site_host(host_a, host_b),
+            _ => false,
+        }
+    }
+}
+
+// https://html.spec.whatwg.org/multipage/browsers.html#obtain-a-site
+fn is_same_site_host(a: &Host, b: &Host) -> bool {
+    if a == b {
+        return true;
+    }
+    match (a.registrable_domain(), b.registrable_domain()) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+impl fmt::Display for Origin {
+    // https://html.spec.whatwg.org/multipage/browsers.html#ascii-serialisation-of-an-origin
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Tuple { scheme, host, port } => {
+                write!(f, "{}://{}", scheme, host)?;
+                if let Some(port) = port {
+                    write!(f, ":{}", port)?;
+                }
+                Ok(())
+            }
+            Origin::Opaque(_) => f.write_str("null"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn origin(url: &str) -> Origin {
+        Url::new(url.to_string()).unwrap().origin()
+    }
+
+    #[test]
+    fn test_from_url() {
+        let cases: &[(&str, &str)] = &[
+            ("http://example.com/a?b#c", "http://example.com"),
+            ("HTTPS://Example.com:443/", "https://example.com"),
+            ("https://example.com:8443/", "https://example.com:8443"),
+            ("http://user:pass@[::1]:8000/", "http://[::1]:8000"),
+            ("http://日本語.jp/", "http://xn--wgv71a119e.jp"),
+            ("file:///etc/hosts", "null"),
+            ("data:text/html,hi", "null"),
+            ("about:blank", "null"),
+            ("gemini://example.org/", "null"),
+        ];
+
+        for (url, expected) in cases {
+            assert_eq!(origin(url).to_string(), *expected, "origin of {:?}", url);
+        }
+    }
+
+    #[test]
+    fn test_same_origin() {
+        assert!(origin("http://example.com/a").is_same_origin(&origin("http://example.com:80/b")));
+        assert!(!origin("http://example.com/").is_same_origin(&origin("https://example.com/")));
+        assert!(!origin("http://example.com/").is_same_origin(&origin("http://example.com:81/")));
+        assert!(!origin("http://a.example.com/").is_same_origin(&origin("http://example.com/")));
+
+        let opaque = origin("data:text/html,hi");
+        assert!(opaque.is_same_origin(&opaque.clone()));
+        assert!(!opaque.is_same_origin(&origin("data:text/html,hi")));
+    }
+
+    #[test]
+    fn test_same_site() {
+        let cases: &[(&str, &str, bool)] = &[
+            (
+                "https://example.com/",
+                "https://www.example.com:8443/",
+                true,
+            ),
+            ("https://a.example.co.jp/", "https://b.example.co.jp/", true),
+            ("https://a.example.co.jp/", "https://example.jp/", false),
+            ("https://a.github.io/", "https://b.github.io/", false),
+            ("https://a.github.io/", "https://x.a.github.io/", true),
+            ("http://example.com/", "https://example.com/", false),
+            ("http://127.0.0.1/", "http://127.0.0.1:8000/", true),
+            ("http://127.0.0.1/", "http://127.0.0.2/", false),
+            ("http://co.jp/", "http://co.jp/", true),
+            ("http://co.jp/", "http://a.co.jp/", false),
+            ("https://example.com/", "data:text/html,hi", false),
+        ];
+
+        for (a, b, expected) in cases {
+            assert_eq!(
+                origin(a).is_same_site(&origin(b)),
+                *expected,
+                "{:?} and {:?}",
+                a,
+                b
+            );
+        }
+
+        let opaque = origin("about:blank");
+        assert!(opaque.is_same_site(&opaque.clone()));
+        assert!(!opaque.is_same_site(&origin("about:blank")));
+    }
+}
@@ -0,0 +1,129 @@
+//! Public suffix and registrable domain lookup against an embedded copy of
+//! the Public Suffix List.
+//!
+//! https://publicsuffix.org/list/
+
+use super::punycode;
+use alloc::format;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+// build.rs が書き出す、ソートされたルールの配列 RULES
+include!(concat!(env!("OUT_DIR"), "/public_suffix_rules.rs"));
+
+/// Returns the public suffix of the ASCII domain `domain`, e.g. `co.jp` for
+/// `www.example.co.jp`. Domains under a TLD that is not on the list fall back
+/// to the implicit `*` rule, so their public suffix is the TLD.
+pub fn public_suffix(domain: &str) -> Option<&str> {
+    let count = suffix_label_count(domain)?;
+    Some(last_labels(domain, count))
+}
+
+/// Returns the public suffix plus one label, e.g. `example.co.jp` for
+/// `www.example.co.jp`, or `None` if `domain` is itself a public suffix.
+pub fn registrable_domain(domain: &str) -> Option<&str> {
+    let count = suffix_label_count(domain)?;
+    if domain.trim_end_matches('.').split('.').count() <= count {
+        return None;
+    }
+    Some(last_labels(domain, count + 1))
+}
+
+/// Returns the last `count` labels of `domain`, keeping a trailing dot.
+fn last_labels(domain: &str, count: usize) -> &str {
+    let trimmed = domain.trim_end_matches('.');
+    let start = trimmed
+        .rmatch_indices('.')
+        .nth(count - 1)
+        .map_or(0, |(i, _)| i + 1);
+    &domain[start..]
+}
+
+/// Finds the prevailing rule for `domain` and returns how many labels the
+/// public suffix has.
+///
+/// https://github.com/publicsuffix/list/wiki/Format#algorithm
+fn suffix_label_count(domain: &str) -> Option<usize> {
+    let domain = domain.trim_end_matches('.');
+    if domain.is_empty() || domain.split('.').any(|label| label.is_empty()) {
+        return None;
+    }
+    // リストのルールは Unicode で書かれているので、ラベルを Punycode から戻して比べる
+    let labels: Vec<String> = domain
+        .split('.')
+        .map(|label| match label.strip_prefix("xn--") {
+            Some(encoded) => punycode::decode(encoded).unwrap_or_else(|| label.to_string()),
+            None => label.to_string(),
+        })
+        .collect();
+
+    let is_rule = |rule: &str| RULES.binary_search(&rule).is_ok();
+    let mut longest = 1;
+    let mut exception: Option<usize> = None;
+    // 右から 1 ラベルずつ伸ばした接尾辞ごとに、完全一致、ワイルドカード、例外を探す
+    for count in 1..=labels.len() {
+        let suffix = labels[labels.len() - count..].join(".");
+        if is_rule(&format!("!{}", suffix)) {
+            exception = Some(count - 1);
+        }
+        let wildcard = match count {
+            1 => "*".to_string(),
+            _ => format!("*.{}", labels[labels.len() - count + 1..].join(".")),
+        };
+        if is_rule(&suffix) || is_rule(&wildcard) {
+            longest = count;
+        }
+    }
+    Some(exception.unwrap_or(longest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_suffix() {
+        // (domain, public suffix, registrable domain)
+        let cases: &[(&str, &str, Option<&str>)] = &[
+            ("com", "com", None),
+            ("example.com", "com", Some("example.com")),
+            ("www.example.com", "com", Some("example.com")),
+            ("www.example.co.jp", "co.jp", Some("example.co.jp")),
+            ("co.jp", "co.jp", None),
+            ("example.jp", "jp", Some("example.jp")),
+            ("foo.bar.kobe.jp", "bar.kobe.jp", Some("foo.bar.kobe.jp")),
+            ("www.city.kobe.jp", "kobe.jp", Some("city.kobe.jp")),
+            ("user.github.io", "github.io", Some("user.github.io")),
+            (
+                "a.example.xn--55qx5d.cn",
+                "xn--55qx5d.cn",
+                Some("example.xn--55qx5d.cn"),
+            ),
+            (
+                "www.example.unknowntld",
+                "unknowntld",
+                Some("example.unknowntld"),
+            ),
+            ("www.example.com.", "com.", Some("example.com.")),
+        ];
+
+        for (domain, suffix, registrable) in cases {
+            assert_eq!(
+                public_suffix(domain),
+                Some(*suffix),
+                "suffix of {:?}",
+                domain
+            );
+            assert_eq!(
+                registrable_domain(domain),
+                *registrable,
+                "registrable domain of {:?}",
+                domain
+            );
+        }
+
+        assert_eq!(public_suffix(""), None);
+        assert_eq!(public_suffix("a..com"), None);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::percent_encoding::form_urldecode;
+use crate::percent_encoding::form_urlencode;
+use alloc::string::String;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// An ordered list of name-value pairs parsed from an
+/// `application/x-www-form-urlencoded` string, the model behind
+/// `URLSearchParams` and HTML form submission.
+///
+/// https://url.spec.whatwg.org/#interface-urlsearchparams
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchParams {
+    pairs: Vec<(String, String)>,
+}
+
+impl SearchParams {
+    pub fn new() -> Self {
+        Self { pairs: Vec::new() }
+    }
+
+    /// Parses `input`, ignoring one leading `?` so that both `Url::query()`
+    /// and `location.search` style strings are accepted.
+    pub fn parse(input: &str) -> Self {
+        let input = input.strip_prefix('?').unwrap_or(input);
+        let mut pairs = Vec::new();
+        for sequence in input.split('&') {
+            if sequence.is_empty() {
+                continue;
+            }
+            let (name, value) = sequence.split_once('=').unwrap_or((sequence, ""));
+            pairs.push((form_urldecode(name), form_urldecode(value)));
+        }
+        Self { pairs }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn pairs(&self) -> Vec<(String, String)> {
+        self.pairs.clone()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.pairs
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+    }
+
+    pub fn get_all(&self, name: &str) -> Vec<String> {
+        self.pairs
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, v)| v.clone())
+            .collect()
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.pairs.iter().any(|(n, _)| n == name)
+    }
+
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.pairs.push((name.to_string(), value.to_string()));
+    }
+
+    /// Replaces the value of the first pair named `name` and removes the
+    /// others, or appends a new pair if there is none.
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self.pairs.iter().position(|(n, _)| n == name) {
+            Some(index) => {
+                self.pairs[index].1 = value.to_string();
+                let mut i = 0;
+                self.pairs.retain(|(n, _)| {
+                    let keep = i <= index || n != name;
+                    i += 1;
+                    keep
+                });
+            }
+            None => self.append(name, value),
+        }
+    }
+
+    pub fn delete(&mut self, name: &str) {
+        self.pairs.retain(|(n, _)| n != name);
+    }
+
+    /// Sorts the pairs by name, keeping the relative order of pairs with the
+    /// same name.
+    pub fn sort(&mut self) {
+        // 仕様では UTF-16 のコード単位で比較する
+        self.pairs
+            .sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+    }
+}
+
+impl fmt::Display for SearchParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (name, value)) in self.pairs.iter().enumerate() {
+            if i > 0 {
+                f.write_str("&")?;
+            }
+            write!(f, "{}={}", form_urlencode(name), form_urlencode(value))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let params = SearchParams::parse("?a=1&b=x+y&&c&a=%E3%81%82&=v&d=e=f");
+        assert_eq!(
+            params.pairs(),
+            [
+                ("a", "1"),
+                ("b", "x y"),
+                ("c", ""),
+                ("a", "\u{3042}"),
+                ("", "v"),
+                ("d", "e=f"),
+            ]
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect::<Vec<_>>()
+        );
+        assert_eq!(params.get("a"), Some("1".to_string()));
+        assert_eq!(params.get_all("a"), ["1", "\u{3042}"]);
+        assert_eq!(params.get("z"), None);
+        assert!(params.has("c"));
+    }
+
+    #[test]
+    fn test_mutate() {
+        let mut params = SearchParams::parse("a=1&b=2&a=3&c=4");
+        params.set("a", "x");
+        assert_eq!(params.to_string(), "a=x&b=2&c=4");
+        params.set("d", "y z");
+        assert_eq!(params.to_string(), "a=x&b=2&c=4&d=y+z");
+        params.delete("b");
+        assert_eq!(params.to_string(), "a=x&c=4&d=y+z");
+        params.append("a", "&=");
+        assert_eq!(params.to_string(), "a=x&c=4&d=y+z&a=%26%3D");
+        params.sort();
+        assert_eq!(params.to_string(), "a=x&a=%26%3D&c=4&d=y+z");
+        assert_eq!(params.len(), 4);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = "q=rust+%E3%83%96%E3%83%A9%E3%82%A6%E3%82%B6&lang=ja";
+        assert_eq!(SearchParams::parse(input).to_string(), input);
+        assert!(SearchParams::parse("").is_empty());
+        assert_eq!(SearchParams::new().to_string(), "");
+    }
+}
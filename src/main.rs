@@ -1,23 +1,141 @@
-#![no_std]
-#![cfg_attr(not(target_os = "linux"), no_main)]
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(all(not(feature = "std"), not(target_os = "linux")), no_main)]
+
+#[cfg(not(any(feature = "std", feature = "wasabi")))]
+compile_error!("enable the \"wasabi\" or the \"std\" feature");
 
 extern crate alloc;
 
 use crate::alloc::string::ToString;
+#[cfg(feature = "std")]
+use net_std::cache::DiskStorage;
+#[cfg(feature = "std")]
+use net_std::http::system_clock;
+#[cfg(feature = "std")]
+use net_std::http::HttpClient;
+#[cfg(feature = "std")]
+use net_std::http::StdTransport;
+#[cfg(feature = "std")]
+use net_std::tls::RootStore;
+#[cfg(not(feature = "std"))]
 use net_wasabi::http::HttpClient;
+#[cfg(not(feature = "std"))]
+use net_wasabi::http::WasabiTransport;
+#[cfg(not(feature = "std"))]
 use noli::prelude::*;
+use rwb_core::error::Error;
+use rwb_core::http::HttpResponse;
+#[cfg(feature = "std")]
+use rwb_core::url::Url;
 
-fn main() -> u64 {
-    let client = HttpClient::new();
-    match client.get("host.test".to_string(), 8000, "/test.html".to_string()) {
-        Ok(res) => {
+fn print_response(result: Result<HttpResponse, Error>) {
+    match result {
+        Ok(res) if res.status_code().is_success() => {
             print!("response:\n{:#?}", res);
         }
+        Ok(res) => {
+            // リダイレクトは HttpClient が辿るので、ここに来るのはエラーか辿れなかったもの
+            print!(
+                "error page:\n{} {}\n{}",
+                res.status_code(),
+                res.reason(),
+                res.text_lossy()
+            );
+        }
         Err(e) => {
             print!("error:\n{:#?}", e);
         }
     }
+}
+
+#[cfg(feature = "std")]
+const USAGE: &str = "usage: rwb [host] [port] [path]\n       rwb <url>";
+
+/// What to fetch, as given on the command line.
+#[cfg(feature = "std")]
+enum Target {
+    Url(Url),
+    Origin(String, u16, String),
+}
+
+#[cfg(feature = "std")]
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Target, String> {
+    let first = args.next();
+    let target = match first {
+        Some(arg) if arg.contains("://") => Target::Url(Url::new(arg).map_err(|e| e.to_string())?),
+        first => {
+            let host = first.unwrap_or_else(|| "host.test".to_string());
+            let port = match args.next().map(|port| port.parse::<u16>()) {
+                Some(Ok(port)) => port,
+                Some(Err(e)) => return Err(format!("invalid port: {}", e)),
+                None => 8000,
+            };
+            let path = args.next().unwrap_or_else(|| "/test.html".to_string());
+            Target::Origin(host, port, path)
+        }
+    };
+    if args.next().is_some() {
+        return Err(USAGE.to_string());
+    }
+    Ok(target)
+}
+
+/// Usage: `rwb [host] [port] [path]` or `rwb <url>`. Set `RWB_CACHE_DIR`
+/// to keep the cache on disk between runs, and `RWB_CA_FILE` to trust the
+/// PEM certificates in that file instead of the Mozilla roots.
+#[cfg(feature = "std")]
+fn main() {
+    let target = match parse_args(std::env::args().skip(1)) {
+        Ok(target) => target,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let mut transport = StdTransport::new();
+    if let Some(path) = std::env::var_os("RWB_CA_FILE") {
+        let mut roots = RootStore::new();
+        match std::fs::read(path).map(|pem| roots.add_pem(&pem)) {
+            Ok(Ok(_)) => transport.set_root_store(&roots),
+            Ok(Err(e)) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+            Err(e) => {
+                eprintln!("failed to read RWB_CA_FILE: {}", e);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let mut client = HttpClient::new(transport);
+    client.set_clock(system_clock);
+    if let Some(dir) = std::env::var_os("RWB_CACHE_DIR") {
+        match DiskStorage::new(dir) {
+            Ok(storage) => client.set_cache_storage(Box::new(storage)),
+            // キャッシュが使えなくても取得はできる
+            Err(e) => eprintln!("cache disabled: {}", e),
+        }
+    }
+    let result = match target {
+        Target::Url(url) => client.get_url(&url),
+        Target::Origin(host, port, path) => client.get(host, port, path),
+    };
+    print_response(result);
+    println!();
+}
+
+/// Fetches the test page from the host. noli has no wall clock to pass to
+/// `HttpClient::set_clock`, so this build has no HTTP cache, keeps only
+/// session cookies and never closes idle connections; build with the `std`
+/// feature for those.
+#[cfg(not(feature = "std"))]
+fn main() -> u64 {
+    let client = HttpClient::new(WasabiTransport::new());
+    print_response(client.get("host.test".to_string(), 8000, "/test.html".to_string()));
     0
 }
 
+#[cfg(not(feature = "std"))]
 entry_point!(main);